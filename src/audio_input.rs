@@ -0,0 +1,85 @@
+// src/audio_input.rs
+// External audio input, for playing a live source through the effects. The default input
+// device is captured, downmixed to mono, into a bounded queue that the output callback drains
+// once per buffer. Neither callback blocks on the other: the input drops a block if the queue
+// is busy, and the output plays silence for samples that haven't arrived.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample, StreamConfig};
+
+/// Most samples kept waiting (about 170 ms at 48 kHz); past that the oldest are dropped, which
+/// bounds the latency if the input clock runs slightly fast
+pub const INPUT_QUEUE: usize = 8192;
+
+pub type InputQueue = Mutex<VecDeque<f32>>;
+
+pub fn new_queue() -> InputQueue {
+    Mutex::new(VecDeque::with_capacity(INPUT_QUEUE))
+}
+
+/// Open the default input device at `sample_rate` and start feeding `queue`. Returns the
+/// running stream (capture stops when it's dropped) and the device name.
+pub fn start(
+    host: &cpal::Host,
+    sample_rate: u32,
+    queue: Arc<InputQueue>,
+) -> Result<(cpal::Stream, String)> {
+    let device = host
+        .default_input_device()
+        .ok_or_else(|| anyhow::anyhow!("no input device"))?;
+    let cfg = device.default_input_config()?;
+    // there is no resampler; the input has to run at the output's rate
+    if cfg.sample_rate().0 != sample_rate {
+        anyhow::bail!(
+            "input runs at {} Hz but the output at {} Hz",
+            cfg.sample_rate().0,
+            sample_rate
+        );
+    }
+    let config: StreamConfig = cfg.clone().into();
+    let stream = match cfg.sample_format() {
+        SampleFormat::F32 => build::<f32>(&device, &config, queue)?,
+        SampleFormat::I16 => build::<i16>(&device, &config, queue)?,
+        SampleFormat::U16 => build::<u16>(&device, &config, queue)?,
+        _ => anyhow::bail!("unsupported input sample format"),
+    };
+    stream.play()?;
+    Ok((
+        stream,
+        device.name().unwrap_or_else(|_| "input".to_string()),
+    ))
+}
+
+fn build<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    queue: Arc<InputQueue>,
+) -> Result<cpal::Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = (config.channels as usize).max(1);
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], _| {
+            let Ok(mut q) = queue.try_lock() else {
+                return;
+            };
+            for frame in data.chunks(channels) {
+                let sum: f32 = frame.iter().map(|s| s.to_sample::<f32>()).sum();
+                if q.len() >= INPUT_QUEUE {
+                    q.pop_front();
+                }
+                q.push_back(sum / channels as f32);
+            }
+        },
+        |err| eprintln!("audio input err: {}", err),
+        None,
+    )?;
+    Ok(stream)
+}
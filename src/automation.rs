@@ -0,0 +1,169 @@
+// src/automation.rs
+// Parameter automation: records timestamped parameter changes for one loop length and then
+// replays them on repeat. Times are seconds on the audio sample clock, so a take keeps its
+// timing however fast the UI happens to be polling (changes are still applied per UI frame).
+
+/// What the automation loop is doing, for the UI
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AutomationState {
+    Idle,
+    Recording,
+    Playing,
+}
+
+impl AutomationState {
+    pub fn label(self) -> &'static str {
+        match self {
+            AutomationState::Idle => "idle",
+            AutomationState::Recording => "recording",
+            AutomationState::Playing => "playing",
+        }
+    }
+}
+
+/// Automation lane for parameters identified by `K`
+pub struct Automation<K> {
+    // (seconds into the loop, parameter, value), in time order
+    events: Vec<(f32, K, f32)>,
+    state: AutomationState,
+    // clock time the current pass started
+    start: f32,
+    // loop position already played up to
+    played: f32,
+}
+
+impl<K: Copy> Automation<K> {
+    pub fn new() -> Self {
+        Self {
+            events: Vec::new(),
+            state: AutomationState::Idle,
+            start: 0.0,
+            played: 0.0,
+        }
+    }
+
+    pub fn state(&self) -> AutomationState {
+        self.state
+    }
+
+    pub fn event_count(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Start a new take at clock time `now`, replacing what was recorded before
+    pub fn record(&mut self, now: f32) {
+        self.events.clear();
+        self.state = AutomationState::Recording;
+        self.start = now;
+    }
+
+    /// Loop the recorded changes from the top
+    pub fn play(&mut self, now: f32) {
+        self.state = AutomationState::Playing;
+        self.start = now;
+        self.played = 0.0;
+    }
+
+    pub fn stop(&mut self) {
+        self.state = AutomationState::Idle;
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+        self.state = AutomationState::Idle;
+    }
+
+    /// Note a parameter change while recording (ignored otherwise)
+    pub fn capture(&mut self, now: f32, key: K, value: f32) {
+        if self.state == AutomationState::Recording {
+            self.events.push((now - self.start, key, value));
+        }
+    }
+
+    /// How far through the take or loop `now` is, 0..1
+    pub fn position(&self, now: f32, length: f32) -> f32 {
+        match self.state {
+            AutomationState::Idle => 0.0,
+            _ => ((now - self.start) / length).rem_euclid(1.0),
+        }
+    }
+
+    /// Bring playback up to clock time `now`, handing every change that came due to `apply`.
+    /// A finished take switches straight to playback.
+    pub fn advance(&mut self, now: f32, length: f32, mut apply: impl FnMut(K, f32)) {
+        let length = length.max(0.01);
+        if self.state == AutomationState::Recording && now - self.start >= length {
+            self.events.retain(|&(t, _, _)| t < length);
+            self.play(self.start + length);
+        }
+        if self.state != AutomationState::Playing {
+            return;
+        }
+        let pos = (now - self.start).rem_euclid(length);
+        let due = |t: f32, from: f32, to: f32| t >= from && t < to;
+        let (played, wrapped) = (self.played, pos < self.played);
+        for &(t, key, value) in &self.events {
+            let fire = if wrapped {
+                due(t, played, length) || due(t, 0.0, pos)
+            } else {
+                due(t, played, pos)
+            };
+            if fire {
+                apply(key, value);
+            }
+        }
+        self.played = pos;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Times (clock seconds) at which each change is applied while polling every 10 ms
+    fn replay(auto: &mut Automation<u8>, from: f32, to: f32, length: f32) -> Vec<(f32, u8, f32)> {
+        let mut out = Vec::new();
+        let mut now = from;
+        while now < to {
+            auto.advance(now, length, |k, v| out.push((now, k, v)));
+            now += 0.01;
+        }
+        out
+    }
+
+    #[test]
+    fn take_replays_in_a_loop() {
+        let mut auto = Automation::new();
+        auto.record(10.0);
+        auto.capture(10.25, 1, 0.5);
+        auto.capture(11.5, 2, 0.9);
+        // the take ends one loop length (2 s) after it started and plays from there
+        let fired = replay(&mut auto, 10.0, 16.0, 2.0);
+        assert_eq!(auto.state(), AutomationState::Playing);
+        let keys: Vec<u8> = fired.iter().map(|&(_, k, _)| k).collect();
+        assert_eq!(keys, [1, 2, 1, 2]);
+        for (&(at, _, _), expected) in fired.iter().zip([12.25, 13.5, 14.25, 15.5]) {
+            assert!((at - expected).abs() < 0.015, "{} vs {}", at, expected);
+        }
+    }
+
+    #[test]
+    fn events_at_the_loop_start_fire_on_every_pass() {
+        let mut auto = Automation::new();
+        auto.record(0.0);
+        auto.capture(0.0, 7, 1.0);
+        let fired = replay(&mut auto, 0.0, 4.95, 1.0);
+        assert_eq!(fired.len(), 4);
+    }
+
+    #[test]
+    fn clear_forgets_the_take() {
+        let mut auto = Automation::new();
+        auto.record(0.0);
+        auto.capture(0.5, 1, 0.2);
+        auto.clear();
+        assert_eq!(auto.event_count(), 0);
+        assert_eq!(auto.state(), AutomationState::Idle);
+        assert!(replay(&mut auto, 0.0, 3.0, 1.0).is_empty());
+    }
+}
@@ -0,0 +1,62 @@
+// src/base64.rs
+// Base64 for patch codes: the URL-safe alphabet without padding, so a code survives being
+// pasted into forums, chat and links unchanged.
+
+use anyhow::Result;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+pub fn encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        // 3 bytes -> 4 characters, 2 -> 3, 1 -> 2
+        for i in 0..=chunk.len() {
+            out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+        }
+    }
+    out
+}
+
+/// Decode `text`, ignoring whitespace (codes get wrapped when pasted)
+pub fn decode(text: &str) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut n, mut bits) = (0u32, 0);
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let Some(v) = ALPHABET.iter().position(|&a| a == c) else {
+            anyhow::bail!("unexpected character {:?}", c as char);
+        };
+        n = n << 6 | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+        }
+    }
+    // a single leftover character can't come from any whole byte
+    if bits >= 6 {
+        anyhow::bail!("truncated");
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_every_length_and_rejects_garbage() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        for len in 0..8 {
+            let code = encode(&bytes[250 - len..250]);
+            assert_eq!(decode(&code).unwrap(), &bytes[250 - len..250]);
+        }
+        assert_eq!(encode(b"synth"), "c3ludGg");
+        assert_eq!(decode("c3lu\n dGg").unwrap(), b"synth");
+        assert!(decode("c3l=").is_err());
+        assert!(decode("c3ludGgA1").is_err());
+    }
+}
@@ -0,0 +1,415 @@
+// src/compressor.rs
+// Master-bus compressor: a detector follows the level of the louder channel, a gain computer
+// turns the level over the threshold into gain reduction (dB), and attack/release smoothing
+// shapes how fast that reduction comes and goes. Both channels share the gain. State lives
+// in the audio callback.
+//
+// A sidechain envelope can add its own reduction on top: retriggered every beat (or every
+// sequencer step), it ducks the whole mix and lets it swell back, for the pumping sound of a
+// kick keying the compressor, without needing a kick.
+
+/// Averaging time of the RMS detector
+const RMS_WINDOW_SECS: f32 = 0.01;
+/// Width of the soft knee, centered on the threshold
+const SOFT_KNEE_DB: f32 = 6.0;
+/// Time the sidechain takes to duck fully once triggered, so the drop doesn't click
+const DUCK_ATTACK_SECS: f32 = 0.002;
+
+/// How the detector measures level
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Detector {
+    /// The instantaneous peak: catches every transient
+    Peak,
+    /// The average power over `RMS_WINDOW_SECS`: smoother, closer to perceived loudness
+    Rms,
+}
+
+impl Detector {
+    pub const ALL: [Detector; 2] = [Detector::Peak, Detector::Rms];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL.get(v as usize).copied().unwrap_or(Detector::Peak)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Detector::Peak => "peak",
+            Detector::Rms => "RMS",
+        }
+    }
+}
+
+/// How compression starts around the threshold
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Knee {
+    /// Full ratio the moment the level crosses the threshold
+    Hard,
+    /// The ratio eases in across `SOFT_KNEE_DB`
+    Soft,
+}
+
+impl Knee {
+    pub const ALL: [Knee; 2] = [Knee::Hard, Knee::Soft];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL.get(v as usize).copied().unwrap_or(Knee::Hard)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Knee::Hard => "hard",
+            Knee::Soft => "soft",
+        }
+    }
+}
+
+/// What retriggers the sidechain envelope
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DuckTrigger {
+    Off,
+    /// Every beat of the tempo
+    Beat,
+    /// Every sequencer step, while it runs (the engine calls `trigger_duck`)
+    Step,
+}
+
+impl DuckTrigger {
+    pub const ALL: [DuckTrigger; 3] = [DuckTrigger::Off, DuckTrigger::Beat, DuckTrigger::Step];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL
+            .get(v as usize)
+            .copied()
+            .unwrap_or(DuckTrigger::Off)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DuckTrigger::Off => "off",
+            DuckTrigger::Beat => "each beat",
+            DuckTrigger::Step => "each step",
+        }
+    }
+}
+
+/// How the ducked level comes back up
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DuckShape {
+    /// A steady climb back
+    Linear,
+    /// Most of the level back straight away, then a slow tail: a tight pump
+    Exponential,
+    /// Stays down, then rises in a smooth S: the deep, breathing pump
+    Smooth,
+}
+
+impl DuckShape {
+    pub const ALL: [DuckShape; 3] = [DuckShape::Linear, DuckShape::Exponential, DuckShape::Smooth];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL
+            .get(v as usize)
+            .copied()
+            .unwrap_or(DuckShape::Linear)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DuckShape::Linear => "linear",
+            DuckShape::Exponential => "exponential",
+            DuckShape::Smooth => "smooth",
+        }
+    }
+
+    /// Share of the full duck still applied `t` (0..1) of the way through the recovery
+    fn depth(self, t: f32) -> f32 {
+        let left = 1.0 - t.clamp(0.0, 1.0);
+        match self {
+            DuckShape::Linear => left,
+            DuckShape::Exponential => left.powi(4),
+            DuckShape::Smooth => 0.5 - 0.5 * (std::f32::consts::PI * left).cos(),
+        }
+    }
+}
+
+/// Sidechain ducking settings, part of `CompParams`
+#[derive(Clone, Copy, Debug)]
+pub struct DuckParams {
+    pub trigger: DuckTrigger,
+    pub beat_rate: f32, // beats per second (already converted from the tempo)
+    pub amount: f32,    // dB of reduction at the bottom of the duck
+    pub length: f32,    // recovery time as a fraction of the time between triggers, 0..1
+    pub shape: DuckShape,
+}
+
+impl DuckParams {
+    /// Recovery time in seconds
+    fn recovery(&self) -> f32 {
+        let per_second = match self.trigger {
+            // the sequencer runs in 16ths
+            DuckTrigger::Step => 4.0 * self.beat_rate,
+            _ => self.beat_rate,
+        };
+        self.length.clamp(0.01, 1.0) / per_second.max(1e-3)
+    }
+}
+
+/// Compressor settings read once per audio buffer
+#[derive(Clone, Copy, Debug)]
+pub struct CompParams {
+    pub threshold: f32, // dBFS
+    pub ratio: f32,     // input dB over the threshold per output dB
+    pub attack: f32,    // s
+    pub release: f32,   // s
+    pub makeup: f32,    // dB
+    pub detector: Detector,
+    pub knee: Knee,
+    pub duck: DuckParams,
+}
+
+impl CompParams {
+    /// Gain reduction (dB, positive) for a detected `level_db`
+    fn reduction(&self, level_db: f32) -> f32 {
+        let over = level_db - self.threshold;
+        let slope = 1.0 - 1.0 / self.ratio.max(1.0);
+        match self.knee {
+            Knee::Soft if over.abs() <= SOFT_KNEE_DB / 2.0 => {
+                slope * (over + SOFT_KNEE_DB / 2.0).powi(2) / (2.0 * SOFT_KNEE_DB)
+            }
+            _ => slope * over.max(0.0),
+        }
+    }
+}
+
+pub struct Compressor {
+    // mean square for the RMS detector
+    power: f32,
+    // smoothed gain reduction, dB
+    reduction: f32,
+    rms_coef: f32,
+    attack_coef: f32,
+    release_coef: f32,
+    // sidechain: position in the beat (0..1, 1 = due) for beat triggers, samples since the last
+    // trigger (None once recovered), and the reduction it's applying (dB)
+    beat: f32,
+    since_duck: Option<f32>,
+    duck: f32,
+    sample_rate: f32,
+}
+
+/// One-pole coefficient for a time constant of `secs`
+fn coef(secs: f32, sample_rate: f32) -> f32 {
+    1.0 - (-1.0 / (secs.max(1e-5) * sample_rate)).exp()
+}
+
+impl Compressor {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            power: 0.0,
+            reduction: 0.0,
+            rms_coef: coef(RMS_WINDOW_SECS, sample_rate),
+            attack_coef: 1.0,
+            release_coef: 1.0,
+            beat: 1.0,
+            since_duck: None,
+            duck: 0.0,
+            sample_rate,
+        }
+    }
+
+    /// Forget the detector level, the gain reduction and any duck in progress (after a fault,
+    /// or while switched off so it starts clean)
+    pub fn clear(&mut self) {
+        self.power = 0.0;
+        self.reduction = 0.0;
+        self.beat = 1.0;
+        self.since_duck = None;
+        self.duck = 0.0;
+    }
+
+    /// Restart the sidechain envelope (each sequencer step, in `DuckTrigger::Step`)
+    pub fn trigger_duck(&mut self) {
+        self.since_duck = Some(0.0);
+    }
+
+    /// A beat of the running transport starts now: duck, and count `DuckTrigger::Beat`'s
+    /// beats on from here, so the pump lands on the sequencer's (and MIDI clock's) beats
+    /// rather than wherever the compressor happened to be switched on
+    pub fn sync_beat(&mut self) {
+        self.beat = 0.0;
+        self.trigger_duck();
+    }
+
+    /// Current gain reduction in dB (positive), sidechain included, for the meter
+    pub fn reduction(&self) -> f32 {
+        self.reduction + self.duck
+    }
+
+    /// Update the attack/release smoothing from `p`; call once per buffer
+    pub fn set(&mut self, p: &CompParams) {
+        self.attack_coef = coef(p.attack, self.sample_rate);
+        self.release_coef = coef(p.release, self.sample_rate);
+    }
+
+    pub fn process(&mut self, l: f32, r: f32, p: &CompParams) -> (f32, f32) {
+        let peak = l.abs().max(r.abs());
+        self.power += (peak * peak - self.power) * self.rms_coef;
+        let level = match p.detector {
+            Detector::Peak => peak,
+            Detector::Rms => self.power.sqrt(),
+        };
+        let target = p.reduction(20.0 * level.max(1e-9).log10());
+        let k = if target > self.reduction {
+            self.attack_coef
+        } else {
+            self.release_coef
+        };
+        self.reduction += (target - self.reduction) * k;
+        self.duck = self.sidechain(&p.duck);
+        let gain = 10f32.powf((p.makeup - self.reduction - self.duck) / 20.0);
+        (l * gain, r * gain)
+    }
+
+    /// Sidechain reduction (dB) for this sample
+    fn sidechain(&mut self, d: &DuckParams) -> f32 {
+        if d.trigger == DuckTrigger::Beat {
+            if self.beat >= 1.0 {
+                self.beat -= 1.0;
+                self.trigger_duck();
+            }
+            self.beat += d.beat_rate / self.sample_rate;
+        } else {
+            // primed, so the first beat lands on the first sample
+            self.beat = 1.0;
+        }
+        if d.trigger == DuckTrigger::Off {
+            self.since_duck = None;
+        }
+        let Some(n) = self.since_duck else {
+            return 0.0;
+        };
+        let t = n / self.sample_rate;
+        let recovery = d.recovery();
+        if t >= recovery {
+            self.since_duck = None;
+            return 0.0;
+        }
+        self.since_duck = Some(n + 1.0);
+        let attack = (t / DUCK_ATTACK_SECS).min(1.0);
+        d.amount * attack * d.shape.depth(t / recovery)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SR: f32 = 48_000.0;
+
+    fn params(detector: Detector, knee: Knee) -> CompParams {
+        CompParams {
+            threshold: -20.0,
+            ratio: 4.0,
+            attack: 0.001,
+            release: 0.1,
+            makeup: 0.0,
+            detector,
+            knee,
+            duck: DuckParams {
+                trigger: DuckTrigger::Off,
+                beat_rate: 2.0,
+                amount: 12.0,
+                length: 0.5,
+                shape: DuckShape::Linear,
+            },
+        }
+    }
+
+    #[test]
+    fn rms_reacts_to_a_transient_more_slowly_than_peak() {
+        // gain reduction 2 ms after a quiet tone jumps to full scale
+        let after_jump = |detector: Detector| {
+            let p = params(detector, Knee::Hard);
+            let mut comp = Compressor::new(SR);
+            comp.set(&p);
+            for n in 0..(0.102 * SR) as usize {
+                let amp = if n < 4_800 { 0.01 } else { 1.0 };
+                let x = amp * (n as f32 * 0.06).sin();
+                comp.process(x, x, &p);
+            }
+            comp.reduction()
+        };
+        let (peak, rms) = (after_jump(Detector::Peak), after_jump(Detector::Rms));
+        assert!(peak > 10.0, "{}", peak);
+        assert!(rms < 0.5 * peak, "{} {}", rms, peak);
+    }
+
+    #[test]
+    fn clearing_recovers_from_a_non_finite_input() {
+        let p = params(Detector::Rms, Knee::Hard);
+        let mut comp = Compressor::new(SR);
+        comp.set(&p);
+        let loud = |comp: &mut Compressor| {
+            for _ in 0..4_800 {
+                comp.process(1.0, 1.0, &p);
+            }
+            comp.reduction()
+        };
+        comp.process(f32::NAN, f32::NAN, &p);
+        // the RMS detector holds on to the NaN and reads silence until cleared
+        assert_eq!(loud(&mut comp), 0.0);
+        comp.clear();
+        assert!(loud(&mut comp) > 10.0);
+    }
+
+    #[test]
+    fn the_soft_knee_eases_in_around_the_threshold() {
+        let (hard, soft) = (
+            params(Detector::Peak, Knee::Hard),
+            params(Detector::Peak, Knee::Soft),
+        );
+        assert_eq!(hard.reduction(-20.0), 0.0);
+        assert!(soft.reduction(-20.0) > 0.0);
+        assert_eq!(soft.reduction(-24.0), 0.0);
+        // well above the knee both follow the ratio: 20 dB over at 4:1 is 15 dB down
+        assert_eq!(hard.reduction(0.0), 15.0);
+        assert_eq!(soft.reduction(0.0), 15.0);
+    }
+
+    #[test]
+    fn the_sidechain_ducks_on_every_beat() {
+        // a quiet tone that never reaches the threshold, ducked each beat at 120 BPM
+        let mut p = params(Detector::Peak, Knee::Hard);
+        p.duck.trigger = DuckTrigger::Beat;
+        let mut comp = Compressor::new(SR);
+        comp.set(&p);
+        let beat = (SR / 2.0) as usize;
+        let reductions: Vec<f32> = (0..4 * beat)
+            .map(|n| {
+                let x = 0.01 * (n as f32 * 0.06).sin();
+                comp.process(x, x, &p);
+                comp.reduction()
+            })
+            .collect();
+        for b in 0..4 {
+            // fully down just after the beat, halfway back a quarter beat in (the recovery is
+            // half a beat, linear) and untouched in the second half
+            let at = |frac: f32| reductions[b * beat + (frac * beat as f32) as usize];
+            assert!((at(0.01) - 12.0).abs() < 0.5, "{}", at(0.01));
+            assert!((at(0.25) - 6.0).abs() < 0.1, "{}", at(0.25));
+            assert_eq!(at(0.75), 0.0);
+        }
+    }
+
+    #[test]
+    fn duck_shapes_run_from_full_depth_to_none() {
+        for shape in DuckShape::ALL {
+            assert_eq!(shape.depth(0.0), 1.0);
+            assert!(shape.depth(1.0).abs() < 1e-6);
+        }
+        // the exponential shape recovers early, the smooth one holds on
+        let (exp, smooth) = (DuckShape::Exponential, DuckShape::Smooth);
+        assert!(exp.depth(0.3) < DuckShape::Linear.depth(0.3));
+        assert!(smooth.depth(0.3) > DuckShape::Linear.depth(0.3));
+    }
+}
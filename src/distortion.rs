@@ -0,0 +1,371 @@
+// src/distortion.rs
+// Drive stage on the bus into the effects: a tanh waveshaper, optionally run at 2x or 4x the
+// sample rate (zero-stuff, low-pass, shape, low-pass, decimate) so the harmonics it creates
+// above Nyquist are filtered out instead of folding back as inharmonic aliases.
+// In multiband mode a crossover first splits the signal into low and high bands, each with its
+// own drive; the high band is the input minus the low one, so the bands always sum back to the
+// input exactly. State lives in the audio callback.
+
+use std::f32::consts::PI;
+
+use crate::dsp::flush_denormal;
+
+/// Oversampling factor around the waveshaper
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Oversample {
+    Off,
+    X2,
+    X4,
+}
+
+impl Oversample {
+    pub const ALL: [Oversample; 3] = [Oversample::Off, Oversample::X2, Oversample::X4];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL
+            .get(v as usize)
+            .copied()
+            .unwrap_or(Oversample::Off)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Oversample::Off => "off",
+            Oversample::X2 => "2x",
+            Oversample::X4 => "4x",
+        }
+    }
+
+    fn factor(self) -> usize {
+        match self {
+            Oversample::Off => 1,
+            Oversample::X2 => 2,
+            Oversample::X4 => 4,
+        }
+    }
+}
+
+/// Distortion settings read once per audio buffer
+#[derive(Clone, Copy, Debug)]
+pub struct DistortionParams {
+    pub drive: f32, // dB into the shaper; 0 = stage bypassed
+    pub oversample: Oversample,
+    // split into two bands (and ignore `drive`), or None for the whole signal
+    pub multiband: Option<Multiband>,
+}
+
+/// Two-band split ahead of the shaper
+#[derive(Clone, Copy, Debug)]
+pub struct Multiband {
+    pub crossover: f32,  // Hz
+    pub low_drive: f32,  // dB; 0 = band passes clean
+    pub high_drive: f32, // dB; 0 = band passes clean
+}
+
+/// Q of each biquad in a 12th-order Butterworth cascade
+const BUTTERWORTH_Q: [f32; 6] = [0.5043, 0.5412, 0.6302, 0.8213, 1.3066, 3.8306];
+/// Anti-aliasing cutoff as a fraction of the base sample rate (just under Nyquist)
+const CUTOFF: f32 = 0.42;
+/// Q of the crossover's low-pass (Butterworth)
+const CROSSOVER_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+#[derive(Clone, Copy)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    /// RBJ low-pass at `freq` (fraction of the rate it runs at)
+    fn lowpass(freq: f32, q: f32) -> Self {
+        let (sin, cos) = (2.0 * PI * freq).sin_cos();
+        let alpha = sin / (2.0 * q);
+        let a0 = 1.0 + alpha;
+        Self {
+            b0: (1.0 - cos) / 2.0 / a0,
+            b1: (1.0 - cos) / a0,
+            b2: (1.0 - cos) / 2.0 / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha) / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    /// New coefficients, keeping the running state (for a moving cutoff)
+    fn retune(&mut self, freq: f32, q: f32) {
+        *self = Self {
+            z1: self.z1,
+            z2: self.z2,
+            ..Self::lowpass(freq, q)
+        };
+    }
+
+    fn clear(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = flush_denormal(self.b1 * x - self.a1 * y + self.z2);
+        self.z2 = flush_denormal(self.b2 * x - self.a2 * y);
+        y
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Lowpass([Biquad; 6]);
+
+impl Lowpass {
+    fn new(freq: f32) -> Self {
+        Self(BUTTERWORTH_Q.map(|q| Biquad::lowpass(freq, q)))
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        self.0.iter_mut().fold(x, |s, b| b.process(s))
+    }
+}
+
+/// One waveshaper with its oversampling filters
+#[derive(Clone, Copy)]
+struct Shaper {
+    up: Lowpass,
+    down: Lowpass,
+}
+
+impl Shaper {
+    fn new(oversample: Oversample) -> Self {
+        let lp = Lowpass::new(CUTOFF / oversample.factor() as f32);
+        Self { up: lp, down: lp }
+    }
+
+    /// tanh of `x` at gain `g`, run at `n` times the sample rate
+    fn process(&mut self, x: f32, g: f32, n: usize) -> f32 {
+        if n == 1 {
+            return (g * x).tanh();
+        }
+        // zero-stuffing spreads the input over n samples; scale by n to keep its level
+        let input = x * n as f32;
+        let mut y = 0.0;
+        for i in 0..n {
+            let s = self.up.process(if i == 0 { input } else { 0.0 });
+            y = self.down.process((g * s).tanh());
+        }
+        y
+    }
+}
+
+pub struct Distortion {
+    // [band][channel]; single-band mode uses band 0
+    shapers: [[Shaper; 2]; 2],
+    oversample: Oversample,
+    // the crossover's low-pass per channel, and the frequency it is tuned to
+    split: [Biquad; 2],
+    crossover: f32,
+    sample_rate: f32,
+}
+
+impl Distortion {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            shapers: [[Shaper::new(Oversample::Off); 2]; 2],
+            oversample: Oversample::Off,
+            split: [Biquad::lowpass(0.25, CROSSOVER_Q); 2],
+            crossover: 0.0,
+            sample_rate,
+        }
+    }
+
+    /// Silence the oversampling and crossover filters, keeping their tuning (after a fault)
+    pub fn clear(&mut self) {
+        let shapers = self.shapers.iter_mut().flatten();
+        let filters = shapers.flat_map(|s| s.up.0.iter_mut().chain(s.down.0.iter_mut()));
+        for b in filters.chain(self.split.iter_mut()) {
+            b.clear();
+        }
+    }
+
+    pub fn process(&mut self, l: f32, r: f32, p: &DistortionParams) -> (f32, f32) {
+        if p.multiband.is_none() && p.drive <= 0.0 {
+            return (l, r);
+        }
+        if p.oversample != self.oversample {
+            // new filters start from rest; switching is an edit, not something automated
+            self.oversample = p.oversample;
+            self.shapers = [[Shaper::new(p.oversample); 2]; 2];
+        }
+        let n = p.oversample.factor();
+        let gain = |drive: f32| 10f32.powf(drive / 20.0);
+        let Some(m) = p.multiband else {
+            let g = gain(p.drive);
+            let [sl, sr] = &mut self.shapers[0];
+            return (sl.process(l, g, n), sr.process(r, g, n));
+        };
+        if m.crossover != self.crossover {
+            self.crossover = m.crossover;
+            let freq = (m.crossover / self.sample_rate).clamp(1e-4, CUTOFF);
+            for b in &mut self.split {
+                b.retune(freq, CROSSOVER_Q);
+            }
+        }
+        let (g_low, g_high) = (gain(m.low_drive), gain(m.high_drive));
+        let mut out = [l, r];
+        for (ch, x) in out.iter_mut().enumerate() {
+            let low = self.split[ch].process(*x);
+            let high = *x - low;
+            let low = if m.low_drive > 0.0 {
+                self.shapers[0][ch].process(low, g_low, n)
+            } else {
+                low
+            };
+            let high = if m.high_drive > 0.0 {
+                self.shapers[1][ch].process(high, g_high, n)
+            } else {
+                high
+            };
+            *x = low + high;
+        }
+        (out[0], out[1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SR: f32 = 48_000.0;
+    const LEN: usize = 4_800; // 10 Hz bins; every tone below lands on an exact bin
+
+    /// Power of DFT bin `k` of `x`
+    fn bin_power(x: &[f32], k: usize) -> f32 {
+        let (mut re, mut im) = (0.0f32, 0.0f32);
+        for (n, &v) in x.iter().enumerate() {
+            let w = 2.0 * PI * ((k * n) % x.len()) as f32 / x.len() as f32;
+            re += v * w.cos();
+            im -= v * w.sin();
+        }
+        re * re + im * im
+    }
+
+    /// Inharmonic (aliased) power relative to harmonic power, in dB, for a 5 kHz sine
+    /// through heavy drive
+    fn alias_db(oversample: Oversample) -> f32 {
+        let mut dist = Distortion::new(SR);
+        let p = DistortionParams {
+            drive: 30.0,
+            oversample,
+            multiband: None,
+        };
+        let hz = 5_000.0;
+        let out: Vec<f32> = (0..2 * LEN)
+            .map(|n| {
+                let x = 0.5 * (2.0 * PI * hz * n as f32 / SR).sin();
+                dist.process(x, x, &p).0
+            })
+            .collect();
+        // skip the filters' settling
+        let tail = &out[LEN..];
+        let harmonic_step = (hz / 10.0) as usize;
+        let (mut harmonic, mut alias) = (0.0, 0.0);
+        for k in 1..LEN / 2 {
+            if k.is_multiple_of(harmonic_step) {
+                harmonic += bin_power(tail, k);
+            } else {
+                alias += bin_power(tail, k);
+            }
+        }
+        10.0 * (alias / harmonic).log10()
+    }
+
+    #[test]
+    fn oversampling_reduces_aliasing() {
+        let off = alias_db(Oversample::Off);
+        let x2 = alias_db(Oversample::X2);
+        let x4 = alias_db(Oversample::X4);
+        assert!(x2 < off - 10.0, "off {:.1} dB, 2x {:.1} dB", off, x2);
+        assert!(x4 < x2 - 6.0, "2x {:.1} dB, 4x {:.1} dB", x2, x4);
+    }
+
+    #[test]
+    fn clearing_recovers_from_a_non_finite_input() {
+        let mut dist = Distortion::new(SR);
+        let p = DistortionParams {
+            drive: 12.0,
+            oversample: Oversample::X4,
+            multiband: None,
+        };
+        dist.process(f32::NAN, f32::NAN, &p);
+        assert!(dist.process(0.3, 0.3, &p).0.is_nan());
+        dist.clear();
+        assert!(dist.process(0.3, 0.3, &p).0.is_finite());
+    }
+
+    #[test]
+    fn zero_drive_bypasses() {
+        let mut dist = Distortion::new(SR);
+        let p = DistortionParams {
+            drive: 0.0,
+            oversample: Oversample::X4,
+            multiband: None,
+        };
+        assert_eq!(dist.process(0.3, -0.7, &p), (0.3, -0.7));
+    }
+
+    fn multiband(low_drive: f32, high_drive: f32) -> DistortionParams {
+        DistortionParams {
+            drive: 0.0,
+            oversample: Oversample::Off,
+            multiband: Some(Multiband {
+                crossover: 1_000.0,
+                low_drive,
+                high_drive,
+            }),
+        }
+    }
+
+    #[test]
+    fn undriven_bands_sum_back_to_the_input() {
+        let mut dist = Distortion::new(SR);
+        let p = multiband(0.0, 0.0);
+        for n in 0..LEN {
+            let t = n as f32 / SR;
+            let x = 0.4 * (2.0 * PI * 150.0 * t).sin() + 0.3 * (2.0 * PI * 4_000.0 * t).sin();
+            let (l, r) = dist.process(x, -x, &p);
+            assert!(
+                (l - x).abs() < 1e-6 && (r + x).abs() < 1e-6,
+                "{} vs {}",
+                l,
+                x
+            );
+        }
+    }
+
+    /// Power of the 3rd harmonic relative to the fundamental, in dB, for a sine at `hz`
+    fn third_harmonic_db(p: &DistortionParams, hz: f32) -> f32 {
+        let mut dist = Distortion::new(SR);
+        let out: Vec<f32> = (0..2 * LEN)
+            .map(|n| {
+                let x = 0.5 * (2.0 * PI * hz * n as f32 / SR).sin();
+                dist.process(x, x, p).0
+            })
+            .collect();
+        let k = (hz / 10.0) as usize;
+        let tail = &out[LEN..];
+        10.0 * (bin_power(tail, 3 * k) / bin_power(tail, k)).log10()
+    }
+
+    #[test]
+    fn only_the_driven_band_distorts() {
+        let p = multiband(0.0, 24.0);
+        let low = third_harmonic_db(&p, 50.0);
+        let high = third_harmonic_db(&p, 4_000.0);
+        assert!(low < -30.0, "{:.1} dB", low);
+        assert!(high > -20.0, "{:.1} dB", high);
+    }
+}
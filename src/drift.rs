@@ -0,0 +1,44 @@
+// src/drift.rs
+// Slow random pitch wander ("analog drift"): a random target that is picked every so often
+// and glided towards, giving smooth, aperiodic movement in -1..1.
+
+use crate::rng::Rng;
+
+/// How often a new drift target is chosen (seconds)
+const RETARGET_SECS: f32 = 0.4;
+/// Time constant of the glide towards the target (seconds)
+const GLIDE_SECS: f32 = 0.5;
+
+pub struct Drift {
+    rng: Rng,
+    target: f32,
+    value: f32,
+    timer: f32,
+}
+
+impl Drift {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            target: 0.0,
+            value: 0.0,
+            timer: 0.0,
+        }
+    }
+
+    /// Restart the random sequence from `seed`, as if newly built with it
+    pub fn reseed(&mut self, seed: u32) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// Advance by `dt` seconds (called once per buffer); returns the offset in -1..1
+    pub fn advance(&mut self, dt: f32) -> f32 {
+        self.timer -= dt;
+        if self.timer <= 0.0 {
+            self.target = self.rng.bipolar();
+            self.timer += RETARGET_SECS;
+        }
+        self.value += (self.target - self.value) * (1.0 - (-dt / GLIDE_SECS).exp());
+        self.value
+    }
+}
@@ -0,0 +1,95 @@
+// src/dsp.rs
+// Small numeric helpers shared by the DSP modules.
+
+/// Magnitudes below this are treated as zero in feedback state (about -300 dBFS, far below
+/// anything audible but well above the f32 subnormal range)
+const DENORMAL_THRESHOLD: f32 = 1e-15;
+
+/// Flush tiny values to zero. Decaying feedback paths (delay lines, filter integrators) would
+/// otherwise settle into subnormal floats during silence, which many CPUs process dramatically
+/// slower; call this on values written back into feedback state.
+#[inline]
+pub fn flush_denormal(x: f32) -> f32 {
+    if x.abs() < DENORMAL_THRESHOLD {
+        0.0
+    } else {
+        x
+    }
+}
+
+/// Constant-power pan law for `pan` in -1 (left)..1 (right), scaled so the center position
+/// is unity on both channels (a centered source sounds exactly as it did before panning)
+pub fn pan_gains(pan: f32) -> (f32, f32) {
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * std::f32::consts::FRAC_PI_4;
+    (
+        angle.cos() * std::f32::consts::SQRT_2,
+        angle.sin() * std::f32::consts::SQRT_2,
+    )
+}
+
+/// Normalized cross-correlation of two channels from their sums of products (L·R, L², R²):
+/// +1 identical, 0 unrelated, -1 inverted (cancels when summed to mono). Silence reads 0.
+pub fn correlation(lr: f32, ll: f32, rr: f32) -> f32 {
+    let energy = (ll * rr).sqrt();
+    if energy < 1e-12 {
+        0.0
+    } else {
+        (lr / energy).clamp(-1.0, 1.0)
+    }
+}
+
+/// `current` moved toward `target` by at most `max_step`
+#[inline]
+pub fn slew(current: f32, target: f32, max_step: f32) -> f32 {
+    current + (target - current).clamp(-max_step, max_step)
+}
+
+/// Polynomial sine for phase 0..1 (a cycle): a parabola per half-cycle with one refinement
+/// step, at most 0.0011 from the true sine at a fraction of the cost
+pub fn fast_sine(phase: f32) -> f32 {
+    // u in -1..1 covers the cycle shifted by half, so the result is negated
+    let u = 2.0 * phase - 1.0;
+    let y = 4.0 * u * (1.0 - u.abs());
+    -(0.225 * (y * y.abs() - y) + y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn correlation_spans_identical_to_inverted() {
+        let sums = |f: &dyn Fn(f32) -> (f32, f32)| {
+            (0..480).fold((0.0, 0.0, 0.0), |(lr, ll, rr), n| {
+                let (l, r) = f((n as f32 * 0.1).sin());
+                (lr + l * r, ll + l * l, rr + r * r)
+            })
+        };
+        let (lr, ll, rr) = sums(&|x| (x, 0.5 * x));
+        assert!((correlation(lr, ll, rr) - 1.0).abs() < 1e-4);
+        let (lr, ll, rr) = sums(&|x| (x, -x));
+        assert!((correlation(lr, ll, rr) + 1.0).abs() < 1e-4);
+        let (lr, ll, rr) = sums(&|x| (x, 0.0));
+        assert_eq!(correlation(lr, ll, rr), 0.0);
+    }
+
+    #[test]
+    fn only_values_below_the_threshold_are_flushed() {
+        assert_eq!(flush_denormal(1e-16), 0.0);
+        assert_eq!(flush_denormal(-1e-16), 0.0);
+        assert_eq!(flush_denormal(f32::MIN_POSITIVE / 2.0), 0.0);
+        assert_eq!(flush_denormal(1e-14), 1e-14);
+        assert_eq!(flush_denormal(-0.5), -0.5);
+    }
+
+    #[test]
+    fn fast_sine_is_within_its_documented_error() {
+        let worst = (0..10_000)
+            .map(|i| {
+                let phase = i as f32 / 10_000.0;
+                (fast_sine(phase) - (phase * std::f32::consts::TAU).sin()).abs()
+            })
+            .fold(0.0f32, f32::max);
+        assert!(worst <= 0.0011, "{}", worst);
+    }
+}
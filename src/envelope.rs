@@ -0,0 +1,164 @@
+// src/envelope.rs
+// Delay-Attack-Hold-Decay-Sustain-Release envelope. Lives in the audio callback closure.
+// Each voice runs two: one for amplitude and a modulation envelope routed by `ModEnvTarget`.
+
+use serde::{Deserialize, Serialize};
+
+/// Envelope stage (state machine position)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stage {
+    Idle,
+    Delay,
+    Attack,
+    Hold,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/// What the modulation envelope drives
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum ModEnvTarget {
+    #[default]
+    Off,
+    Cutoff,
+    Pitch,
+    OscMix,
+}
+
+impl ModEnvTarget {
+    pub const ALL: [ModEnvTarget; 4] = [
+        ModEnvTarget::Off,
+        ModEnvTarget::Cutoff,
+        ModEnvTarget::Pitch,
+        ModEnvTarget::OscMix,
+    ];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL
+            .get(v as usize)
+            .copied()
+            .unwrap_or(ModEnvTarget::Off)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ModEnvTarget::Off => "off",
+            ModEnvTarget::Cutoff => "cutoff",
+            ModEnvTarget::Pitch => "pitch",
+            ModEnvTarget::OscMix => "osc mix",
+        }
+    }
+}
+
+/// Envelope settings: times in seconds, sustain as a 0..=1 level
+#[derive(Clone, Copy, Debug)]
+pub struct EnvParams {
+    pub delay: f32,
+    pub attack: f32,
+    pub hold: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+/// Linear DAHDSR envelope. Zero-length stages are skipped within the same sample,
+/// so with `delay == 0` and `hold == 0` this is a plain ADSR.
+pub struct Envelope {
+    stage: Stage,
+    level: f32,
+    // seconds spent in the current stage
+    timer: f32,
+    // level at the moment the current stage was entered (ramps start from here)
+    from: f32,
+}
+
+impl Envelope {
+    pub fn new() -> Self {
+        Self {
+            stage: Stage::Idle,
+            level: 0.0,
+            timer: 0.0,
+            from: 0.0,
+        }
+    }
+
+    /// Restart from the delay stage (new note or retrigger). Ramps start from the current
+    /// level, so retriggering a sounding voice doesn't click.
+    pub fn trigger(&mut self) {
+        self.enter(Stage::Delay);
+    }
+
+    /// Finished (or never started): the voice using this envelope is silent
+    pub fn is_idle(&self) -> bool {
+        self.stage == Stage::Idle
+    }
+
+    fn enter(&mut self, stage: Stage) {
+        self.stage = stage;
+        self.timer = 0.0;
+        self.from = self.level;
+    }
+
+    /// Advance one sample and return the current level (0..=1)
+    pub fn process(&mut self, gate: bool, p: &EnvParams, sample_rate: f32) -> f32 {
+        match (gate, self.stage) {
+            (true, Stage::Idle | Stage::Release) => self.enter(Stage::Delay),
+            (false, Stage::Idle | Stage::Release) => {}
+            (false, _) => self.enter(Stage::Release),
+            _ => {}
+        }
+
+        loop {
+            match self.stage {
+                Stage::Idle => {
+                    self.level = 0.0;
+                    break;
+                }
+                Stage::Delay => {
+                    if self.timer < p.delay {
+                        break;
+                    }
+                    self.enter(Stage::Attack);
+                }
+                Stage::Attack => {
+                    if self.timer < p.attack {
+                        self.level = self.from + (1.0 - self.from) * (self.timer / p.attack);
+                        break;
+                    }
+                    self.level = 1.0;
+                    self.enter(Stage::Hold);
+                }
+                Stage::Hold => {
+                    if self.timer < p.hold {
+                        break;
+                    }
+                    self.enter(Stage::Decay);
+                }
+                Stage::Decay => {
+                    if self.timer < p.decay {
+                        self.level = 1.0 - (1.0 - p.sustain) * (self.timer / p.decay);
+                        break;
+                    }
+                    self.level = p.sustain;
+                    self.enter(Stage::Sustain);
+                }
+                Stage::Sustain => {
+                    self.level = p.sustain;
+                    break;
+                }
+                Stage::Release => {
+                    if self.timer < p.release {
+                        self.level = self.from * (1.0 - self.timer / p.release);
+                        break;
+                    }
+                    self.level = 0.0;
+                    self.enter(Stage::Idle);
+                }
+            }
+        }
+
+        self.timer += 1.0 / sample_rate;
+        self.level
+    }
+}
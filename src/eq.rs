@@ -0,0 +1,208 @@
+// src/eq.rs
+// Three-band master EQ: low shelf, mid peak, high shelf, each a biquad from the RBJ audio
+// EQ cookbook (transposed direct form II). State lives in the audio callback.
+
+use std::f32::consts::PI;
+
+use crate::dsp::flush_denormal;
+
+/// Bandwidth of the mid band
+const MID_Q: f32 = 0.7;
+/// Shelf slope (1 = steepest without overshoot)
+const SHELF_SLOPE: f32 = 1.0;
+
+/// EQ settings read once per audio buffer
+#[derive(Clone, Copy, Debug)]
+pub struct EqParams {
+    pub low_gain: f32, // dB
+    pub low_freq: f32, // Hz
+    pub mid_gain: f32,
+    pub mid_freq: f32,
+    pub high_gain: f32,
+    pub high_freq: f32,
+}
+
+#[derive(Clone, Copy)]
+enum Shape {
+    LowShelf,
+    Peak,
+    HighShelf,
+}
+
+#[derive(Clone, Copy)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    const IDENTITY: Biquad = Biquad {
+        b0: 1.0,
+        b1: 0.0,
+        b2: 0.0,
+        a1: 0.0,
+        a2: 0.0,
+        z1: 0.0,
+        z2: 0.0,
+    };
+
+    /// Recompute coefficients, keeping the filter state
+    fn set(&mut self, shape: Shape, freq: f32, gain_db: f32, sample_rate: f32) {
+        let a = 10f32.powf(gain_db / 40.0);
+        let w0 = 2.0 * PI * freq.clamp(20.0, 0.45 * sample_rate) / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let (b0, b1, b2, a0, a1, a2) = match shape {
+            Shape::Peak => {
+                let alpha = sin / (2.0 * MID_Q);
+                (
+                    1.0 + alpha * a,
+                    -2.0 * cos,
+                    1.0 - alpha * a,
+                    1.0 + alpha / a,
+                    -2.0 * cos,
+                    1.0 - alpha / a,
+                )
+            }
+            Shape::LowShelf | Shape::HighShelf => {
+                let alpha = sin / 2.0 * ((a + 1.0 / a) * (1.0 / SHELF_SLOPE - 1.0) + 2.0).sqrt();
+                let k = 2.0 * a.sqrt() * alpha;
+                // the high shelf is the low shelf with the sign of cos flipped
+                let (s, c) = match shape {
+                    Shape::LowShelf => (1.0, cos),
+                    _ => (-1.0, -cos),
+                };
+                (
+                    a * ((a + 1.0) - (a - 1.0) * c + k),
+                    s * 2.0 * a * ((a - 1.0) - (a + 1.0) * c),
+                    a * ((a + 1.0) - (a - 1.0) * c - k),
+                    (a + 1.0) + (a - 1.0) * c + k,
+                    s * -2.0 * ((a - 1.0) + (a + 1.0) * c),
+                    (a + 1.0) + (a - 1.0) * c - k,
+                )
+            }
+        };
+        self.b0 = b0 / a0;
+        self.b1 = b1 / a0;
+        self.b2 = b2 / a0;
+        self.a1 = a1 / a0;
+        self.a2 = a2 / a0;
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = flush_denormal(self.b1 * x - self.a1 * y + self.z2);
+        self.z2 = flush_denormal(self.b2 * x - self.a2 * y);
+        y
+    }
+}
+
+/// Stereo three-band EQ
+pub struct Eq3 {
+    // [band][channel]
+    bands: [[Biquad; 2]; 3],
+    sample_rate: f32,
+}
+
+impl Eq3 {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            bands: [[Biquad::IDENTITY; 2]; 3],
+            sample_rate,
+        }
+    }
+
+    /// Update the bands from `p`; call once per buffer
+    pub fn set(&mut self, p: &EqParams) {
+        let settings = [
+            (Shape::LowShelf, p.low_freq, p.low_gain),
+            (Shape::Peak, p.mid_freq, p.mid_gain),
+            (Shape::HighShelf, p.high_freq, p.high_gain),
+        ];
+        for (band, (shape, freq, gain)) in self.bands.iter_mut().zip(settings) {
+            for ch in band.iter_mut() {
+                ch.set(shape, freq, gain, self.sample_rate);
+            }
+        }
+    }
+
+    pub fn process(&mut self, l: f32, r: f32) -> (f32, f32) {
+        let (mut l, mut r) = (l, r);
+        for [bl, br] in self.bands.iter_mut() {
+            l = bl.process(l);
+            r = br.process(r);
+        }
+        (l, r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flat() -> EqParams {
+        EqParams {
+            low_gain: 0.0,
+            low_freq: 200.0,
+            mid_gain: 0.0,
+            mid_freq: 1_000.0,
+            high_gain: 0.0,
+            high_freq: 5_000.0,
+        }
+    }
+
+    #[test]
+    fn flat_settings_pass_signal_unchanged() {
+        let mut eq = Eq3::new(48_000.0);
+        eq.set(&flat());
+        let mut rng = crate::rng::Rng::new(7);
+        for _ in 0..48_000 {
+            let (l, r) = (rng.bipolar(), rng.bipolar());
+            let (ol, or) = eq.process(l, r);
+            assert!((ol - l).abs() < 1e-5 && (or - r).abs() < 1e-5);
+        }
+    }
+
+    /// Steady-state gain (RMS out / RMS in) of a sine at `hz` through the EQ
+    fn gain_at(p: &EqParams, hz: f32) -> f32 {
+        let sr = 48_000.0;
+        let mut eq = Eq3::new(sr);
+        eq.set(p);
+        let (mut sum_in, mut sum_out) = (0.0f32, 0.0f32);
+        for n in 0..48_000 {
+            let x = (2.0 * PI * hz * n as f32 / sr).sin();
+            let (y, _) = eq.process(x, x);
+            if n >= 24_000 {
+                sum_in += x * x;
+                sum_out += y * y;
+            }
+        }
+        (sum_out / sum_in).sqrt()
+    }
+
+    #[test]
+    fn bands_boost_their_own_region() {
+        let db = |g: f32| 20.0 * g.log10();
+        let low = EqParams {
+            low_gain: 6.0,
+            ..flat()
+        };
+        assert!((db(gain_at(&low, 40.0)) - 6.0).abs() < 0.5);
+        assert!(db(gain_at(&low, 8_000.0)).abs() < 0.5);
+        let mid = EqParams {
+            mid_gain: 6.0,
+            ..flat()
+        };
+        assert!((db(gain_at(&mid, 1_000.0)) - 6.0).abs() < 0.5);
+        let high = EqParams {
+            high_gain: -6.0,
+            ..flat()
+        };
+        assert!((db(gain_at(&high, 15_000.0)) + 6.0).abs() < 0.5);
+        assert!(db(gain_at(&high, 100.0)).abs() < 0.5);
+    }
+}
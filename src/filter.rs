@@ -0,0 +1,234 @@
+// src/filter.rs
+// State-variable filter (trapezoidal / "TPT" form, stable under fast cutoff changes) with
+// low/high/band-pass and notch outputs, keyboard tracking of the cutoff, and series/parallel
+// routing of two filters. State lives in the audio callback closure.
+
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::flush_denormal;
+
+/// Which SVF output is used
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum FilterMode {
+    #[default]
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+}
+
+impl FilterMode {
+    pub const ALL: [FilterMode; 4] = [
+        FilterMode::LowPass,
+        FilterMode::HighPass,
+        FilterMode::BandPass,
+        FilterMode::Notch,
+    ];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL
+            .get(v as usize)
+            .copied()
+            .unwrap_or(FilterMode::LowPass)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FilterMode::LowPass => "low-pass",
+            FilterMode::HighPass => "high-pass",
+            FilterMode::BandPass => "band-pass",
+            FilterMode::Notch => "notch",
+        }
+    }
+}
+
+/// How the second filter is combined with the first
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FilterRouting {
+    /// Filter 2 bypassed
+    Single,
+    /// Filter 2 processes filter 1's output
+    Series,
+    /// Both filters see the input; outputs are summed
+    Parallel,
+}
+
+impl FilterRouting {
+    pub const ALL: [FilterRouting; 3] = [
+        FilterRouting::Single,
+        FilterRouting::Series,
+        FilterRouting::Parallel,
+    ];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL
+            .get(v as usize)
+            .copied()
+            .unwrap_or(FilterRouting::Single)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FilterRouting::Single => "filter 1 only",
+            FilterRouting::Series => "series",
+            FilterRouting::Parallel => "parallel",
+        }
+    }
+
+    /// Run `x` through the two filters according to the routing
+    pub fn process(self, f1: &mut Svf, f2: &mut Svf, x: f32) -> f32 {
+        match self {
+            FilterRouting::Single => f1.process(x),
+            FilterRouting::Series => {
+                let y = f1.process(x);
+                f2.process(y)
+            }
+            FilterRouting::Parallel => f1.process(x) + f2.process(x),
+        }
+    }
+}
+
+/// Filter settings read once per audio buffer
+#[derive(Clone, Copy, Debug)]
+pub struct FilterParams {
+    pub cutoff: f32,    // Hz at the center note
+    pub resonance: f32, // 0..1
+    pub mode: FilterMode,
+    pub key_track: f32, // semitones of cutoff per semitone of note (0 = off, 1 = full)
+    pub center_note: f32, // MIDI note where key tracking has no effect
+    pub drive: f32,     // input saturation, 1 = clean
+}
+
+impl FilterParams {
+    /// Cutoff for a voice playing `note`: shifted by `(note - center_note) * key_track` semitones
+    pub fn tracked_cutoff(&self, note: f32) -> f32 {
+        self.cutoff * 2f32.powf((note - self.center_note) * self.key_track / 12.0)
+    }
+}
+
+/// Saturate the filter input as `tanh(x * drive)`, with `1 / drive` makeup so small signals
+/// keep their level; at drive 1 normal levels pass almost untouched
+pub fn drive(x: f32, drive: f32) -> f32 {
+    let d = drive.max(1.0);
+    (x * d).tanh() / d
+}
+
+#[derive(Clone, Copy)]
+pub struct Svf {
+    ic1eq: f32,
+    ic2eq: f32,
+    g: f32,
+    k: f32,
+    mode: FilterMode,
+}
+
+impl Svf {
+    pub fn new() -> Self {
+        Self {
+            ic1eq: 0.0,
+            ic2eq: 0.0,
+            g: 0.0,
+            k: 2.0,
+            mode: FilterMode::LowPass,
+        }
+    }
+
+    /// Recompute coefficients; cheap enough to call once per buffer
+    pub fn set(&mut self, cutoff: f32, resonance: f32, mode: FilterMode, sample_rate: f32) {
+        let fc = cutoff.clamp(20.0, 0.49 * sample_rate);
+        self.g = (PI * fc / sample_rate).tan();
+        self.k = 2.0 - 2.0 * resonance.clamp(0.0, 0.98);
+        self.mode = mode;
+    }
+
+    /// Damping `k` (2 = no resonance); the band-pass output peaks at `1 / k`
+    pub fn damping(&self) -> f32 {
+        self.k
+    }
+
+    /// Take `other`'s coefficients, keeping this filter's own state
+    pub fn follow(&mut self, other: &Svf) {
+        self.g = other.g;
+        self.k = other.k;
+        self.mode = other.mode;
+    }
+
+    /// Filter one sample, returning the output selected by the mode
+    pub fn process(&mut self, x: f32) -> f32 {
+        let a1 = 1.0 / (1.0 + self.g * (self.g + self.k));
+        let a2 = self.g * a1;
+        let a3 = self.g * a2;
+        let v3 = x - self.ic2eq;
+        let v1 = a1 * self.ic1eq + a2 * v3;
+        let v2 = self.ic2eq + a2 * self.ic1eq + a3 * v3;
+        self.ic1eq = flush_denormal(2.0 * v1 - self.ic1eq);
+        self.ic2eq = flush_denormal(2.0 * v2 - self.ic2eq);
+        match self.mode {
+            FilterMode::LowPass => v2,
+            FilterMode::BandPass => v1,
+            FilterMode::HighPass => x - self.k * v1 - v2,
+            FilterMode::Notch => x - self.k * v1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drive_is_nearly_clean_at_one_and_bounded_above() {
+        for i in -100..=100 {
+            let x = i as f32 / 400.0; // +/-0.25
+            assert!((drive(x, 1.0) - x).abs() < 0.006, "{}", x);
+        }
+        // small signals keep their level at any drive; large ones saturate at 1/drive
+        assert!((drive(0.001, 8.0) - 0.001).abs() < 1e-5);
+        assert!(drive(1.0, 8.0) <= 1.0 / 8.0);
+        assert!(drive(-1.0, 8.0) >= -1.0 / 8.0);
+    }
+
+    #[test]
+    fn resonant_state_decays_to_exact_zero() {
+        let mut svf = Svf::new();
+        svf.set(1_000.0, 0.9, FilterMode::LowPass, 48_000.0);
+        svf.process(1.0);
+        for _ in 0..48_000 {
+            svf.process(0.0);
+        }
+        assert_eq!((svf.ic1eq, svf.ic2eq), (0.0, 0.0));
+    }
+
+    #[test]
+    fn series_low_passes_roll_off_twice_as_steeply() {
+        let sr = 48_000.0;
+        // steady-state peak of a sine at `hz` through 1 kHz low-passes routed by `routing`
+        let gain = |routing: FilterRouting, hz: f32| {
+            let mut f1 = Svf::new();
+            f1.set(1_000.0, 0.0, FilterMode::LowPass, sr);
+            let mut f2 = f1;
+            (0..9_600)
+                .map(|n| {
+                    let x = (std::f32::consts::TAU * hz * n as f32 / sr).sin();
+                    routing.process(&mut f1, &mut f2, x)
+                })
+                .skip(4_800)
+                .fold(0.0f32, |m, y| m.max(y.abs()))
+        };
+        for hz in [2_000.0, 4_000.0] {
+            let single = gain(FilterRouting::Single, hz);
+            let series = gain(FilterRouting::Series, hz);
+            assert!(single < 0.5, "{} Hz: {}", hz, single);
+            // two 12 dB/octave stages: the attenuation in dB doubles
+            assert!(
+                (series / (single * single) - 1.0).abs() < 0.05,
+                "{} Hz: {} vs {}",
+                hz,
+                series,
+                single
+            );
+        }
+    }
+}
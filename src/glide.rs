@@ -0,0 +1,153 @@
+// src/glide.rs
+// Portamento: each voice's sounding frequency slides toward its note's frequency at a set
+// rate, either a constant number of Hz per second or a constant number of cents per second.
+
+use serde::{Deserialize, Serialize};
+
+/// How the glide moves between frequencies
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum GlideCurve {
+    /// Constant Hz/s: the same interval takes longer the higher it is
+    Linear,
+    /// Constant cents/s: every octave takes the same time, which sounds even to the ear
+    #[default]
+    Exponential,
+}
+
+impl GlideCurve {
+    pub const ALL: [GlideCurve; 2] = [GlideCurve::Linear, GlideCurve::Exponential];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL
+            .get(v as usize)
+            .copied()
+            .unwrap_or(GlideCurve::Exponential)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GlideCurve::Linear => "linear (Hz)",
+            GlideCurve::Exponential => "exponential (cents)",
+        }
+    }
+}
+
+/// Where a newly allocated voice's glide starts
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum GlideMode {
+    /// From the last note played, however long ago
+    #[default]
+    LastNote,
+    /// Fingered poly glide: from the pitch of the nearest voice still sounding; with none
+    /// sounding, the note starts at its own pitch
+    Nearest,
+}
+
+impl GlideMode {
+    pub const ALL: [GlideMode; 2] = [GlideMode::LastNote, GlideMode::Nearest];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL.get(v as usize).copied().unwrap_or_default()
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GlideMode::LastNote => "from last note",
+            GlideMode::Nearest => "from nearest voice (poly)",
+        }
+    }
+}
+
+/// Frequency the linear curve's rate is referenced to: it covers `REF_HZ` Hz in the same
+/// time the exponential curve covers an octave
+const REF_HZ: f32 = 440.0;
+
+/// Glide settings read once per audio buffer
+#[derive(Clone, Copy, Debug)]
+pub struct GlideParams {
+    pub time: f32, // seconds per octave (0 = off)
+    pub curve: GlideCurve,
+}
+
+/// Per-sample increments for one buffer, so the inner loop needs no `powf`
+#[derive(Clone, Copy)]
+pub struct GlideStep {
+    curve: GlideCurve,
+    ratio: f32, // exponential: multiplier per sample
+    hz: f32,    // linear: Hz per sample
+    off: bool,
+}
+
+impl GlideStep {
+    pub fn new(p: &GlideParams, sample_rate: f32) -> Self {
+        let samples = p.time * sample_rate;
+        Self {
+            curve: p.curve,
+            ratio: 2f32.powf(1.0 / samples.max(1.0)),
+            hz: REF_HZ / samples.max(1.0),
+            off: samples < 1.0,
+        }
+    }
+
+    /// `current` moved one sample toward `target`, landing exactly on it
+    pub fn advance(&self, current: f32, target: f32) -> f32 {
+        if self.off || current <= 0.0 {
+            return target;
+        }
+        match self.curve {
+            GlideCurve::Exponential if current < target => (current * self.ratio).min(target),
+            GlideCurve::Exponential => (current / self.ratio).max(target),
+            GlideCurve::Linear if current < target => (current + self.hz).min(target),
+            GlideCurve::Linear => (current - self.hz).max(target),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SR: f32 = 48_000.0;
+
+    /// Samples until a glide from `from` reaches `to`
+    fn glide_samples(curve: GlideCurve, from: f32, to: f32) -> usize {
+        let step = GlideStep::new(&GlideParams { time: 0.1, curve }, SR);
+        let mut f = from;
+        let mut n = 0;
+        while f != to {
+            f = step.advance(f, to);
+            n += 1;
+        }
+        n
+    }
+
+    #[test]
+    fn exponential_octaves_take_equal_time() {
+        let low = glide_samples(GlideCurve::Exponential, 100.0, 200.0);
+        let high = glide_samples(GlideCurve::Exponential, 200.0, 400.0);
+        let down = glide_samples(GlideCurve::Exponential, 400.0, 200.0);
+        // 0.1 s per octave, within a couple of samples of rounding
+        assert!(low.abs_diff(4800) <= 2, "{}", low);
+        assert!(low.abs_diff(high) <= 2, "{} vs {}", low, high);
+        assert!(low.abs_diff(down) <= 2, "{} vs {}", low, down);
+    }
+
+    #[test]
+    fn linear_time_grows_with_the_hz_distance() {
+        let low = glide_samples(GlideCurve::Linear, 100.0, 200.0);
+        let high = glide_samples(GlideCurve::Linear, 200.0, 400.0);
+        assert!(high.abs_diff(2 * low) <= 2, "{} vs {}", low, high);
+    }
+
+    #[test]
+    fn zero_time_jumps() {
+        let step = GlideStep::new(
+            &GlideParams {
+                time: 0.0,
+                curve: GlideCurve::Exponential,
+            },
+            SR,
+        );
+        assert_eq!(step.advance(100.0, 300.0), 300.0);
+    }
+}
@@ -0,0 +1,112 @@
+// src/granular.rs
+// Granular texture engine: short Hann-windowed grains read from a loaded sample and summed.
+// Grain slots are a fixed array, so spawning a grain never allocates.
+
+use std::f32::consts::TAU;
+
+use crate::rng::{mix, Rng};
+
+/// Upper bound on simultaneous grains (CPU safety). New grains are dropped while all are busy.
+pub const MAX_GRAINS: usize = 32;
+/// Built-in seed of the grain scatter
+const SEED: u32 = 0x9E37_79B9;
+
+/// Grain settings read once per audio buffer
+#[derive(Clone, Copy, Debug)]
+pub struct GrainParams {
+    pub size: f32,       // grain length in seconds
+    pub density: f32,    // grains spawned per second
+    pub position: f32,   // 0..1 through the source
+    pub pitch: f32,      // semitones
+    pub spray: f32,      // 0..1 random position offset (fraction of the source)
+    pub pitch_rand: f32, // +/- semitones of random pitch per grain
+}
+
+#[derive(Clone, Copy)]
+struct Grain {
+    active: bool,
+    pos: f32,  // read position in source samples
+    step: f32, // source samples per output sample
+    age: usize,
+    len: usize,
+}
+
+impl Grain {
+    const IDLE: Grain = Grain {
+        active: false,
+        pos: 0.0,
+        step: 1.0,
+        age: 0,
+        len: 0,
+    };
+}
+
+pub struct Granular {
+    grains: [Grain; MAX_GRAINS],
+    // samples until the next grain is due
+    until_next: f32,
+    rng: Rng,
+    sample_rate: f32,
+}
+
+impl Granular {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            grains: [Grain::IDLE; MAX_GRAINS],
+            until_next: 0.0,
+            rng: Rng::new(SEED),
+            sample_rate,
+        }
+    }
+
+    /// Restart the random sequence under the global `seed` (see `rng::mix`)
+    pub fn reseed(&mut self, seed: u32) {
+        self.rng = Rng::new(mix(seed, SEED));
+    }
+
+    fn spawn(&mut self, src_len: usize, p: &GrainParams) {
+        let Some(slot) = self.grains.iter().position(|g| !g.active) else {
+            return;
+        };
+        let offset = p.spray * (self.rng.next_f32() - 0.5);
+        let start = (p.position + offset).clamp(0.0, 1.0) * (src_len - 1) as f32;
+        let semis = p.pitch + p.pitch_rand * self.rng.bipolar();
+        self.grains[slot] = Grain {
+            active: true,
+            pos: start,
+            step: 2f32.powf(semis / 12.0),
+            age: 0,
+            len: ((p.size * self.sample_rate) as usize).max(2),
+        };
+    }
+
+    /// Next mono output sample from `src`
+    pub fn process(&mut self, src: &[f32], p: &GrainParams) -> f32 {
+        if src.len() < 2 {
+            return 0.0;
+        }
+
+        self.until_next -= 1.0;
+        if self.until_next <= 0.0 {
+            self.spawn(src.len(), p);
+            self.until_next += self.sample_rate / p.density.max(0.1);
+        }
+
+        let len = src.len() as f32;
+        let mut out = 0.0;
+        for g in self.grains.iter_mut().filter(|g| g.active) {
+            let window = 0.5 - 0.5 * (TAU * g.age as f32 / g.len as f32).cos();
+            let i = g.pos as usize;
+            let frac = g.pos - i as f32;
+            let s = src[i] + (src[(i + 1) % src.len()] - src[i]) * frac;
+            out += s * window;
+
+            g.pos = (g.pos + g.step) % len;
+            g.age += 1;
+            if g.age >= g.len {
+                g.active = false;
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,129 @@
+// src/handoff.rs
+// Lock-free hand-off of the latest value to the audio thread. Writers box each value and
+// publish it with one atomic swap; the reader takes it with another swap and pushes the spent
+// box onto a stack the writers reclaim, so the reader never locks, allocates or frees.
+
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+struct Node<T> {
+    value: T,
+    next: *mut Node<T>,
+}
+
+pub struct Handoff<T> {
+    // the newest value not yet taken, or null
+    latest: AtomicPtr<Node<T>>,
+    // boxes the reader is done with, linked through `next`; reused or freed by writers
+    spent: AtomicPtr<Node<T>>,
+}
+
+// every node is owned by exactly one side at a time: whoever swapped it out of a slot
+unsafe impl<T: Send> Send for Handoff<T> {}
+unsafe impl<T: Send> Sync for Handoff<T> {}
+
+impl<T: Copy> Handoff<T> {
+    pub fn new() -> Self {
+        Self {
+            latest: AtomicPtr::new(null_mut()),
+            spent: AtomicPtr::new(null_mut()),
+        }
+    }
+
+    /// Make `value` what the next `take` returns, replacing one not yet taken. Any thread may
+    /// publish; this is where boxes are allocated and freed.
+    pub fn publish(&self, value: T) {
+        let spent = self.spent.swap(null_mut(), Ordering::AcqRel);
+        let node = if spent.is_null() {
+            Box::into_raw(Box::new(Node {
+                value,
+                next: null_mut(),
+            }))
+        } else {
+            // SAFETY: the swap made this thread the only owner of the whole spent stack
+            unsafe {
+                free((*spent).next);
+                (*spent).value = value;
+                (*spent).next = null_mut();
+            }
+            spent
+        };
+        free(self.latest.swap(node, Ordering::AcqRel));
+    }
+
+    /// The value published since the last take, if any
+    pub fn take(&self) -> Option<T> {
+        let node = self.latest.swap(null_mut(), Ordering::AcqRel);
+        if node.is_null() {
+            return None;
+        }
+        // SAFETY: the swap made this thread the node's only owner until it is pushed below
+        let value = unsafe { (*node).value };
+        let mut head = self.spent.load(Ordering::Acquire);
+        loop {
+            unsafe { (*node).next = head };
+            match self
+                .spent
+                .compare_exchange_weak(head, node, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => return Some(value),
+                Err(current) => head = current,
+            }
+        }
+    }
+}
+
+impl<T> Drop for Handoff<T> {
+    fn drop(&mut self) {
+        free(*self.latest.get_mut());
+        free(*self.spent.get_mut());
+    }
+}
+
+/// Free `node` and every node linked after it
+fn free<T>(mut node: *mut Node<T>) {
+    while !node.is_null() {
+        // SAFETY: callers pass nodes they own, having swapped them out of a slot
+        let boxed = unsafe { Box::from_raw(node) };
+        node = boxed.next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_latest_value_is_taken_and_only_once() {
+        let h = Handoff::new();
+        assert_eq!(h.take(), None);
+        h.publish(1);
+        h.publish(2);
+        assert_eq!(h.take(), Some(2));
+        assert_eq!(h.take(), None);
+        h.publish(3);
+        assert_eq!(h.take(), Some(3));
+    }
+
+    #[test]
+    fn a_reader_never_sees_a_torn_value() {
+        let h = std::sync::Arc::new(Handoff::new());
+        let writer = {
+            let h = h.clone();
+            std::thread::spawn(move || {
+                for i in 0..100_000u64 {
+                    h.publish([i; 8]);
+                }
+            })
+        };
+        let mut last = 0;
+        while !writer.is_finished() {
+            if let Some(v) = h.take() {
+                assert!(v.iter().all(|&x| x == v[0]), "{:?}", v);
+                assert!(v[0] >= last);
+                last = v[0];
+            }
+        }
+        writer.join().unwrap();
+    }
+}
@@ -0,0 +1,153 @@
+// src/limiter.rs
+// Lookahead limiter ahead of the safety ceiling. The output is delayed by `LOOKAHEAD_SECS` so
+// the gain can ramp down before a peak gets there; the ceiling's hard clamp then has nothing
+// left to cut. Both channels share one gain so the image doesn't shift. The delay line is
+// allocated once; `process` never allocates.
+
+use std::collections::VecDeque;
+
+/// Delay, and so the added latency, while the lookahead is on
+pub const LOOKAHEAD_SECS: f32 = 0.005;
+/// Time for the gain to recover most of the way once the peaks have passed
+const RELEASE_SECS: f32 = 0.05;
+
+pub struct Limiter {
+    // delayed frames, each with the gain it needs to stay within the ceiling
+    buf: Vec<(f32, f32, f32)>,
+    pos: usize,
+    gain: f32,
+    // while `step` is non-zero the gain ramps down by it each sample until it reaches `target`
+    target: f32,
+    step: f32,
+    release: f32, // one-pole coefficient
+    // smallest gain needed by the frames in the delay and the one leaving it, kept as a
+    // sliding minimum: (frame number, need) with the needs rising from the front. Capacity
+    // for the whole window is reserved up front.
+    mins: VecDeque<(usize, f32)>,
+    frames: usize,
+}
+
+impl Limiter {
+    pub fn new(sample_rate: f32) -> Self {
+        let len = (LOOKAHEAD_SECS * sample_rate) as usize;
+        Self {
+            buf: vec![(0.0, 0.0, 1.0); len],
+            pos: 0,
+            gain: 1.0,
+            target: 1.0,
+            step: 0.0,
+            release: 1.0 - (-1.0 / (RELEASE_SECS * sample_rate)).exp(),
+            mins: VecDeque::with_capacity(len + 1),
+            frames: 0,
+        }
+    }
+
+    /// Forget the delayed signal and any gain reduction (after a fault)
+    pub fn clear(&mut self) {
+        self.buf.fill((0.0, 0.0, 1.0));
+        self.gain = 1.0;
+        self.target = 1.0;
+        self.step = 0.0;
+        self.mins.clear();
+    }
+
+    /// Push a frame in and get the frame from `LOOKAHEAD_SECS` ago back, turned down as
+    /// needed to stay within `ceiling`
+    pub fn process(&mut self, l: f32, r: f32, ceiling: f32) -> (f32, f32) {
+        let len = self.buf.len();
+        let peak = l.abs().max(r.abs());
+        let need = if peak > ceiling { ceiling / peak } else { 1.0 };
+        if need < self.gain {
+            // this frame comes out `len` samples from now: be down to `need` by then
+            self.target = if self.step > 0.0 {
+                self.target.min(need)
+            } else {
+                need
+            };
+            self.step = self.step.max((self.gain - need) / len as f32);
+        }
+        let (dl, dr, _) = std::mem::replace(&mut self.buf[self.pos], (l, r, need));
+        self.pos = (self.pos + 1) % len;
+        // the window is this frame and the `len` before it; a need can't matter once a
+        // smaller one has come in after it
+        while self.mins.back().is_some_and(|&(_, m)| m >= need) {
+            self.mins.pop_back();
+        }
+        self.mins.push_back((self.frames, need));
+        while self
+            .mins
+            .front()
+            .is_some_and(|&(n, _)| n + len < self.frames)
+        {
+            self.mins.pop_front();
+        }
+        self.frames += 1;
+        if self.step > 0.0 {
+            self.gain = (self.gain - self.step).max(self.target);
+            if self.gain == self.target {
+                self.step = 0.0;
+            }
+        } else {
+            // recover, but never past what this frame or one still in the delay needs (the
+            // cleared frames all need 1)
+            let floor = self.mins.front().map_or(1.0, |&(_, m)| m);
+            if floor > self.gain {
+                self.gain += (floor - self.gain) * self.release;
+            }
+        }
+        (dl * self.gain, dr * self.gain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transients_never_pass_the_ceiling() {
+        let sr = 48_000.0;
+        let mut limiter = Limiter::new(sr);
+        let ceiling = 0.5;
+        let mut loudest = 0.0f32;
+        for n in 0..sr as usize {
+            // a quiet tone with a full-scale click every 10 ms for 3/4 s, and a louder burst
+            let mut x = 0.2 * (n as f32 * 0.05).sin();
+            if n % 480 == 0 && n < 36_000 {
+                x = 1.0;
+            }
+            if (24_000..24_480).contains(&n) {
+                x *= 4.0;
+            }
+            let (l, r) = limiter.process(x, -x, ceiling);
+            loudest = loudest.max(l.abs()).max(r.abs());
+        }
+        assert!(loudest <= ceiling * (1.0 + 1e-5), "{}", loudest);
+        // and the tone comes back to full level once the clicks stop
+        assert!(limiter.gain > 0.99, "{}", limiter.gain);
+    }
+
+    #[test]
+    fn the_sliding_minimum_matches_a_scan_of_the_window() {
+        let mut limiter = Limiter::new(48_000.0);
+        let len = limiter.buf.len();
+        let mut needs = Vec::new();
+        for n in 0..5_000 {
+            // peaks of varying height at irregular spacing
+            let x = if n % 97 == 0 || n % 131 == 0 {
+                1.0 + (n % 7) as f32
+            } else {
+                0.1
+            };
+            limiter.process(x, x, 1.0);
+            needs.push(if x > 1.0 { 1.0 / x } else { 1.0 });
+            let window = &needs[needs.len().saturating_sub(len + 1)..];
+            let scan = window.iter().fold(1.0f32, |m, &v| m.min(v));
+            assert_eq!(
+                limiter.mins.front().map_or(1.0, |m| m.1),
+                scan,
+                "frame {}",
+                n
+            );
+        }
+    }
+}
@@ -0,0 +1,262 @@
+// src/looper.rs
+// Loop recorder: captures a fixed-length stereo phrase, plays it back on repeat, and can
+// overdub new input on later passes. The first take runs a few milliseconds past the loop
+// end and crossfades that continuation into the loop start, so the seam doesn't click.
+// Buffers are allocated once when the stream is built; `process` never allocates.
+
+use crate::dsp::flush_denormal;
+
+/// Longest loop the buffers are sized for (seconds); 16 beats at 40 BPM fits
+pub const MAX_LOOP_SECS: f32 = 30.0;
+
+/// Length of the seam crossfade, and of the overdub and clear ramps
+const FADE_SECS: f32 = 0.01;
+
+/// What the looper is doing, for the UI
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LoopState {
+    Empty,
+    Recording,
+    Playing,
+}
+
+impl LoopState {
+    pub const ALL: [LoopState; 3] = [LoopState::Empty, LoopState::Recording, LoopState::Playing];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL
+            .get(v as usize)
+            .copied()
+            .unwrap_or(LoopState::Empty)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LoopState::Empty => "empty",
+            LoopState::Recording => "recording",
+            LoopState::Playing => "playing",
+        }
+    }
+}
+
+/// Looper settings read once per audio buffer
+#[derive(Clone, Copy, Debug)]
+pub struct LooperParams {
+    pub length: f32, // seconds (converted from beats); fixed when a take starts
+    pub overdub: bool,
+}
+
+pub struct Looper {
+    buf_l: Vec<f32>,
+    buf_r: Vec<f32>,
+    len: usize,
+    pos: usize,
+    state: LoopState,
+    // first playback pass: the take's continuation is still being blended into the start
+    blending: bool,
+    fade: usize,
+    overdub_gain: f32,
+    play_gain: f32,
+    clearing: bool,
+}
+
+impl Looper {
+    pub fn new(sample_rate: f32) -> Self {
+        let max = (MAX_LOOP_SECS * sample_rate) as usize;
+        Self {
+            buf_l: vec![0.0; max],
+            buf_r: vec![0.0; max],
+            len: max,
+            pos: 0,
+            state: LoopState::Empty,
+            blending: false,
+            fade: ((FADE_SECS * sample_rate) as usize).max(1),
+            overdub_gain: 0.0,
+            play_gain: 1.0,
+            clearing: false,
+        }
+    }
+
+    pub fn state(&self) -> LoopState {
+        self.state
+    }
+
+    /// How far through the loop (or the take) playback is, 0..1
+    pub fn position(&self) -> f32 {
+        match self.state {
+            LoopState::Empty => 0.0,
+            _ => self.pos as f32 / self.len as f32,
+        }
+    }
+
+    /// Start a new take of `p.length`; ignored unless the looper is empty
+    pub fn record(&mut self, p: &LooperParams, sample_rate: f32) {
+        if self.state != LoopState::Empty {
+            return;
+        }
+        self.len = ((p.length * sample_rate) as usize).clamp(2 * self.fade, self.buf_l.len());
+        self.pos = 0;
+        self.state = LoopState::Recording;
+    }
+
+    /// Fade the loop out and forget it (a take in progress is dropped at once)
+    pub fn clear(&mut self) {
+        match self.state {
+            LoopState::Playing => self.clearing = true,
+            _ => self.reset(),
+        }
+    }
+
+    /// Stop immediately (e.g. after a non-finite value got into the buffer)
+    pub fn reset(&mut self) {
+        self.state = LoopState::Empty;
+        self.blending = false;
+        self.clearing = false;
+        self.overdub_gain = 0.0;
+        self.play_gain = 1.0;
+    }
+
+    /// Feed one stereo frame of input and return the loop's playback for it
+    pub fn process(&mut self, l: f32, r: f32, p: &LooperParams) -> (f32, f32) {
+        let step = 1.0 / self.fade as f32;
+        match self.state {
+            LoopState::Empty => (0.0, 0.0),
+            LoopState::Recording => {
+                self.buf_l[self.pos] = l;
+                self.buf_r[self.pos] = r;
+                self.pos += 1;
+                if self.pos == self.len {
+                    self.pos = 0;
+                    self.state = LoopState::Playing;
+                    self.blending = true;
+                }
+                (0.0, 0.0)
+            }
+            LoopState::Playing => {
+                let pos = self.pos;
+                if self.blending {
+                    // at the seam the recording is all continuation (following the loop's last
+                    // sample), handing over to the original start by the end of the fade
+                    let g = pos as f32 / self.fade as f32;
+                    self.buf_l[pos] = self.buf_l[pos] * g + l * (1.0 - g);
+                    self.buf_r[pos] = self.buf_r[pos] * g + r * (1.0 - g);
+                    self.blending = pos + 1 < self.fade;
+                } else if self.overdub_gain > 0.0 {
+                    self.buf_l[pos] = flush_denormal(self.buf_l[pos] + l * self.overdub_gain);
+                    self.buf_r[pos] = flush_denormal(self.buf_r[pos] + r * self.overdub_gain);
+                }
+                let out = (
+                    self.buf_l[pos] * self.play_gain,
+                    self.buf_r[pos] * self.play_gain,
+                );
+
+                // ramp overdub in/out so toggling it mid-loop doesn't leave a step in the buffer
+                let target = if p.overdub && !self.clearing {
+                    1.0
+                } else {
+                    0.0
+                };
+                self.overdub_gain = if self.overdub_gain < target {
+                    (self.overdub_gain + step).min(target)
+                } else {
+                    (self.overdub_gain - step).max(target)
+                };
+                if self.clearing {
+                    self.play_gain -= step;
+                    if self.play_gain <= 0.0 {
+                        self.reset();
+                    }
+                }
+                self.pos = (pos + 1) % self.len;
+                out
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+
+    const SR: f32 = 48_000.0;
+
+    fn params(overdub: bool) -> LooperParams {
+        LooperParams {
+            length: 0.5,
+            overdub,
+        }
+    }
+
+    /// A sine whose period doesn't divide the loop, so the raw seam would jump
+    fn input(n: usize) -> f32 {
+        (TAU * 330.5 * n as f32 / SR).sin() * 0.5
+    }
+
+    #[test]
+    fn take_plays_back_on_repeat() {
+        let mut looper = Looper::new(SR);
+        looper.record(&params(false), SR);
+        let len = (0.5 * SR) as usize;
+        for n in 0..len {
+            assert_eq!(looper.process(input(n), 0.0, &params(false)), (0.0, 0.0));
+        }
+        assert_eq!(looper.state(), LoopState::Playing);
+        // past the seam fade, later passes replay the take exactly (input now silent)
+        for _ in 0..3 {
+            for n in 0..len {
+                let (l, _) = looper.process(0.0, 0.0, &params(false));
+                if n >= looper.fade {
+                    assert!((l - input(n)).abs() < 1e-6);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn seam_is_continuous() {
+        let mut looper = Looper::new(SR);
+        looper.record(&params(false), SR);
+        let len = (0.5 * SR) as usize;
+        // keep playing the same sine past the loop end, as a performer would
+        let mut n = 0;
+        while n < len + looper.fade {
+            looper.process(input(n), 0.0, &params(false));
+            n += 1;
+        }
+        // a quarter-cycle jump (0.5) without the crossfade
+        let max_step = TAU * 330.5 / SR * 0.5 * 1.2;
+        let mut prev = looper.process(0.0, 0.0, &params(false)).0;
+        for _ in 0..2 * len {
+            let (l, _) = looper.process(0.0, 0.0, &params(false));
+            assert!((l - prev).abs() <= max_step, "jump of {}", (l - prev).abs());
+            prev = l;
+        }
+    }
+
+    #[test]
+    fn overdub_layers_and_clear_fades_out() {
+        let mut looper = Looper::new(SR);
+        looper.record(&params(false), SR);
+        let len = (0.5 * SR) as usize;
+        for _ in 0..len + looper.fade {
+            looper.process(0.25, 0.25, &params(false));
+        }
+        // one full pass of overdub once the ramp is done
+        for _ in 0..len {
+            looper.process(0.25, 0.25, &params(true));
+        }
+        for _ in 0..len + len / 2 {
+            looper.process(0.0, 0.0, &params(false));
+        }
+        let (l, _) = looper.process(0.0, 0.0, &params(false));
+        assert!((l - 0.5).abs() < 1e-3, "{}", l);
+
+        looper.clear();
+        for _ in 0..looper.fade + 1 {
+            looper.process(0.0, 0.0, &params(false));
+        }
+        assert_eq!(looper.state(), LoopState::Empty);
+        assert_eq!(looper.process(0.0, 0.0, &params(false)), (0.0, 0.0));
+    }
+}
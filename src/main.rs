@@ -2,12 +2,14 @@
 // Requires Cargo.toml with: cpal = "0.15", egui = "0.27", eframe = { version = "0.27", features = ["wgpu"] }, anyhow = "1"
 
 use std::f32::consts::TAU;
-use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU64, AtomicU8, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{SampleFormat, StreamConfig};
@@ -15,6 +17,83 @@ use cpal::{SampleFormat, StreamConfig};
 use eframe::egui;
 use eframe::egui::Color32;
 
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+mod audio_input;
+mod automation;
+mod base64;
+mod compressor;
+mod delay;
+mod distortion;
+mod drift;
+mod dsp;
+mod envelope;
+mod eq;
+mod filter;
+mod glide;
+mod granular;
+mod handoff;
+mod limiter;
+mod looper;
+mod midi;
+mod midi_file;
+mod noise;
+mod pitch;
+mod pluck;
+mod presets;
+mod recorder;
+mod reverb;
+mod rng;
+mod rt_priority;
+mod sample;
+mod sample_hold;
+mod scale;
+mod scope;
+mod sequencer;
+mod smoothing;
+mod spectrum;
+mod stereoize;
+mod trance_gate;
+mod tremolo;
+mod voice;
+mod wavetable;
+use audio_input::{InputQueue, INPUT_QUEUE};
+use automation::{Automation, AutomationState};
+use compressor::{CompParams, Compressor, Detector, DuckParams, DuckShape, DuckTrigger, Knee};
+use delay::{Delay, DelayParams, MAX_DELAY_SECS};
+use distortion::{Distortion, DistortionParams, Multiband, Oversample};
+use dsp::{correlation, fast_sine, pan_gains, slew};
+use envelope::{EnvParams, Envelope, ModEnvTarget};
+use eq::{Eq3, EqParams};
+use filter::{FilterMode, FilterParams, FilterRouting, Svf};
+use glide::{GlideCurve, GlideMode, GlideParams, GlideStep};
+use granular::{GrainParams, Granular};
+use handoff::Handoff;
+use limiter::{Limiter, LOOKAHEAD_SECS};
+use looper::{LoopState, Looper, LooperParams};
+use midi::{expand_velocity, ClockTracker, MidiEvent, VelocityCurve};
+use midi_file::{Song, SongCursor};
+use noise::NoiseParams;
+use presets::PresetEntry;
+use recorder::{BitDepth, Recorder, TAP_CAPACITY};
+use reverb::{Reverb, ReverbParams, ReverbQuality};
+use rt_priority::RtStatus;
+use sample_hold::{SampleHold, ShParams, ShTarget};
+use scale::{Scale, NOTE_NAMES};
+use scope::{ScopeBuffer, MAX_WINDOW_MS, MIN_WINDOW_MS};
+use sequencer::{SeqEvent, SeqParams, Sequencer, Step, DEFAULT_GATE, MIN_GATE, SEQ_STEPS};
+use smoothing::SmoothedParam;
+use stereoize::Stereoize;
+use trance_gate::{TranceGate, TranceGateParams};
+use tremolo::{LfoShape, Tremolo, TremoloParams};
+use voice::{
+    ChordType, NoteEvent, NotePriority, Voice, VoiceEngine, VoicePool, VoiceSteal, GATE_KEY,
+    MAX_VOICES,
+};
+use wavetable::Wavetable;
+
 /// Helper to store/load f32 in AtomicU32
 fn load_f32(a: &AtomicU32) -> f32 {
     f32::from_bits(a.load(Ordering::SeqCst))
@@ -23,347 +102,5341 @@ fn store_f32(a: &AtomicU32, v: f32) {
     a.store(v.to_bits(), Ordering::SeqCst)
 }
 
-/// Preset descriptor (pure data)
-#[derive(Clone)]
-struct Preset {
-    name: &'static str,
-    osc_mix: f32,
-    detune: f32,
-    gain: f32,
+/// Levels at or below this are treated as silence (-inf dB)
+const MIN_DB: f32 = -60.0;
+
+/// Linear amplitude -> dBFS (20*log10). Silence maps to -inf.
+fn linear_to_db(v: f32) -> f32 {
+    if v <= 0.0 {
+        f32::NEG_INFINITY
+    } else {
+        20.0 * v.log10()
+    }
 }
 
-impl Preset {
-    fn ryan_josh() -> Self {
-        Self {
-            name: "Ryan & Josh Allen (romantic)",
-            osc_mix: 0.25,
-            detune: 2.0,
-            gain: 0.45,
-        }
+/// dBFS -> linear amplitude; anything at or below `MIN_DB` is silence
+fn db_to_linear(db: f32) -> f32 {
+    if db <= MIN_DB {
+        0.0
+    } else {
+        10f32.powf(db / 20.0)
     }
-    fn laura_les() -> Self {
-        Self {
-            name: "Laura Les (fast hyperpopish)",
-            osc_mix: 0.85,
-            detune: 8.0,
-            gain: 0.75,
-        }
+}
+
+/// Readout like "-6.0 dB" / "-inf dB" for a linear level
+fn db_label(v: f32) -> String {
+    let db = linear_to_db(v);
+    if db <= MIN_DB {
+        "-inf dB".to_string()
+    } else {
+        format!("{:+.1} dB", db)
     }
 }
 
-/// Shared state between UI and audio. All fields audio reads are atomic (lock-free).
-struct SharedState {
-    // human readable preset name for the UI:
-    preset_name: Mutex<String>,
+/// Note name with octave, e.g. 69 -> "A4"
+fn note_name(note: u8) -> String {
+    format!("{}{}", NOTE_NAMES[note as usize % 12], note as i32 / 12 - 1)
+}
 
-    // synth params stored as atomics (f32 via AtomicU32)
-    osc_mix: AtomicU32,
-    detune: AtomicU32,
-    gain: AtomicU32,
+/// Nearest note to `hz` and its deviation in cents, e.g. 445 Hz -> ("A4", +19.6)
+fn freq_to_note_name(hz: f32) -> (String, f32) {
+    let note = freq_to_note(hz);
+    let nearest = note.round().clamp(0.0, 127.0);
+    (note_name(nearest as u8), (note - nearest) * 100.0)
+}
 
-    // master gain
-    master_gain: AtomicU32,
+/// Frequency (Hz) -> fractional MIDI note number (A4 = 440 Hz = 69)
+fn freq_to_note(hz: f32) -> f32 {
+    69.0 + 12.0 * (hz / 440.0).log2()
+}
 
-    // disco mode and ad tick
-    disco: AtomicBool,
-    ad_tick: AtomicU32,
+/// MIDI note number -> frequency (Hz)
+fn note_to_freq(note: f32) -> f32 {
+    440.0 * 2f32.powf((note - 69.0) / 12.0)
+}
 
-    // frequency (Hz) for demo tone
-    freq_hz: AtomicU32,
+/// Fundamental (Hz) of the latest audio input, if it has one, using `scratch` for the copy
+fn input_pitch(state: &SharedState, scratch: &mut Vec<f32>) -> Option<f32> {
+    let sample_rate = state.sample_rate.load(Ordering::SeqCst) as f32;
+    state.input_capture.snapshot(scratch);
+    let window = pitch::window_len(sample_rate).min(scratch.len());
+    pitch::detect(&scratch[scratch.len() - window..], sample_rate)
 }
 
-impl SharedState {
-    fn new() -> Self {
-        let preset = Preset::ryan_josh();
-        let s = SharedState {
-            preset_name: Mutex::new(preset.name.to_string()),
-            osc_mix: AtomicU32::new(preset.osc_mix.to_bits()),
-            detune: AtomicU32::new(preset.detune.to_bits()),
-            gain: AtomicU32::new(preset.gain.to_bits()),
-            master_gain: AtomicU32::new(0.8f32.to_bits()),
-            disco: AtomicBool::new(false),
-            ad_tick: AtomicU32::new(0),
-            freq_hz: AtomicU32::new((220.0f32).to_bits()), // default 220Hz
-        };
-        s
+/// Small horizontal peak meter with a dB readout
+fn level_meter(ui: &mut egui::Ui, label: &str, peak: f32) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.add(
+            egui::ProgressBar::new(peak.min(1.0))
+                .desired_width(120.0)
+                .text(db_label(peak)),
+        );
+    });
+}
+
+/// Tempo-synced note lengths: (label, length in quarter-note beats)
+const DIVISIONS: [(&str, f32); 7] = [
+    ("1/1", 4.0),
+    ("1/2", 2.0),
+    ("1/4", 1.0),
+    ("1/8", 0.5),
+    ("1/16", 0.25),
+    ("1/4T", 2.0 / 3.0),
+    ("1/8T", 1.0 / 3.0),
+];
+
+/// Rate (Hz) of one cycle per `DIVISIONS[division]` at `bpm`
+fn division_hz(bpm: f32, division: u8) -> f32 {
+    let beats = DIVISIONS[(division as usize).min(DIVISIONS.len() - 1)].1;
+    bpm / 60.0 / beats
+}
+
+/// Small "M"/"S" toggle buttons for one source's mute and solo
+fn mute_solo(ui: &mut egui::Ui, mute: &AtomicBool, solo: &AtomicBool) {
+    for (flag, text) in [(mute, "M"), (solo, "S")] {
+        let mut on = flag.load(Ordering::SeqCst);
+        if ui.toggle_value(&mut on, text).changed() {
+            flag.store(on, Ordering::SeqCst);
+        }
     }
+}
 
-    fn apply_preset(&self, p: &Preset) {
-        if let Ok(mut name) = self.preset_name.lock() {
-            *name = p.name.to_string();
+/// Section heading for an effect, with its on/off switch
+fn effect_header(ui: &mut egui::Ui, state: &SharedState, effect: Effect) {
+    ui.horizontal(|ui| {
+        ui.label(effect.label());
+        let a = &state.fx_on[effect as usize];
+        let mut on = a.load(Ordering::SeqCst);
+        if ui.checkbox(&mut on, "on").changed() {
+            a.store(on, Ordering::SeqCst);
         }
-        store_f32(&self.osc_mix, p.osc_mix);
-        store_f32(&self.detune, p.detune);
-        store_f32(&self.gain, p.gain);
+    });
+}
+
+/// Combo box over `options`, storing the selected index in an atomic
+fn atomic_combo(ui: &mut egui::Ui, a: &AtomicU8, label: &str, options: &[&str]) {
+    let mut idx = (a.load(Ordering::SeqCst) as usize).min(options.len() - 1);
+    let before = idx;
+    egui::ComboBox::from_label(label)
+        .selected_text(options[idx])
+        .show_ui(ui, |ui| {
+            for (i, name) in options.iter().enumerate() {
+                ui.selectable_value(&mut idx, i, *name);
+            }
+        });
+    if idx != before {
+        a.store(idx as u8, Ordering::SeqCst);
     }
 }
 
-// ---------- GUI + App ----------
+/// Logarithmic 20 Hz..20 kHz slider for a cutoff atomic
+fn cutoff_slider(ui: &mut egui::Ui, a: &AtomicU32, label: &str) {
+    let mut cutoff = load_f32(a);
+    if ui
+        .add(
+            egui::Slider::new(&mut cutoff, 20.0..=20000.0)
+                .logarithmic(true)
+                .text(label),
+        )
+        .changed()
+    {
+        store_f32(a, cutoff);
+    }
+}
 
-struct SynthApp {
-    state: Arc<SharedState>,
+/// Slider bound directly to an f32 atomic (writes only when the user changes it)
+fn atomic_slider(ui: &mut egui::Ui, a: &AtomicU32, max: f32, label: &str) -> egui::Response {
+    atomic_slider_range(ui, a, 0.0..=max, label)
 }
 
-impl eframe::App for SynthApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Disco color cycling if enabled
-        let disco_on = self.state.disco.load(Ordering::SeqCst);
-        if disco_on {
-            // increment ad tick
-            self.state.ad_tick.fetch_add(1, Ordering::SeqCst);
+/// `atomic_slider` for ranges that don't start at zero (e.g. bipolar pitch)
+fn atomic_slider_range(
+    ui: &mut egui::Ui,
+    a: &AtomicU32,
+    range: std::ops::RangeInclusive<f32>,
+    label: &str,
+) -> egui::Response {
+    let mut v = load_f32(a);
+    let resp = ui.add(egui::Slider::new(&mut v, range).text(label));
+    if resp.changed() {
+        store_f32(a, v);
+    }
+    resp
+}
 
-            let t = (Instant::now().elapsed().as_millis() as f32 / 200.0).sin();
-            let accent = Color32::from_rgb(
-                ((t * 0.5 + 0.5) * 255.0) as u8,
-                ((-t * 0.8 + 0.5) * 255.0) as u8,
-                ((t * 0.2 + 0.4) * 255.0) as u8,
-            );
-            let mut visuals = (*ctx.style()).visuals.clone();
-            visuals.widgets.inactive.bg_fill = accent;
-            ctx.set_visuals(visuals);
+/// What devices with more than two channels (5.1, multi-out interfaces) get on the
+/// channels past front L/R
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum OutputRouting {
+    /// Front L/R only; every other channel stays silent
+    FrontOnly,
+    /// The L/R mid (mono sum) on every extra channel
+    MidToAll,
+}
+
+impl OutputRouting {
+    const ALL: [OutputRouting; 2] = [OutputRouting::FrontOnly, OutputRouting::MidToAll];
+
+    fn from_u8(v: u8) -> Self {
+        Self::ALL
+            .get(v as usize)
+            .copied()
+            .unwrap_or(OutputRouting::FrontOnly)
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            OutputRouting::FrontOnly => "front L/R only",
+            OutputRouting::MidToAll => "mono sum to all extra channels",
         }
+    }
+}
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("Rust Synth Prototype — built-in presets (egui) ");
-            ui.horizontal(|ui| {
-                if ui.button("Load: Ryan & Josh Allen (romantic)").clicked() {
-                    self.state.apply_preset(&Preset::ryan_josh());
-                }
-                if ui.button("Load: Laura Les (fast hyperpopish)").clicked() {
-                    self.state.apply_preset(&Preset::laura_les());
-                }
-                let mut disco_val = self.state.disco.load(Ordering::SeqCst);
-                let mut disco_bool = disco_val;
-                if ui.checkbox(&mut disco_bool, "Disco mode").changed() {
-                    self.state.disco.store(disco_bool, Ordering::SeqCst);
-                }
-            });
+/// One-switch performance tradeoff for lower-power hardware
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Quality {
+    Full,
+    /// No oversampling, the cheapest reverb, `LOW_QUALITY_VOICES` voices and fast math
+    Low,
+}
 
-            ui.separator();
+impl Quality {
+    const ALL: [Quality; 2] = [Quality::Full, Quality::Low];
 
-            // show and tweak current preset atomics (read current values)
-            let osc_mix = load_f32(&self.state.osc_mix);
-            let detune = load_f32(&self.state.detune);
-            let gain = load_f32(&self.state.gain);
-            let mut osc_mix_mut = osc_mix;
-            let mut detune_mut = detune;
-            let mut gain_mut = gain;
+    fn from_u8(v: u8) -> Self {
+        Self::ALL.get(v as usize).copied().unwrap_or(Quality::Full)
+    }
 
-            ui.label(format!(
-                "Preset: {}",
-                self.state.preset_name.lock().unwrap()
-            ));
-            ui.add(egui::Slider::new(&mut osc_mix_mut, 0.0..=1.0).text("osc mix"));
-            ui.add(
-                egui::DragValue::new(&mut detune_mut)
-                    .speed(0.1)
-                    .clamp_range(-100.0..=100.0)
-                    .prefix("detune: "),
-            );
-            ui.add(egui::Slider::new(&mut gain_mut, 0.0..=2.0).text("gain"));
+    fn label(self) -> &'static str {
+        match self {
+            Quality::Full => "full quality",
+            Quality::Low => "low quality (embedded)",
+        }
+    }
+}
 
-            if ui.button("Apply changes").clicked() {
-                store_f32(&self.state.osc_mix, osc_mix_mut);
-                store_f32(&self.state.detune, detune_mut);
-                store_f32(&self.state.gain, gain_mut);
-            }
+/// Base look of the UI; disco mode paints over whichever is chosen
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
 
-            ui.separator();
+impl Theme {
+    const ALL: [Theme; 2] = [Theme::Dark, Theme::Light];
 
-            let mut mg = load_f32(&self.state.master_gain);
-            if ui
-                .add(egui::Slider::new(&mut mg, 0.0..=2.0).text("master gain"))
-                .changed()
-            {
-                store_f32(&self.state.master_gain, mg);
-            }
+    fn label(self) -> &'static str {
+        match self {
+            Theme::Dark => "dark",
+            Theme::Light => "light",
+        }
+    }
 
-            ui.separator();
-            ui.label("Advertisement area (disco mode spams this when enabled):");
-            if self.state.disco.load(Ordering::SeqCst) {
-                for i in 0..6 {
-                    ui.colored_label(
-                        Color32::LIGHT_YELLOW,
-                        format!("🔥 SUPER SYNTH SALE! BUY NOW — LIMITED TIME! ({})", i),
-                    );
-                }
-            } else {
-                ui.label("(disco mode disabled)");
-            }
+    /// The UI visuals for this theme, with the disco accent on top while it is cycling
+    fn visuals(self, disco: Option<Color32>) -> egui::Visuals {
+        let mut visuals = match self {
+            Theme::Dark => egui::Visuals::dark(),
+            Theme::Light => egui::Visuals::light(),
+        };
+        if let Some(accent) = disco {
+            visuals.widgets.inactive.bg_fill = accent;
+        }
+        visuals
+    }
+}
 
-            ui.with_layout(egui::Layout::bottom_up(egui::Align::LEFT), |ui| {
-                ui.small("Demo: monophonic continuous tone driven by preset parameters.");
-            });
-        });
+/// Collapsible groups of controls in the main window; which are open is saved between runs
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+enum Section {
+    Patches,
+    Oscillators,
+    Filter,
+    Envelope,
+    Modulation,
+    Effects,
+    Master,
+    Sequencing,
+    Analysis,
+}
 
-        ctx.request_repaint_after(Duration::from_millis(16));
+impl Section {
+    const ALL: [Section; 9] = [
+        Section::Patches,
+        Section::Oscillators,
+        Section::Filter,
+        Section::Envelope,
+        Section::Modulation,
+        Section::Effects,
+        Section::Master,
+        Section::Sequencing,
+        Section::Analysis,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Section::Patches => "Patch browser",
+            Section::Oscillators => "Oscillators",
+            Section::Filter => "Filter",
+            Section::Envelope => "Envelope",
+            Section::Modulation => "Modulation",
+            Section::Effects => "Effects",
+            Section::Master => "Master",
+            Section::Sequencing => "Tempo & sequencing",
+            Section::Analysis => "Scope & spectrum",
+        }
     }
 }
 
-// ---------- Audio: CPAL stream builders ----------
+/// Oscillator waveform while no wavetable is in use
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+enum Waveform {
+    #[default]
+    Sine,
+    /// Band-limited sawtooth (`Wavetable::saw`)
+    Saw,
+}
 
-fn start_audio_thread(state: Arc<SharedState>) -> Result<()> {
-    let host = cpal::default_host();
-    let device = host
-        .default_output_device()
-        .ok_or_else(|| anyhow::anyhow!("No output device"))?;
-    let cfg = device.default_output_config()?;
-    let sample_rate = cfg.sample_rate().0 as f32;
-    let config: StreamConfig = cfg.clone().into();
+impl Waveform {
+    const ALL: [Waveform; 2] = [Waveform::Sine, Waveform::Saw];
 
-    // spawn appropriate stream based on format
-    let stream = match cfg.sample_format() {
-        SampleFormat::F32 => build_stream_f32(&device, &config, sample_rate, state.clone())?,
-        SampleFormat::I16 => build_stream_i16(&device, &config, sample_rate, state.clone())?,
-        SampleFormat::U16 => build_stream_u16(&device, &config, sample_rate, state.clone())?,
-        _ => return Err(anyhow::anyhow!("Unsupported sample format")),
-    };
+    fn from_u8(v: u8) -> Self {
+        Self::ALL.get(v as usize).copied().unwrap_or(Waveform::Sine)
+    }
 
-    stream.play()?;
-    // keep thread alive while audio plays
-    loop {
-        thread::sleep(Duration::from_millis(200));
+    fn label(self) -> &'static str {
+        match self {
+            Waveform::Sine => "sine",
+            Waveform::Saw => "saw",
+        }
     }
 }
 
-/// Basic oscillator: two slightly-detuned sines mixed
-fn synth_sample(sample_phase: f32, osc_mix: f32, detune: f32, gain: f32, master: f32) -> f32 {
-    // detune: interpret as cents-ish fraction scaled small
-    let detune_frac = detune * 0.001; // small demo scaling
-    let a = (sample_phase * TAU).sin();
-    let b = ((sample_phase + detune_frac).fract() * TAU).sin();
-    ((1.0 - osc_mix) * a + osc_mix * b) * gain * master
+/// Sub-oscillator waveform
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum SubWaveform {
+    Sine,
+    /// Band-limited square: two copies of the built-in saw half a cycle apart
+    Square,
 }
 
-fn build_stream_f32(
-    device: &cpal::Device,
-    config: &StreamConfig,
-    sample_rate: f32,
-    state: Arc<SharedState>,
-) -> Result<cpal::Stream, anyhow::Error> {
-    let channels = config.channels as usize;
-    let mut phase: f32 = 0.0;
+impl SubWaveform {
+    const ALL: [SubWaveform; 2] = [SubWaveform::Sine, SubWaveform::Square];
 
-    let stream = device.build_output_stream(
-        config,
-        move |data: &mut [f32], _| {
-            let osc_mix = load_f32(&state.osc_mix);
-            let detune = load_f32(&state.detune);
-            let gain = load_f32(&state.gain);
-            let master = load_f32(&state.master_gain);
-            // read freq once
-            let freq = load_f32(&state.freq_hz);
-
-            let step = freq / sample_rate;
-            for frame in data.chunks_mut(channels) {
-                let s = synth_sample(phase.fract(), osc_mix, detune, gain, master);
-                for sample in frame.iter_mut() {
-                    *sample = s;
-                }
-                phase = (phase + step) % 1.0;
-            }
-        },
-        |err| eprintln!("audio err: {}", err),
-        None,
-    )?;
+    fn from_u8(v: u8) -> Self {
+        Self::ALL
+            .get(v as usize)
+            .copied()
+            .unwrap_or(SubWaveform::Sine)
+    }
 
-    Ok(stream)
+    fn label(self) -> &'static str {
+        match self {
+            SubWaveform::Sine => "sine",
+            SubWaveform::Square => "square",
+        }
+    }
 }
 
-fn build_stream_i16(
-    device: &cpal::Device,
-    config: &StreamConfig,
-    sample_rate: f32,
-    state: Arc<SharedState>,
-) -> Result<cpal::Stream, anyhow::Error> {
-    let channels = config.channels as usize;
-    let mut phase: f32 = 0.0;
+/// Sub-oscillator: oscillator A divided down by one or two octaves, the way an analog
+/// flip-flop divider does it, so it stays locked to A's phase
+#[derive(Clone, Copy, Debug)]
+struct SubParams {
+    level: f32,
+    octaves: u8, // 1 or 2
+    waveform: SubWaveform,
+}
 
-    let stream = device.build_output_stream(
-        config,
-        move |data: &mut [i16], _| {
-            let osc_mix = load_f32(&state.osc_mix);
-            let detune = load_f32(&state.detune);
-            let gain = load_f32(&state.gain);
-            let master = load_f32(&state.master_gain);
-            let freq = load_f32(&state.freq_hz);
+impl SubParams {
+    /// Sub phase (0..1) from A's phase and its count of completed cycles
+    fn phase(&self, a_phase: f32, a_cycles: u8) -> f32 {
+        let div = 1u8 << self.octaves.clamp(1, 2);
+        ((a_cycles % div) as f32 + a_phase) / div as f32
+    }
 
-            let step = freq / sample_rate;
-            for frame in data.chunks_mut(channels) {
-                let s = synth_sample(phase.fract(), osc_mix, detune, gain, master);
-                // clamp & scale to i16
-                let scaled = (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
-                for sample in frame.iter_mut() {
-                    *sample = scaled;
-                }
-                phase = (phase + step) % 1.0;
+    /// Sample at `phase` while A advances `a_step` cycles a sample
+    fn sample(&self, phase: f32, a_step: f32, sine: fn(f32) -> f32, saw: &Wavetable) -> f32 {
+        let level = Wavetable::saw_level(a_step / (1u8 << self.octaves.clamp(1, 2)) as f32);
+        match self.waveform {
+            SubWaveform::Sine => sine(phase),
+            SubWaveform::Square => {
+                saw.frame_sample(level, phase + 0.5) - saw.frame_sample(level, phase)
             }
-        },
-        |err| eprintln!("audio err: {}", err),
-        None,
-    )?;
+        }
+    }
+}
 
-    Ok(stream)
+/// Effects that can be switched off individually (`fx_on` is indexed by these)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Effect {
+    /// Both voice filters and their drive
+    Filter,
+    Distortion,
+    Delay,
+    Reverb,
+    Eq,
 }
 
-fn build_stream_u16(
-    device: &cpal::Device,
-    config: &StreamConfig,
-    sample_rate: f32,
-    state: Arc<SharedState>,
-) -> Result<cpal::Stream, anyhow::Error> {
-    let channels = config.channels as usize;
-    let mut phase: f32 = 0.0;
+impl Effect {
+    const ALL: [Effect; EFFECTS] = [
+        Effect::Filter,
+        Effect::Distortion,
+        Effect::Delay,
+        Effect::Reverb,
+        Effect::Eq,
+    ];
 
-    let stream = device.build_output_stream(
-        config,
-        move |data: &mut [u16], _| {
-            let osc_mix = load_f32(&state.osc_mix);
-            let detune = load_f32(&state.detune);
-            let gain = load_f32(&state.gain);
-            let master = load_f32(&state.master_gain);
-            let freq = load_f32(&state.freq_hz);
+    fn label(self) -> &'static str {
+        match self {
+            Effect::Filter => "Filter",
+            Effect::Distortion => "Distortion",
+            Effect::Delay => "Delay",
+            Effect::Reverb => "Reverb",
+            Effect::Eq => "EQ",
+        }
+    }
+}
 
-            let step = freq / sample_rate;
-            for frame in data.chunks_mut(channels) {
-                let s = synth_sample(phase.fract(), osc_mix, detune, gain, master);
-                // convert from [-1,1] to [0, u16::MAX]
-                let scaled = (((s.clamp(-1.0, 1.0) * 0.5) + 0.5) * u16::MAX as f32) as u16;
-                for sample in frame.iter_mut() {
-                    *sample = scaled;
-                }
-                phase = (phase + step) % 1.0;
-            }
-        },
-        |err| eprintln!("audio err: {}", err),
-        None,
-    )?;
+const EFFECTS: usize = 5;
 
-    Ok(stream)
+/// Deliberate faults injected into the audio callback to exercise the watchdogs. Only honored
+/// in debug builds.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum StressMode {
+    Off,
+    /// Sleep through twice the buffer's duration, so the callback misses its deadline
+    Overrun,
+    /// Put a NaN on the bus into the effects
+    Nan,
 }
 
-// ---------- main ----------
+impl StressMode {
+    const ALL: [StressMode; 3] = [StressMode::Off, StressMode::Overrun, StressMode::Nan];
 
-fn main() {
-    let shared = Arc::new(SharedState::new());
+    fn from_u8(v: u8) -> Self {
+        Self::ALL
+            .get(v as usize)
+            .copied()
+            .unwrap_or(StressMode::Off)
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            StressMode::Off => "off",
+            StressMode::Overrun => "overrun the deadline",
+            StressMode::Nan => "inject a NaN",
+        }
+    }
+}
 
-    // apply initial preset
-    shared.apply_preset(&Preset::ryan_josh());
+/// Polyphony in low-quality mode
+const LOW_QUALITY_VOICES: usize = 4;
 
-    // spawn audio thread
-    {
-        let s = shared.clone();
-        thread::spawn(move || {
-            if let Err(e) = start_audio_thread(s) {
-                eprintln!("Audio thread error: {:?}", e);
-            }
-        });
+/// Continuous parameters that controllers (the XY pad) can be assigned to
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ParamId {
+    None,
+    OscMix,
+    FilterCutoff,
+    FilterResonance,
+    WavetablePosition,
+    GrainPosition,
+    DelayMix,
+    DelayFeedback,
+    TremoloDepth,
+}
+
+impl ParamId {
+    const ALL: [ParamId; 9] = [
+        ParamId::None,
+        ParamId::OscMix,
+        ParamId::FilterCutoff,
+        ParamId::FilterResonance,
+        ParamId::WavetablePosition,
+        ParamId::GrainPosition,
+        ParamId::DelayMix,
+        ParamId::DelayFeedback,
+        ParamId::TremoloDepth,
+    ];
+
+    fn from_u8(v: u8) -> Self {
+        Self::ALL.get(v as usize).copied().unwrap_or(ParamId::None)
     }
 
-    // run eframe GUI
-    let options = eframe::NativeOptions::default();
-    let app = SynthApp { state: shared };
-    if let Err(e) = eframe::run_native(
+    fn label(self) -> &'static str {
+        match self {
+            ParamId::None => "(none)",
+            ParamId::OscMix => "osc mix",
+            ParamId::FilterCutoff => "filter cutoff",
+            ParamId::FilterResonance => "filter resonance",
+            ParamId::WavetablePosition => "wavetable position",
+            ParamId::GrainPosition => "grain position",
+            ParamId::DelayMix => "delay mix",
+            ParamId::DelayFeedback => "delay feedback",
+            ParamId::TremoloDepth => "tremolo depth",
+        }
+    }
+
+    fn atomic(self, s: &SharedState) -> Option<&AtomicU32> {
+        match self {
+            ParamId::None => None,
+            ParamId::OscMix => Some(&s.osc_mix),
+            ParamId::FilterCutoff => Some(&s.filter_cutoff),
+            ParamId::FilterResonance => Some(&s.filter_resonance),
+            ParamId::WavetablePosition => Some(&s.wt_position),
+            ParamId::GrainPosition => Some(&s.grain_position),
+            ParamId::DelayMix => Some(&s.delay_mix),
+            ParamId::DelayFeedback => Some(&s.delay_feedback),
+            ParamId::TremoloDepth => Some(&s.tremolo_depth),
+        }
+    }
+
+    /// Write a 0..1 control value, scaled to the parameter's range (cutoff is exponential)
+    fn set_normalized(self, s: &SharedState, t: f32) {
+        let t = t.clamp(0.0, 1.0);
+        let v = match self {
+            ParamId::FilterCutoff => 20.0 * 1000f32.powf(t), // 20 Hz .. 20 kHz
+            ParamId::FilterResonance => 0.98 * t,
+            ParamId::DelayFeedback => 0.95 * t,
+            _ => t,
+        };
+        if let Some(a) = self.atomic(s) {
+            store_f32(a, v);
+        }
+    }
+}
+
+/// Most JSON a patch code may inflate to; real presets are a few KB, so anything past this is
+/// a decompression bomb rather than a sound
+const MAX_CODE_JSON_BYTES: u64 = 64 * 1024;
+
+/// Preset descriptor (pure data); saved as JSON (see `presets`)
+#[derive(Clone, Serialize, Deserialize)]
+struct Preset {
+    name: String,
+    osc_mix: f32,
+    detune: f32,
+    gain: f32,
+    // portamento; missing from older preset files, which load with it off
+    #[serde(default)]
+    glide_time: f32,
+    #[serde(default)]
+    glide_curve: GlideCurve,
+    #[serde(default)]
+    glide_mode: GlideMode,
+    // waveform, filter 1 and the modulation envelope; older files load with the defaults
+    #[serde(default)]
+    waveform: Waveform,
+    #[serde(default)]
+    filter: PresetFilter,
+    #[serde(default)]
+    mod_env: PresetModEnv,
+    // free-text notes and tags (lowercase, e.g. "bass") for the patch browser
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    // linear gain that brings this preset to `LOUDNESS_TARGET_DB` (see `loudness_trim`);
+    // derived, so recomputed on load rather than stored
+    #[serde(skip)]
+    trim: f32,
+}
+
+/// Filter 1 as stored in a preset (the default is the fully open low-pass)
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+struct PresetFilter {
+    cutoff: f32,
+    resonance: f32,
+    mode: FilterMode,
+}
+
+impl Default for PresetFilter {
+    fn default() -> Self {
+        Self {
+            cutoff: 20_000.0,
+            resonance: 0.0,
+            mode: FilterMode::LowPass,
+        }
+    }
+}
+
+/// The modulation envelope and where it's routed, as stored in a preset (off by default)
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+struct PresetModEnv {
+    attack: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+    target: ModEnvTarget,
+    amount: f32,
+}
+
+impl Default for PresetModEnv {
+    fn default() -> Self {
+        Self {
+            attack: 0.005,
+            decay: 0.4,
+            sustain: 0.0,
+            release: 0.3,
+            target: ModEnvTarget::Off,
+            amount: 0.5,
+        }
+    }
+}
+
+impl Preset {
+    fn ryan_josh() -> Self {
+        Self {
+            name: "Ryan & Josh Allen (romantic)".to_string(),
+            osc_mix: 0.25,
+            detune: 2.0,
+            gain: 0.45,
+            glide_time: 0.0,
+            glide_curve: GlideCurve::Exponential,
+            glide_mode: GlideMode::LastNote,
+            waveform: Waveform::Sine,
+            filter: PresetFilter::default(),
+            mod_env: PresetModEnv::default(),
+            description: "Two gently detuned sines, mostly A".to_string(),
+            tags: vec!["pad".to_string(), "soft".to_string()],
+            trim: 1.0,
+        }
+        .with_loudness_trim()
+    }
+    /// Built-in preset for quick-access slot `slot` (the "Load:" buttons, in order), which is
+    /// also the MIDI program number that recalls it
+    fn slot(slot: u8) -> Option<Self> {
+        match slot {
+            0 => Some(Self::ryan_josh()),
+            1 => Some(Self::laura_les()),
+            2 => Some(Self::filter_sweep()),
+            _ => None,
+        }
+    }
+
+    fn laura_les() -> Self {
+        Self {
+            name: "Laura Les (fast hyperpopish)".to_string(),
+            osc_mix: 0.85,
+            detune: 8.0,
+            gain: 0.75,
+            glide_time: 0.0,
+            glide_curve: GlideCurve::Exponential,
+            glide_mode: GlideMode::LastNote,
+            waveform: Waveform::Sine,
+            filter: PresetFilter::default(),
+            mod_env: PresetModEnv::default(),
+            description: "Wide detune leaning on B, loud and bright".to_string(),
+            tags: vec!["lead".to_string(), "bright".to_string()],
+            trim: 1.0,
+        }
+        .with_loudness_trim()
+    }
+
+    /// Demo of the filter and modulation envelope: a sawtooth through a resonant low-pass
+    /// that the envelope sweeps open on every note and lets close over about a second
+    fn filter_sweep() -> Self {
+        Self {
+            name: "Filter sweep (demo)".to_string(),
+            osc_mix: 0.0,
+            detune: 0.0,
+            gain: 0.6,
+            glide_time: 0.0,
+            glide_curve: GlideCurve::Exponential,
+            glide_mode: GlideMode::LastNote,
+            waveform: Waveform::Saw,
+            filter: PresetFilter {
+                cutoff: 200.0,
+                resonance: 0.7,
+                mode: FilterMode::LowPass,
+            },
+            mod_env: PresetModEnv {
+                attack: 0.005,
+                decay: 1.2,
+                sustain: 0.1,
+                release: 0.5,
+                target: ModEnvTarget::Cutoff,
+                amount: 1.0,
+            },
+            description: "A saw through a resonant low-pass the envelope sweeps".to_string(),
+            tags: vec!["bass".to_string(), "demo".to_string()],
+            trim: 1.0,
+        }
+        .with_loudness_trim()
+    }
+
+    fn load(path: &std::path::Path) -> Result<Self> {
+        let preset: Preset = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        Ok(preset.with_loudness_trim())
+    }
+
+    /// Load a file dropped on the window, which must be a `.json` preset
+    fn load_dropped(path: &std::path::Path) -> Result<Self> {
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            anyhow::bail!("{} is not a .json preset", path.display());
+        }
+        Self::load(path)
+    }
+
+    fn save(&self, path: &std::path::Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// The preset as a short shareable code: its JSON, deflated and base64-encoded
+    fn to_code(&self) -> String {
+        let mut deflate = DeflateEncoder::new(Vec::new(), Compression::best());
+        // writing to a Vec can't fail, nor can serializing plain fields
+        let json = serde_json::to_vec(self).expect("preset serializes");
+        deflate.write_all(&json).expect("in-memory write");
+        base64::encode(&deflate.finish().expect("in-memory write"))
+    }
+
+    /// Read a code made by `to_code`
+    fn from_code(code: &str) -> Result<Self> {
+        let bytes =
+            base64::decode(code).map_err(|e| anyhow::anyhow!("not a patch code ({})", e))?;
+        let mut json = String::new();
+        // one byte past the cap tells a code at the limit from one over it
+        DeflateDecoder::new(&bytes[..])
+            .take(MAX_CODE_JSON_BYTES + 1)
+            .read_to_string(&mut json)
+            .map_err(|_| anyhow::anyhow!("corrupt patch code"))?;
+        if json.len() as u64 > MAX_CODE_JSON_BYTES {
+            anyhow::bail!("patch code too large");
+        }
+        let preset: Preset = serde_json::from_str(&json)?;
+        Ok(preset.with_loudness_trim())
+    }
+
+    fn with_loudness_trim(mut self) -> Self {
+        self.trim = loudness_trim(&self);
+        self
+    }
+}
+
+/// RMS level presets are normalized to when "normalize loudness" is on
+const LOUDNESS_TARGET_DB: f32 = -18.0;
+
+/// Render half a second of a note of `p` offline at a reference pitch, through its waveform,
+/// filter 1 and modulation envelope, and return the gain that brings its short-term RMS to
+/// `LOUDNESS_TARGET_DB`
+fn loudness_trim(p: &Preset) -> f32 {
+    const SAMPLE_RATE: f32 = 48_000.0;
+    const REF_FREQ: f32 = 220.0;
+    static SAW: std::sync::OnceLock<Wavetable> = std::sync::OnceLock::new();
+    let saw = SAW.get_or_init(Wavetable::saw);
+    let saw_level = Wavetable::saw_level(REF_FREQ / SAMPLE_RATE);
+    let osc = |phase: f32| match p.waveform {
+        Waveform::Sine => sine(phase),
+        Waveform::Saw => saw.frame_sample(saw_level, phase),
+    };
+    let env_params = EnvParams {
+        delay: 0.0,
+        attack: p.mod_env.attack,
+        hold: 0.0,
+        decay: p.mod_env.decay,
+        sustain: p.mod_env.sustain,
+        release: p.mod_env.release,
+    };
+    let mut env = Envelope::new();
+    env.trigger();
+    let mut filter = Svf::new();
+    let f = p.filter;
+    filter.set(f.cutoff, f.resonance, f.mode, SAMPLE_RATE);
+    let n = (SAMPLE_RATE * 0.5) as usize;
+    let sum_sq: f32 = (0..n)
+        .map(|i| {
+            let m = env.process(true, &env_params, SAMPLE_RATE) * p.mod_env.amount;
+            let mut osc_mix = p.osc_mix;
+            match p.mod_env.target {
+                ModEnvTarget::Cutoff => {
+                    let cutoff = f.cutoff * 2f32.powf(m * MOD_ENV_CUTOFF_SEMIS / 12.0);
+                    filter.set(cutoff, f.resonance, f.mode, SAMPLE_RATE);
+                }
+                ModEnvTarget::OscMix => osc_mix = (osc_mix + m).clamp(0.0, 1.0),
+                // a little pitch movement hardly changes the level
+                ModEnvTarget::Off | ModEnvTarget::Pitch => {}
+            }
+            let phase = (i as f32 * REF_FREQ / SAMPLE_RATE).fract();
+            let (a, b) = osc_sources(osc, [phase; 2], osc_mix, p.detune, 0.0, p.gain, 1.0);
+            let y = filter.process(a + b);
+            y * y
+        })
+        .sum();
+    let rms = (sum_sq / n as f32).sqrt();
+    if rms <= db_to_linear(MIN_DB) {
+        // silent preset: nothing sensible to normalize
+        return 1.0;
+    }
+    (db_to_linear(LOUDNESS_TARGET_DB) / rms).min(db_to_linear(24.0))
+}
+
+/// On-screen keyboard range (C3..C5)
+const KB_LOW: u8 = 48;
+const KB_HIGH: u8 = 72;
+
+fn is_black_key(note: u8) -> bool {
+    matches!(note % 12, 1 | 3 | 6 | 8 | 10)
+}
+
+/// Computer keys playing an octave from middle C, tracker style: the home row for the white
+/// keys and the row above for the black ones
+const COMPUTER_KEYS: [egui::Key; 13] = {
+    use egui::Key::*;
+    [A, W, S, E, D, F, T, G, Y, H, U, J, K]
+};
+const COMPUTER_KEYS_LOW: u8 = 60;
+/// Velocity of computer-keyboard notes (the keys have no touch)
+const COMPUTER_KEY_VELOCITY: u8 = 100;
+
+/// What a computer-keyboard key event does: `Some((note, true))` plays the note, `Some((note,
+/// false))` releases it. Nothing is played while `typing` (a text field has focus), and key
+/// repeats are ignored; releases always go through, so focus moving mid-note can't leave it
+/// hanging.
+fn computer_key(
+    held: &mut Vec<u8>,
+    key: egui::Key,
+    pressed: bool,
+    typing: bool,
+) -> Option<(u8, bool)> {
+    let note = COMPUTER_KEYS_LOW + COMPUTER_KEYS.iter().position(|&k| k == key)? as u8;
+    let was_held = held.contains(&note);
+    if pressed && !typing && !was_held {
+        held.push(note);
+        Some((note, true))
+    } else if !pressed && was_held {
+        held.retain(|&n| n != note);
+        Some((note, false))
+    } else {
+        None
+    }
+}
+
+/// MIDI velocity for a click `y` of the way down a key (0 = top): soft at the top, full at
+/// the bottom, as on a touch-sensitive keyboard
+fn key_velocity(y: f32) -> u8 {
+    (1.0 + 126.0 * y.clamp(0.0, 1.0)).round() as u8
+}
+
+/// Where the patch browser looks for presets until another folder is entered
+const PRESET_DIR: &str = "presets";
+
+/// UI preferences kept between runs (the sound itself lives in presets)
+const UI_SETTINGS_FILE: &str = "ui_settings.json";
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct UiSettings {
+    theme: Theme,
+    // sections expanded in the main window
+    open: Vec<Section>,
+}
+
+impl Default for UiSettings {
+    /// A first run opens just what's needed to shape a sound
+    fn default() -> Self {
+        Self {
+            theme: Theme::default(),
+            open: vec![Section::Oscillators, Section::Filter, Section::Envelope],
+        }
+    }
+}
+
+impl UiSettings {
+    /// The saved settings, or the defaults if there are none (or they can't be read)
+    fn load(path: &std::path::Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, path: &std::path::Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Sound sources with their own mute/solo and meter
+const SRC_OSC_A: usize = 0;
+const SRC_OSC_B: usize = 1;
+const SRC_GRAINS: usize = 2;
+const SOURCES: usize = 3;
+
+/// Everything the audio callback reads per buffer, captured together so related settings
+/// (cutoff and resonance, delay time and feedback, ...) are never seen half-updated
+#[derive(Clone, Copy, Debug)]
+struct Params {
+    osc_mix: f32,
+    detune: f32,
+    // each oscillator's frequency relative to the played note, indexed by `SRC_OSC_*`
+    osc_ratio: [f32; 2],
+    key_track: [KeyTrack; 2],
+    gain: f32,
+    trim: f32, // loudness trim, 1.0 unless normalization is on
+    master: f32,
+    max_output: f32, // safety ceiling, linear
+    lookahead: bool,
+    smoothing: f32, // ramp time (s) for the smoothed controls; 0 = instant
+    freq: f32,
+    gate: bool,
+    analog_drift: f32,
+    humanize: f32, // cents
+    seed: u32,
+    reset_phase: bool,
+    precision: bool,
+    phase_offset: f32,
+    phase_width: f32,
+    fm: Option<FmParams>,
+    voice_spread: f32,
+    voice_engine: VoiceEngine,
+    string_damping: f32,
+    noise: NoiseParams,
+    glide: GlideParams,
+    glide_mode: GlideMode,
+    voice_steal: VoiceSteal,
+    // reference tone frequency while the tuner is on
+    tuner: Option<f32>,
+    vel_to_release: f32,
+    pressure_to_cutoff: f32,
+    declick: bool,
+    env: EnvParams,
+    mod_env: EnvParams,
+    mod_env_target: ModEnvTarget,
+    mod_env_amount: f32,
+    filter: FilterParams,
+    filter2: FilterParams,
+    filter_routing: FilterRouting,
+    // the warmth low-pass, fully key-tracked from its offset above C4 (None at zero warmth)
+    warmth: Option<FilterParams>,
+    distortion: DistortionParams,
+    delay: DelayParams,
+    reverb: ReverbParams,
+    stereoize: f32,
+    tremolo: TremoloParams,
+    trance_gate: TranceGateParams,
+    sequencer: SeqParams,
+    eq: EqParams,
+    compressor: Option<CompParams>,
+    looper: LooperParams,
+    sample_hold: ShParams,
+    grains: GrainParams,
+    grain_level: f32,
+    // trim on the external input, and whether it is mixed into the effects bus
+    input_gain: f32,
+    input_monitor: bool,
+    waveform: Waveform,
+    sub: SubParams,
+    wt_enabled: bool,
+    wt_position: f32,
+    chord: ChordType,
+    // monophonic note priority, or None when playing polyphonically
+    mono: Option<NotePriority>,
+    output_routing: OutputRouting,
+    quality: Quality,
+    fast_math: bool,
+    // which effects are switched in, indexed by `Effect`
+    fx_on: [bool; EFFECTS],
+    // per-source mute/solo outcome (1.0 audible, 0.0 silenced), indexed by `SRC_*`
+    source_gains: [f32; SOURCES],
+    // how the sequencer's keys become played pitches
+    note_map: NoteMap,
+    rt_priority: bool,
+    exiting: bool,
+    recording: bool,
+    stress_mode: StressMode,
+    stress_interval: u32,
+}
+
+/// Scale, root and transpose: how a key maps to the pitch actually played
+#[derive(Clone, Copy, Debug)]
+struct NoteMap {
+    scale: Scale,
+    root: u8,
+    transpose: i32,
+}
+
+impl NoteMap {
+    /// Pitch (MIDI note number) played for `note`: quantized, then transposed
+    fn played_note(self, note: u8) -> i32 {
+        self.scale.quantize(note as i32, self.root) + self.transpose
+    }
+}
+
+/// Shared state between UI and audio. All fields audio reads are atomic (lock-free).
+struct SharedState {
+    // human readable preset name for the UI:
+    preset_name: Mutex<String>,
+    // the preset's description and tags, kept so saving or copying it carries them over
+    preset_description: Mutex<String>,
+    preset_tags: Mutex<Vec<String>>,
+
+    // latest consistent parameter snapshot for the audio thread (see `publish`)
+    published: Handoff<Params>,
+
+    // synth params stored as atomics (f32 via AtomicU32)
+    osc_mix: AtomicU32,
+    detune: AtomicU32,
+    // per-oscillator pitch (indexed by `SRC_OSC_*`): octaves, semitones, and cents (f32)
+    osc_octave: [AtomicI32; 2],
+    osc_coarse: [AtomicI32; 2],
+    osc_fine: [AtomicU32; 2],
+    // per-oscillator keyboard tracking (1 = follows the note, 0 = fixed) and the fixed pitch (Hz)
+    osc_key_track: [AtomicU32; 2],
+    osc_fixed_hz: [AtomicU32; 2],
+    gain: AtomicU32,
+    // random pitch wander per voice, in cents (0 = perfectly stable)
+    analog_drift: AtomicU32,
+    // random detune per note, drawn as each voice is triggered, up to this many cents
+    humanize: AtomicU32,
+    // global seed every random source (drift, humanize, noise, strings, S&H, grains) starts
+    // from: the same seed and settings render the same output
+    random_seed: AtomicU32,
+    // every note-on restarts the oscillator phases (otherwise a retriggered note runs on)
+    reset_phase_on_note: AtomicBool,
+    // precision mode: no drift, humanize, S&H or envelope pitch modulation and no tremolo, so
+    // each oscillator sits exactly on its note (for tuning checks)
+    precision: AtomicBool,
+    // oscillator B's phase lead over A, as a fraction of a cycle (0..1)
+    phase_offset: AtomicU32,
+    // the right channel's oscillator phase lead over the left, as a fraction of a cycle
+    // (0 = mono; near 0.5 the channels cancel when summed to mono)
+    phase_width: AtomicU32,
+    // two-operator FM (B modulating A's phase) instead of the crossfade, its index (radians)
+    // and B's frequency ratio to A
+    fm_enabled: AtomicBool,
+    fm_index: AtomicU32,
+    fm_ratio: AtomicU32,
+    // per-effect on/off switches, indexed by `Effect`; an effect that is off costs nothing
+    fx_on: [AtomicBool; EFFECTS],
+    // how far voices are spread across the stereo field (0 = all centered, 1 = full width)
+    voice_spread: AtomicU32,
+    // oscillators or plucked string (`VoiceEngine`), and the string's damping (0..1)
+    voice_engine: AtomicU8,
+    string_damping: AtomicU32,
+    // band-passed noise per voice, for hi-hat and cymbal tones (level 0 = off)
+    noise_level: AtomicU32,
+    noise_freq: AtomicU32,
+    noise_resonance: AtomicU32,
+    // portamento: seconds per octave (0 = off), curve (`GlideCurve`) and where a new voice
+    // glides from (`GlideMode`)
+    glide_time: AtomicU32,
+    glide_curve: AtomicU8,
+    glide_mode: AtomicU8,
+    // loudness-normalizing trim of the current sound, applied while `normalize_loudness` is set
+    preset_trim: AtomicU32,
+    normalize_loudness: AtomicBool,
+
+    // master gain
+    master_gain: AtomicU32,
+    // parameter smoothing: when on, the continuous mixer controls (gain, master, osc mix,
+    // phase offset, stereoize) ramp to new values over `smoothing_time` (s) instead of jumping
+    smoothing_enabled: AtomicBool,
+    smoothing_time: AtomicU32,
+
+    // master output peak of the last buffer, and the meter's reference line (dBFS)
+    peak_output: AtomicU32,
+    meter_reference: AtomicU32,
+    // hearing-safety ceiling (dBFS) the output is hard-clamped to, whatever the gains and
+    // effects, and whether the last buffer needed it; with the lookahead on, a limiter turns
+    // peaks down ahead of the ceiling instead (at `LOOKAHEAD_SECS` of added latency)
+    max_output_db: AtomicU32,
+    output_clamping: AtomicBool,
+    limiter_lookahead: AtomicBool,
+    // L/R correlation of the last buffer (-1..1), for the phase meter
+    correlation: AtomicU32,
+
+    // output device channel count (set when the stream is built) and extra-channel routing
+    out_channels: AtomicU32,
+    output_routing: AtomicU8,
+    // output device the audio thread should play on (None = the system default; it moves the
+    // stream when this changes), and the device in use or why the last switch failed
+    output_device: Mutex<Option<String>>,
+    output_status: Mutex<String>,
+
+    // global quality mode; overrides the per-subsystem settings when low
+    quality: AtomicU8,
+    // polynomial sine (`dsp::fast_sine`) in the oscillators and LFOs instead of `sin`; always
+    // on in low quality
+    fast_math: AtomicBool,
+
+    // disco mode and ad tick
+    disco: AtomicBool,
+    ad_tick: AtomicU32,
+
+    // frequency (Hz) for demo tone
+    freq_hz: AtomicU32,
+
+    // note on/off from MIDI, drained by the audio callback each buffer
+    note_events: Mutex<Vec<NoteEvent>>,
+    // latch mode: a key press toggles its note rather than holding it; `latched` is which
+    // keys (keyboard and MIDI) are currently latched on
+    latch: AtomicBool,
+    latched: [AtomicBool; 128],
+    // MIDI program changes recall the built-in preset slots (`Preset::slot`)
+    program_change: AtomicBool,
+    // voices currently sounding (held or releasing), for display
+    active_voices: AtomicU32,
+    // tuner: a fixed-level reference sine at `tuner_note` replaces the synth output
+    tuner: AtomicBool,
+    tuner_note: AtomicU8,
+    // note-on velocity response (`VelocityCurve`)
+    velocity_curve: AtomicU8,
+    // velocity range the controller actually sends (0..1), stretched over the full range
+    // before the curve
+    vel_min: AtomicU32,
+    vel_max: AtomicU32,
+    // chord played by each key (`ChordType`)
+    chord_mode: AtomicU8,
+    // one note at a time, chosen among the held keys by `note_priority` (`NotePriority`)
+    mono: AtomicBool,
+    note_priority: AtomicU8,
+    // which voice a note takes over when every voice is busy (`VoiceSteal`)
+    voice_steal: AtomicU8,
+    // incoming notes are snapped to `scale` in the key of `scale_root` (0 = C), then
+    // shifted by `transpose` semitones
+    scale: AtomicU8,
+    scale_root: AtomicU8,
+    transpose: AtomicI32,
+
+    // note gate and DAHDSR envelope (times in seconds, sustain is a level)
+    gate: AtomicBool,
+    env_delay: AtomicU32,
+    env_attack: AtomicU32,
+    env_hold: AtomicU32,
+    env_decay: AtomicU32,
+    env_sustain: AtomicU32,
+    env_release: AtomicU32,
+    // how much note-off velocity shortens (fast lift) or lengthens (slow lift) the release
+    vel_to_release: AtomicU32,
+    // how far polyphonic aftertouch opens the pressed key's own filters (0..1 of
+    // `PRESSURE_CUTOFF_SEMIS`)
+    pressure_to_cutoff: AtomicU32,
+    // limit the amplitude envelope's slope to `DECLICK_SECS` per full swing, so zero attack
+    // or release can't click; off for raw, clicky transients
+    declick: AtomicBool,
+
+    // modulation envelope (ADSR, triggered with the amp envelope), its target, and a
+    // bipolar amount (-1..1 of the target's range)
+    mod_attack: AtomicU32,
+    mod_decay: AtomicU32,
+    mod_sustain: AtomicU32,
+    mod_release: AtomicU32,
+    mod_env_target: AtomicU8,
+    mod_env_amount: AtomicU32,
+
+    // tempo; follows incoming MIDI clock while `external_sync` is set
+    tempo_bpm: AtomicU32,
+    external_sync: AtomicBool,
+    external_bpm: AtomicU32, // last tempo derived from MIDI clock (0 = none seen)
+    clock_running: AtomicBool,
+    // set by MIDI Start; the callback rewinds the sequencer and trance gate to their first
+    // step and clears it
+    transport_rewind: AtomicBool,
+
+    // low-pass filter with keyboard tracking
+    filter_cutoff: AtomicU32,
+    filter_resonance: AtomicU32,
+    filter_mode: AtomicU8,
+    filter_key_track: AtomicU32,
+    // input saturation into both filters (1 = clean)
+    filter_drive: AtomicU32,
+    filter_center_note: AtomicU32,
+    // warmth: a gentle low-pass ahead of the filters, its cutoff a fixed interval above each
+    // note (0 = off, 1 = an octave above)
+    warmth: AtomicU32,
+    // second filter (shares key tracking with the first) and how the two are combined
+    filter2_cutoff: AtomicU32,
+    filter2_resonance: AtomicU32,
+    filter2_mode: AtomicU8,
+    filter_routing: AtomicU8,
+
+    // tanh drive on the bus into the effects (dB, 0 = bypassed) and its oversampling
+    dist_drive: AtomicU32,
+    dist_oversample: AtomicU8,
+    // multiband mode: crossover (Hz) and each band's drive (dB, 0 = clean) instead of `dist_drive`
+    dist_multiband: AtomicBool,
+    dist_crossover: AtomicU32,
+    dist_low_drive: AtomicU32,
+    dist_high_drive: AtomicU32,
+
+    // stereo delay: left and right times in seconds, or (synced) as `DIVISIONS` indices
+    delay_time_l: AtomicU32,
+    delay_time_r: AtomicU32,
+    delay_sync: AtomicBool,
+    delay_division: [AtomicU8; 2],
+    delay_feedback: AtomicU32,
+    delay_mix: AtomicU32,
+    delay_ping_pong: AtomicBool,
+
+    // stereo reverb on a send after the delay; the return is summed with the dry signal
+    reverb_size: AtomicU32,
+    reverb_damping: AtomicU32,
+    reverb_send: AtomicU32,
+    reverb_freeze: AtomicBool,
+    // how many of the reverb's lines run (`ReverbQuality`), for slower machines
+    reverb_quality: AtomicU8,
+    // stereoizer after the reverb: level (0..1) of the copies pitched a few cents up on the
+    // left and down on the right (0 = off, a mono source stays mono)
+    stereoize: AtomicU32,
+
+    // loop recorder on the bus into the effects: length in beats (fixed per take), overdub,
+    // record/clear requests consumed by the callback, and its state and position for the UI
+    loop_beats: AtomicU8,
+    loop_overdub: AtomicBool,
+    loop_record: AtomicBool,
+    loop_clear: AtomicBool,
+    loop_state: AtomicU8,
+    loop_position: AtomicU32,
+
+    // granular texture: source sample (swapped in by the UI) and grain controls
+    grain_source: Mutex<Option<Arc<Vec<f32>>>>,
+    grain_size: AtomicU32,
+    grain_density: AtomicU32,
+    grain_position: AtomicU32,
+    grain_pitch: AtomicU32,
+    grain_spray: AtomicU32,
+    grain_pitch_rand: AtomicU32,
+    grain_level: AtomicU32,
+
+    // external audio input (see `audio_input`): samples queued by the input callback, the
+    // device name or why it isn't open, monitoring on/off, its trim, and its level after trim
+    input_queue: Arc<InputQueue>,
+    input_status: Mutex<String>,
+    input_monitor: AtomicBool,
+    input_gain: AtomicU32,
+    peak_input: AtomicU32,
+    // the input after trim, monitored or not, for the pitch detector
+    input_capture: ScopeBuffer,
+
+    // oscillator waveform (`Waveform`) while no wavetable is in use
+    waveform: AtomicU8,
+    // sub-oscillator under A: level (0 = off), octaves down (1 or 2) and `SubWaveform`
+    sub_level: AtomicU32,
+    sub_octave: AtomicU8,
+    sub_waveform: AtomicU8,
+    // multi-frame wavetable replacing the oscillator waveform while enabled
+    wavetable: Mutex<Option<Arc<Wavetable>>>,
+    wt_enabled: AtomicBool,
+    wt_position: AtomicU32,
+
+    // mute/solo per sound source, indexed by `SRC_*`
+    source_mute: [AtomicBool; SOURCES],
+    source_solo: [AtomicBool; SOURCES],
+
+    // per-source peak levels (abs max over the last buffer), for the meters
+    peak_osc_a: AtomicU32,
+    peak_osc_b: AtomicU32,
+    peak_grains: AtomicU32,
+
+    // master-bus tremolo; the rate is free (Hz) or a tempo division while synced
+    tremolo_rate: AtomicU32,
+    tremolo_depth: AtomicU32,
+    tremolo_shape: AtomicU8,
+    tremolo_sync: AtomicBool,
+    tremolo_division: AtomicU8,
+
+    // trance gate: 16-step on/off pattern in 16ths of the tempo (bit n = step n open), and the
+    // step it's on, for the UI
+    trance_enabled: AtomicBool,
+    trance_pattern: AtomicU32,
+    trance_step: AtomicU8,
+
+    // step sequencer (16ths of the tempo): running, the pattern, the glide time of slide
+    // steps (s/octave), whether every step restarts the oscillator phases, and the step
+    // playing, for the UI
+    seq_running: AtomicBool,
+    seq_steps: Mutex<[Step; SEQ_STEPS]>,
+    seq_slide_time: AtomicU32,
+    phase_reset_on_step: AtomicBool,
+    seq_step: AtomicU8,
+
+    // master 3-band EQ: shelf/peak gains in dB and their frequencies in Hz
+    eq_low_gain: AtomicU32,
+    eq_low_freq: AtomicU32,
+    eq_mid_gain: AtomicU32,
+    eq_mid_freq: AtomicU32,
+    eq_high_gain: AtomicU32,
+    eq_high_freq: AtomicU32,
+
+    // master compressor after the EQ: threshold (dBFS), ratio, attack and release (s), makeup
+    // (dB), detector and knee, and the gain reduction of the last buffer (dB), for the meter
+    comp_enabled: AtomicBool,
+    comp_threshold: AtomicU32,
+    comp_ratio: AtomicU32,
+    comp_attack: AtomicU32,
+    comp_release: AtomicU32,
+    comp_makeup: AtomicU32,
+    comp_detector: AtomicU8,
+    comp_knee: AtomicU8,
+    comp_reduction: AtomicU32,
+    // sidechain ducking through the compressor: what retriggers it, how deep (dB), how long it
+    // takes to recover (fraction of the time between triggers) and the shape of the recovery
+    duck_trigger: AtomicU8,
+    duck_amount: AtomicU32,
+    duck_length: AtomicU32,
+    duck_shape: AtomicU8,
+
+    // tempo-synced sample-and-hold random modulation
+    sh_division: AtomicU8,
+    sh_smoothing: AtomicU32,
+    sh_depth: AtomicU32,
+    sh_target: AtomicU8,
+
+    // XY pad position (0..1 each, y up) and which parameter each axis drives
+    xy_x: AtomicU32,
+    xy_y: AtomicU32,
+    xy_x_target: AtomicU8,
+    xy_y_target: AtomicU8,
+
+    // watchdog: a NaN/Inf reached the output and was replaced by silence (cleared by the
+    // UI), and notes are held but the output has been silent for a while
+    output_fault: AtomicBool,
+    // app closing: set by the UI to fade the output out, and by the audio thread once silent
+    exiting: AtomicBool,
+    exit_faded: AtomicBool,
+    silent_while_gated: AtomicBool,
+    // callbacks that took longer than the audio they rendered (each one risks an underrun)
+    late_callbacks: AtomicU32,
+    // debug builds: `StressMode` fault injected every `stress_interval` buffers
+    stress_mode: AtomicU8,
+    stress_interval: AtomicU32,
+
+    // oscilloscope capture of the final output, and its trigger level
+    scope: ScopeBuffer,
+    scope_trigger_level: AtomicU32,
+    scope_window_ms: AtomicU32,
+
+    // WAV recording: the callback appends interleaved stereo to the tap while `recording`
+    // is set (dropping frames rather than growing it); the UI drains it every frame
+    recording: AtomicBool,
+    record_tap: Mutex<Vec<f32>>,
+    record_overrun: AtomicBool,
+    sample_rate: AtomicU32,
+    // frames rendered since the stream started; the clock automation is timed against
+    sample_clock: AtomicU64,
+
+    // opt-in real-time scheduling of the audio callback thread, and how the OS answered
+    rt_priority: AtomicBool,
+    rt_status: AtomicU8,
+}
+
+impl SharedState {
+    fn new() -> Self {
+        let preset = Preset::ryan_josh();
+        SharedState {
+            preset_name: Mutex::new(preset.name.clone()),
+            preset_description: Mutex::new(preset.description.clone()),
+            preset_tags: Mutex::new(preset.tags.clone()),
+            published: Handoff::new(),
+            osc_mix: AtomicU32::new(preset.osc_mix.to_bits()),
+            detune: AtomicU32::new(preset.detune.to_bits()),
+            osc_octave: Default::default(),
+            osc_coarse: Default::default(),
+            osc_fine: Default::default(),
+            osc_key_track: std::array::from_fn(|_| AtomicU32::new(1.0f32.to_bits())),
+            osc_fixed_hz: std::array::from_fn(|_| AtomicU32::new(110.0f32.to_bits())),
+            gain: AtomicU32::new(preset.gain.to_bits()),
+            analog_drift: AtomicU32::new(0.0f32.to_bits()),
+            humanize: AtomicU32::new(0.0f32.to_bits()),
+            reset_phase_on_note: AtomicBool::new(false),
+            random_seed: AtomicU32::new(0),
+            precision: AtomicBool::new(false),
+            phase_offset: AtomicU32::new(0.0f32.to_bits()),
+            phase_width: AtomicU32::new(0.0f32.to_bits()),
+            fm_enabled: AtomicBool::new(false),
+            fm_index: AtomicU32::new(1.0f32.to_bits()),
+            fm_ratio: AtomicU32::new(2.0f32.to_bits()),
+            fx_on: std::array::from_fn(|_| AtomicBool::new(true)),
+            voice_spread: AtomicU32::new(0.0f32.to_bits()),
+            voice_engine: AtomicU8::new(VoiceEngine::Oscillators as u8),
+            string_damping: AtomicU32::new(0.5f32.to_bits()),
+            noise_level: AtomicU32::new(0.0f32.to_bits()),
+            noise_freq: AtomicU32::new(8_000.0f32.to_bits()),
+            noise_resonance: AtomicU32::new(0.5f32.to_bits()),
+            glide_time: AtomicU32::new(0.0f32.to_bits()),
+            glide_curve: AtomicU8::new(GlideCurve::Exponential as u8),
+            glide_mode: AtomicU8::new(GlideMode::LastNote as u8),
+            preset_trim: AtomicU32::new(preset.trim.to_bits()),
+            normalize_loudness: AtomicBool::new(false),
+            master_gain: AtomicU32::new(0.8f32.to_bits()),
+            smoothing_enabled: AtomicBool::new(true),
+            smoothing_time: AtomicU32::new(0.02f32.to_bits()),
+            out_channels: AtomicU32::new(0),
+            output_device: Mutex::new(None),
+            output_status: Mutex::new("not started".to_string()),
+            output_routing: AtomicU8::new(OutputRouting::FrontOnly as u8),
+            peak_output: AtomicU32::new(0.0f32.to_bits()),
+            max_output_db: AtomicU32::new(0.0f32.to_bits()),
+            output_clamping: AtomicBool::new(false),
+            limiter_lookahead: AtomicBool::new(false),
+            meter_reference: AtomicU32::new((-18.0f32).to_bits()),
+            correlation: AtomicU32::new(0.0f32.to_bits()),
+            quality: AtomicU8::new(Quality::Full as u8),
+            fast_math: AtomicBool::new(false),
+            disco: AtomicBool::new(false),
+            ad_tick: AtomicU32::new(0),
+            freq_hz: AtomicU32::new((220.0f32).to_bits()), // default 220Hz
+            note_events: Mutex::new(Vec::with_capacity(256)),
+            latch: AtomicBool::new(false),
+            latched: std::array::from_fn(|_| AtomicBool::new(false)),
+            program_change: AtomicBool::new(false),
+            active_voices: AtomicU32::new(0),
+            chord_mode: AtomicU8::new(ChordType::Off as u8),
+            mono: AtomicBool::new(false),
+            note_priority: AtomicU8::new(NotePriority::Last as u8),
+            voice_steal: AtomicU8::new(VoiceSteal::Oldest as u8),
+            velocity_curve: AtomicU8::new(VelocityCurve::Linear as u8),
+            vel_min: AtomicU32::new(0.0f32.to_bits()),
+            vel_max: AtomicU32::new(1.0f32.to_bits()),
+            tuner: AtomicBool::new(false),
+            tuner_note: AtomicU8::new(69), // A4
+            scale: AtomicU8::new(Scale::Chromatic as u8),
+            scale_root: AtomicU8::new(0),
+            transpose: AtomicI32::new(0),
+            gate: AtomicBool::new(true), // gate open so the demo tone sounds on launch
+            env_delay: AtomicU32::new(0.0f32.to_bits()),
+            env_attack: AtomicU32::new(0.01f32.to_bits()),
+            env_hold: AtomicU32::new(0.0f32.to_bits()),
+            env_decay: AtomicU32::new(0.2f32.to_bits()),
+            env_sustain: AtomicU32::new(0.8f32.to_bits()),
+            env_release: AtomicU32::new(0.3f32.to_bits()),
+            vel_to_release: AtomicU32::new(0.0f32.to_bits()),
+            pressure_to_cutoff: AtomicU32::new(0.5f32.to_bits()),
+            declick: AtomicBool::new(true),
+            mod_attack: AtomicU32::new(preset.mod_env.attack.to_bits()),
+            mod_decay: AtomicU32::new(preset.mod_env.decay.to_bits()),
+            mod_sustain: AtomicU32::new(preset.mod_env.sustain.to_bits()),
+            mod_release: AtomicU32::new(preset.mod_env.release.to_bits()),
+            mod_env_target: AtomicU8::new(preset.mod_env.target as u8),
+            mod_env_amount: AtomicU32::new(preset.mod_env.amount.to_bits()),
+            tempo_bpm: AtomicU32::new(120.0f32.to_bits()),
+            external_sync: AtomicBool::new(false),
+            external_bpm: AtomicU32::new(0.0f32.to_bits()),
+            clock_running: AtomicBool::new(false),
+            transport_rewind: AtomicBool::new(false),
+            filter_cutoff: AtomicU32::new(preset.filter.cutoff.to_bits()),
+            filter_resonance: AtomicU32::new(preset.filter.resonance.to_bits()),
+            filter_mode: AtomicU8::new(preset.filter.mode as u8),
+            filter_key_track: AtomicU32::new(0.0f32.to_bits()),
+            filter_drive: AtomicU32::new(1.0f32.to_bits()),
+            filter_center_note: AtomicU32::new(60.0f32.to_bits()), // C4
+            warmth: AtomicU32::new(0.0f32.to_bits()),
+            filter2_cutoff: AtomicU32::new(20000.0f32.to_bits()),
+            filter2_resonance: AtomicU32::new(0.0f32.to_bits()),
+            filter2_mode: AtomicU8::new(FilterMode::LowPass as u8),
+            filter_routing: AtomicU8::new(FilterRouting::Single as u8),
+            dist_drive: AtomicU32::new(0.0f32.to_bits()),
+            dist_oversample: AtomicU8::new(Oversample::X2 as u8),
+            dist_multiband: AtomicBool::new(false),
+            dist_crossover: AtomicU32::new(1_000.0f32.to_bits()),
+            dist_low_drive: AtomicU32::new(0.0f32.to_bits()),
+            dist_high_drive: AtomicU32::new(12.0f32.to_bits()),
+            delay_time_l: AtomicU32::new(0.35f32.to_bits()),
+            delay_time_r: AtomicU32::new(0.35f32.to_bits()),
+            delay_sync: AtomicBool::new(false),
+            delay_division: [AtomicU8::new(2), AtomicU8::new(5)], // 1/4 and 1/4T
+            delay_feedback: AtomicU32::new(0.4f32.to_bits()),
+            delay_mix: AtomicU32::new(0.0f32.to_bits()), // dry by default
+            delay_ping_pong: AtomicBool::new(false),
+            reverb_size: AtomicU32::new(0.5f32.to_bits()),
+            reverb_damping: AtomicU32::new(0.5f32.to_bits()),
+            reverb_send: AtomicU32::new(0.0f32.to_bits()), // off
+            reverb_freeze: AtomicBool::new(false),
+            reverb_quality: AtomicU8::new(ReverbQuality::High as u8),
+            stereoize: AtomicU32::new(0.0f32.to_bits()),
+            loop_beats: AtomicU8::new(8),
+            loop_overdub: AtomicBool::new(false),
+            loop_record: AtomicBool::new(false),
+            loop_clear: AtomicBool::new(false),
+            loop_state: AtomicU8::new(LoopState::Empty as u8),
+            loop_position: AtomicU32::new(0.0f32.to_bits()),
+            grain_source: Mutex::new(None),
+            grain_size: AtomicU32::new(0.08f32.to_bits()),
+            grain_density: AtomicU32::new(20.0f32.to_bits()),
+            grain_position: AtomicU32::new(0.5f32.to_bits()),
+            grain_pitch: AtomicU32::new(0.0f32.to_bits()),
+            grain_spray: AtomicU32::new(0.1f32.to_bits()),
+            grain_pitch_rand: AtomicU32::new(0.0f32.to_bits()),
+            grain_level: AtomicU32::new(0.0f32.to_bits()), // silent until a sample is loaded
+            input_queue: Arc::new(audio_input::new_queue()),
+            input_status: Mutex::new("not started".to_string()),
+            input_monitor: AtomicBool::new(false), // a mic near the speakers would feed back
+            input_capture: ScopeBuffer::new(),
+            input_gain: AtomicU32::new(1.0f32.to_bits()),
+            peak_input: AtomicU32::new(0.0f32.to_bits()),
+            wavetable: Mutex::new(None),
+            waveform: AtomicU8::new(preset.waveform as u8),
+            sub_level: AtomicU32::new(0.0f32.to_bits()),
+            sub_octave: AtomicU8::new(1),
+            sub_waveform: AtomicU8::new(SubWaveform::Square as u8),
+            wt_enabled: AtomicBool::new(false),
+            wt_position: AtomicU32::new(0.0f32.to_bits()),
+            source_mute: Default::default(),
+            source_solo: Default::default(),
+            peak_osc_a: AtomicU32::new(0.0f32.to_bits()),
+            peak_osc_b: AtomicU32::new(0.0f32.to_bits()),
+            peak_grains: AtomicU32::new(0.0f32.to_bits()),
+            tremolo_rate: AtomicU32::new(5.0f32.to_bits()),
+            tremolo_depth: AtomicU32::new(0.0f32.to_bits()), // off
+            tremolo_shape: AtomicU8::new(LfoShape::Sine as u8),
+            tremolo_sync: AtomicBool::new(false),
+            tremolo_division: AtomicU8::new(3), // 1/8
+            trance_enabled: AtomicBool::new(false),
+            trance_pattern: AtomicU32::new(0b1011_1011_1011_1011),
+            trance_step: AtomicU8::new(0),
+            seq_running: AtomicBool::new(false),
+            seq_steps: Mutex::new(std::array::from_fn(|i| Step {
+                on: i.is_multiple_of(2),
+                note: 48,
+                slide: false,
+                gate: DEFAULT_GATE,
+            })),
+            seq_slide_time: AtomicU32::new(0.06f32.to_bits()),
+            phase_reset_on_step: AtomicBool::new(false),
+            seq_step: AtomicU8::new(0),
+            sh_division: AtomicU8::new(4), // 1/16
+            eq_low_gain: AtomicU32::new(0.0f32.to_bits()),
+            eq_low_freq: AtomicU32::new(200.0f32.to_bits()),
+            eq_mid_gain: AtomicU32::new(0.0f32.to_bits()),
+            eq_mid_freq: AtomicU32::new(1_000.0f32.to_bits()),
+            eq_high_gain: AtomicU32::new(0.0f32.to_bits()),
+            eq_high_freq: AtomicU32::new(5_000.0f32.to_bits()),
+            comp_enabled: AtomicBool::new(false),
+            comp_threshold: AtomicU32::new((-18.0f32).to_bits()),
+            comp_ratio: AtomicU32::new(4.0f32.to_bits()),
+            comp_attack: AtomicU32::new(0.01f32.to_bits()),
+            comp_release: AtomicU32::new(0.15f32.to_bits()),
+            comp_makeup: AtomicU32::new(0.0f32.to_bits()),
+            comp_detector: AtomicU8::new(Detector::Peak as u8),
+            comp_knee: AtomicU8::new(Knee::Hard as u8),
+            comp_reduction: AtomicU32::new(0.0f32.to_bits()),
+            duck_trigger: AtomicU8::new(DuckTrigger::Off as u8),
+            duck_amount: AtomicU32::new(12.0f32.to_bits()),
+            duck_length: AtomicU32::new(0.6f32.to_bits()),
+            duck_shape: AtomicU8::new(DuckShape::Exponential as u8),
+            sh_smoothing: AtomicU32::new(0.0f32.to_bits()),
+            sh_depth: AtomicU32::new(12.0f32.to_bits()),
+            sh_target: AtomicU8::new(ShTarget::Off as u8),
+            xy_x: AtomicU32::new(0.5f32.to_bits()),
+            xy_y: AtomicU32::new(0.5f32.to_bits()),
+            xy_x_target: AtomicU8::new(ParamId::FilterCutoff as u8),
+            xy_y_target: AtomicU8::new(ParamId::FilterResonance as u8),
+            scope: ScopeBuffer::new(),
+            scope_trigger_level: AtomicU32::new(0.0f32.to_bits()),
+            scope_window_ms: AtomicU32::new(20.0f32.to_bits()),
+            output_fault: AtomicBool::new(false),
+            exiting: AtomicBool::new(false),
+            exit_faded: AtomicBool::new(false),
+            late_callbacks: AtomicU32::new(0),
+            stress_mode: AtomicU8::new(StressMode::Off as u8),
+            stress_interval: AtomicU32::new(100),
+            silent_while_gated: AtomicBool::new(false),
+            recording: AtomicBool::new(false),
+            record_tap: Mutex::new(Vec::with_capacity(TAP_CAPACITY)),
+            record_overrun: AtomicBool::new(false),
+            sample_rate: AtomicU32::new(48_000),
+            sample_clock: AtomicU64::new(0),
+            rt_priority: AtomicBool::new(false),
+            rt_status: AtomicU8::new(RtStatus::Normal as u8),
+        }
+    }
+
+    fn apply_preset(&self, p: &Preset) {
+        if let Ok(mut name) = self.preset_name.lock() {
+            *name = p.name.clone();
+        }
+        if let Ok(mut description) = self.preset_description.lock() {
+            *description = p.description.clone();
+        }
+        if let Ok(mut tags) = self.preset_tags.lock() {
+            *tags = p.tags.clone();
+        }
+        store_f32(&self.osc_mix, p.osc_mix);
+        store_f32(&self.detune, p.detune);
+        store_f32(&self.gain, p.gain);
+        store_f32(&self.glide_time, p.glide_time);
+        self.glide_curve
+            .store(p.glide_curve as u8, Ordering::SeqCst);
+        self.glide_mode.store(p.glide_mode as u8, Ordering::SeqCst);
+        self.waveform.store(p.waveform as u8, Ordering::SeqCst);
+        store_f32(&self.filter_cutoff, p.filter.cutoff);
+        store_f32(&self.filter_resonance, p.filter.resonance);
+        self.filter_mode
+            .store(p.filter.mode as u8, Ordering::SeqCst);
+        store_f32(&self.mod_attack, p.mod_env.attack);
+        store_f32(&self.mod_decay, p.mod_env.decay);
+        store_f32(&self.mod_sustain, p.mod_env.sustain);
+        store_f32(&self.mod_release, p.mod_env.release);
+        self.mod_env_target
+            .store(p.mod_env.target as u8, Ordering::SeqCst);
+        store_f32(&self.mod_env_amount, p.mod_env.amount);
+        store_f32(&self.preset_trim, p.trim);
+    }
+
+    /// The current sound as a preset called `name`
+    fn current_preset(&self, name: &str) -> Preset {
+        Preset {
+            name: name.to_string(),
+            osc_mix: load_f32(&self.osc_mix),
+            detune: load_f32(&self.detune),
+            gain: load_f32(&self.gain),
+            glide_time: load_f32(&self.glide_time),
+            glide_curve: GlideCurve::from_u8(self.glide_curve.load(Ordering::SeqCst)),
+            glide_mode: GlideMode::from_u8(self.glide_mode.load(Ordering::SeqCst)),
+            waveform: Waveform::from_u8(self.waveform.load(Ordering::SeqCst)),
+            filter: PresetFilter {
+                cutoff: load_f32(&self.filter_cutoff),
+                resonance: load_f32(&self.filter_resonance),
+                mode: FilterMode::from_u8(self.filter_mode.load(Ordering::SeqCst)),
+            },
+            mod_env: PresetModEnv {
+                attack: load_f32(&self.mod_attack),
+                decay: load_f32(&self.mod_decay),
+                sustain: load_f32(&self.mod_sustain),
+                release: load_f32(&self.mod_release),
+                target: ModEnvTarget::from_u8(self.mod_env_target.load(Ordering::SeqCst)),
+                amount: load_f32(&self.mod_env_amount),
+            },
+            description: self.preset_description.lock().unwrap().clone(),
+            tags: self.preset_tags.lock().unwrap().clone(),
+            trim: 1.0,
+        }
+        .with_loudness_trim()
+    }
+
+    /// Which sources are heard: with any solo active only soloed sources play, otherwise
+    /// everything that isn't muted
+    fn source_gains(&self) -> [f32; SOURCES] {
+        let any_solo = self.source_solo.iter().any(|s| s.load(Ordering::SeqCst));
+        std::array::from_fn(|i| {
+            let audible = if any_solo {
+                self.source_solo[i].load(Ordering::SeqCst)
+            } else {
+                !self.source_mute[i].load(Ordering::SeqCst)
+            };
+            if audible {
+                1.0
+            } else {
+                0.0
+            }
+        })
+    }
+
+    /// Oscillator `osc`'s (`SRC_OSC_*`) offset from the played note, in semitones
+    fn osc_pitch(&self, osc: usize) -> f32 {
+        12.0 * self.osc_octave[osc].load(Ordering::SeqCst) as f32
+            + self.osc_coarse[osc].load(Ordering::SeqCst) as f32
+            + load_f32(&self.osc_fine[osc]) / 100.0
+    }
+
+    /// Oscillator `osc`'s (`SRC_OSC_*`) keyboard tracking
+    fn key_track(&self, osc: usize) -> KeyTrack {
+        KeyTrack {
+            amount: load_f32(&self.osc_key_track[osc]),
+            fixed_hz: load_f32(&self.osc_fixed_hz[osc]),
+        }
+    }
+
+    fn note_map(&self) -> NoteMap {
+        NoteMap {
+            scale: Scale::from_u8(self.scale.load(Ordering::SeqCst)),
+            root: self.scale_root.load(Ordering::SeqCst),
+            transpose: self.transpose.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Pitch (MIDI note number) actually played for `note`: quantized, then transposed
+    fn played_note(&self, note: u8) -> i32 {
+        self.note_map().played_note(note)
+    }
+
+    /// Full or low-power processing, as chosen in the UI
+    fn quality(&self) -> Quality {
+        Quality::from_u8(self.quality.load(Ordering::SeqCst))
+    }
+
+    /// Read every audio parameter at once
+    fn snapshot(&self) -> Params {
+        let low = self.quality() == Quality::Low;
+        let fast_math = low || self.fast_math.load(Ordering::SeqCst);
+        Params {
+            osc_mix: load_f32(&self.osc_mix),
+            detune: load_f32(&self.detune),
+            osc_ratio: std::array::from_fn(|i| 2f32.powf(self.osc_pitch(i) / 12.0)),
+            key_track: std::array::from_fn(|i| self.key_track(i)),
+            gain: load_f32(&self.gain),
+            trim: if self.normalize_loudness.load(Ordering::SeqCst) {
+                load_f32(&self.preset_trim)
+            } else {
+                1.0
+            },
+            master: load_f32(&self.master_gain),
+            max_output: db_to_linear(load_f32(&self.max_output_db)),
+            lookahead: self.limiter_lookahead.load(Ordering::SeqCst),
+            smoothing: if self.smoothing_enabled.load(Ordering::SeqCst) {
+                load_f32(&self.smoothing_time)
+            } else {
+                0.0
+            },
+            freq: load_f32(&self.freq_hz),
+            gate: self.gate.load(Ordering::SeqCst),
+            analog_drift: load_f32(&self.analog_drift),
+            humanize: load_f32(&self.humanize),
+            reset_phase: self.reset_phase_on_note.load(Ordering::SeqCst),
+            seed: self.random_seed.load(Ordering::SeqCst),
+            precision: self.precision.load(Ordering::SeqCst),
+            phase_offset: load_f32(&self.phase_offset),
+            phase_width: load_f32(&self.phase_width),
+            fm: self.fm_enabled.load(Ordering::SeqCst).then(|| FmParams {
+                index: load_f32(&self.fm_index),
+                ratio: load_f32(&self.fm_ratio),
+            }),
+            voice_spread: load_f32(&self.voice_spread),
+            voice_engine: VoiceEngine::from_u8(self.voice_engine.load(Ordering::SeqCst)),
+            string_damping: load_f32(&self.string_damping),
+            noise: NoiseParams {
+                level: load_f32(&self.noise_level),
+                freq: load_f32(&self.noise_freq),
+                resonance: load_f32(&self.noise_resonance),
+            },
+            tuner: self
+                .tuner
+                .load(Ordering::SeqCst)
+                .then(|| note_to_freq(self.tuner_note.load(Ordering::SeqCst) as f32)),
+            sequencer: SeqParams {
+                running: self.seq_running.load(Ordering::SeqCst),
+                steps: *self.seq_steps.lock().unwrap(),
+                rate: division_hz(load_f32(&self.tempo_bpm), 4), // 1/16
+                slide_time: load_f32(&self.seq_slide_time),
+                phase_reset: self.phase_reset_on_step.load(Ordering::SeqCst),
+            },
+            glide: GlideParams {
+                time: load_f32(&self.glide_time),
+                curve: GlideCurve::from_u8(self.glide_curve.load(Ordering::SeqCst)),
+            },
+            glide_mode: GlideMode::from_u8(self.glide_mode.load(Ordering::SeqCst)),
+            vel_to_release: load_f32(&self.vel_to_release),
+            pressure_to_cutoff: load_f32(&self.pressure_to_cutoff),
+            declick: self.declick.load(Ordering::SeqCst),
+            env: self.env_params(),
+            mod_env: self.mod_env_params(),
+            mod_env_target: ModEnvTarget::from_u8(self.mod_env_target.load(Ordering::SeqCst)),
+            mod_env_amount: load_f32(&self.mod_env_amount),
+            filter: self.filter_params(),
+            filter2: self.filter2_params(),
+            warmth: self.warmth_params(),
+            filter_routing: FilterRouting::from_u8(self.filter_routing.load(Ordering::SeqCst)),
+            distortion: DistortionParams {
+                drive: load_f32(&self.dist_drive),
+                oversample: if low {
+                    Oversample::Off
+                } else {
+                    Oversample::from_u8(self.dist_oversample.load(Ordering::SeqCst))
+                },
+                multiband: self
+                    .dist_multiband
+                    .load(Ordering::SeqCst)
+                    .then(|| Multiband {
+                        crossover: load_f32(&self.dist_crossover),
+                        low_drive: load_f32(&self.dist_low_drive),
+                        high_drive: load_f32(&self.dist_high_drive),
+                    }),
+            },
+            delay: self.delay_params(),
+            reverb: ReverbParams {
+                quality: if low {
+                    ReverbQuality::Low
+                } else {
+                    self.reverb_params().quality
+                },
+                ..self.reverb_params()
+            },
+            stereoize: load_f32(&self.stereoize),
+            tremolo: TremoloParams {
+                fast_math,
+                ..self.tremolo_params()
+            },
+            trance_gate: TranceGateParams {
+                enabled: self.trance_enabled.load(Ordering::SeqCst),
+                pattern: self.trance_pattern.load(Ordering::SeqCst) as u16,
+                rate: division_hz(load_f32(&self.tempo_bpm), 4), // 1/16
+            },
+            eq: EqParams {
+                low_gain: load_f32(&self.eq_low_gain),
+                low_freq: load_f32(&self.eq_low_freq),
+                mid_gain: load_f32(&self.eq_mid_gain),
+                mid_freq: load_f32(&self.eq_mid_freq),
+                high_gain: load_f32(&self.eq_high_gain),
+                high_freq: load_f32(&self.eq_high_freq),
+            },
+            compressor: self
+                .comp_enabled
+                .load(Ordering::SeqCst)
+                .then(|| CompParams {
+                    threshold: load_f32(&self.comp_threshold),
+                    ratio: load_f32(&self.comp_ratio),
+                    attack: load_f32(&self.comp_attack),
+                    release: load_f32(&self.comp_release),
+                    makeup: load_f32(&self.comp_makeup),
+                    detector: Detector::from_u8(self.comp_detector.load(Ordering::SeqCst)),
+                    knee: Knee::from_u8(self.comp_knee.load(Ordering::SeqCst)),
+                    duck: DuckParams {
+                        trigger: DuckTrigger::from_u8(self.duck_trigger.load(Ordering::SeqCst)),
+                        beat_rate: load_f32(&self.tempo_bpm) / 60.0,
+                        amount: load_f32(&self.duck_amount),
+                        length: load_f32(&self.duck_length),
+                        shape: DuckShape::from_u8(self.duck_shape.load(Ordering::SeqCst)),
+                    },
+                }),
+            looper: LooperParams {
+                length: self.loop_beats.load(Ordering::SeqCst) as f32 * 60.0
+                    / load_f32(&self.tempo_bpm),
+                overdub: self.loop_overdub.load(Ordering::SeqCst),
+            },
+            sample_hold: self.sh_params(),
+            grains: self.grain_params(),
+            grain_level: load_f32(&self.grain_level),
+            input_gain: load_f32(&self.input_gain),
+            input_monitor: self.input_monitor.load(Ordering::SeqCst),
+            waveform: Waveform::from_u8(self.waveform.load(Ordering::SeqCst)),
+            sub: SubParams {
+                level: load_f32(&self.sub_level),
+                octaves: self.sub_octave.load(Ordering::SeqCst),
+                waveform: SubWaveform::from_u8(self.sub_waveform.load(Ordering::SeqCst)),
+            },
+            wt_enabled: self.wt_enabled.load(Ordering::SeqCst),
+            wt_position: load_f32(&self.wt_position),
+            chord: ChordType::from_u8(self.chord_mode.load(Ordering::SeqCst)),
+            mono: self
+                .mono
+                .load(Ordering::SeqCst)
+                .then(|| NotePriority::from_u8(self.note_priority.load(Ordering::SeqCst))),
+            voice_steal: VoiceSteal::from_u8(self.voice_steal.load(Ordering::SeqCst)),
+            source_gains: self.source_gains(),
+            output_routing: OutputRouting::from_u8(self.output_routing.load(Ordering::SeqCst)),
+            quality: self.quality(),
+            fast_math,
+            fx_on: self.fx_on.each_ref().map(|on| on.load(Ordering::SeqCst)),
+            note_map: self.note_map(),
+            rt_priority: self.rt_priority.load(Ordering::SeqCst),
+            exiting: self.exiting.load(Ordering::SeqCst),
+            recording: self.recording.load(Ordering::SeqCst),
+            stress_mode: StressMode::from_u8(self.stress_mode.load(Ordering::SeqCst)),
+            stress_interval: self.stress_interval.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Hand the current parameter values to the audio thread as one snapshot. Writers set
+    /// the individual atomics as before, then publish once (the UI does so every frame), so
+    /// the callback never sees a mix of old and new values within a buffer. The hand-off is
+    /// lock-free: the callback never waits on a publishing thread.
+    fn publish(&self) {
+        self.published.publish(self.snapshot());
+    }
+
+    /// Queue a note for the audio thread
+    fn send_note(&self, ev: NoteEvent) {
+        if let Ok(mut events) = self.note_events.lock() {
+            events.push(ev);
+        }
+    }
+
+    /// Queue a note-on for `note` as played: scale, transpose, velocity range and curve
+    /// applied
+    fn play_note(&self, note: u8, velocity: u8) {
+        let velocity = expand_velocity(
+            velocity.min(127) as f32 / 127.0,
+            load_f32(&self.vel_min),
+            load_f32(&self.vel_max),
+        );
+        self.send_note(NoteEvent::On {
+            key: note,
+            freq: note_to_freq(self.played_note(note) as f32),
+            velocity: VelocityCurve::from_u8(self.velocity_curve.load(Ordering::SeqCst))
+                .apply(velocity),
+            slide: false,
+        });
+    }
+
+    /// A key went down on the keyboard or a MIDI controller. In latch mode it toggles: the
+    /// first press starts the note, the next one releases it.
+    fn key_down(&self, note: u8, velocity: u8) {
+        let latched = &self.latched[note as usize & 127];
+        if !self.latch.load(Ordering::SeqCst) {
+            self.play_note(note, velocity);
+        } else if latched.swap(false, Ordering::SeqCst) {
+            self.send_note(NoteEvent::Off {
+                key: note,
+                velocity: 0.5,
+            });
+        } else {
+            latched.store(true, Ordering::SeqCst);
+            self.play_note(note, velocity);
+        }
+    }
+
+    /// A key came up; a latched note keeps sounding
+    fn key_up(&self, note: u8, velocity: f32) {
+        if !self.latched[note as usize & 127].load(Ordering::SeqCst) {
+            self.send_note(NoteEvent::Off {
+                key: note,
+                velocity,
+            });
+        }
+    }
+
+    /// Switch latch mode; switching it off releases every latched note
+    fn set_latch(&self, on: bool) {
+        self.latch.store(on, Ordering::SeqCst);
+        if !on {
+            for (key, latched) in self.latched.iter().enumerate() {
+                if latched.swap(false, Ordering::SeqCst) {
+                    self.send_note(NoteEvent::Off {
+                        key: key as u8,
+                        velocity: 0.5,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Snapshot of the envelope settings (read once per audio buffer)
+    fn env_params(&self) -> EnvParams {
+        EnvParams {
+            delay: load_f32(&self.env_delay),
+            attack: load_f32(&self.env_attack),
+            hold: load_f32(&self.env_hold),
+            decay: load_f32(&self.env_decay),
+            sustain: load_f32(&self.env_sustain),
+            release: load_f32(&self.env_release),
+        }
+    }
+
+    fn mod_env_params(&self) -> EnvParams {
+        EnvParams {
+            delay: 0.0,
+            attack: load_f32(&self.mod_attack),
+            hold: 0.0,
+            decay: load_f32(&self.mod_decay),
+            sustain: load_f32(&self.mod_sustain),
+            release: load_f32(&self.mod_release),
+        }
+    }
+
+    fn filter_params(&self) -> FilterParams {
+        FilterParams {
+            cutoff: load_f32(&self.filter_cutoff),
+            resonance: load_f32(&self.filter_resonance),
+            mode: FilterMode::from_u8(self.filter_mode.load(Ordering::SeqCst)),
+            key_track: load_f32(&self.filter_key_track),
+            drive: load_f32(&self.filter_drive),
+            center_note: load_f32(&self.filter_center_note),
+        }
+    }
+
+    fn warmth_params(&self) -> Option<FilterParams> {
+        let warmth = load_f32(&self.warmth);
+        let offset = WARMTH_OPEN_SEMIS + (WARMTH_WARMEST_SEMIS - WARMTH_OPEN_SEMIS) * warmth;
+        (warmth > 0.0).then(|| FilterParams {
+            cutoff: note_to_freq(60.0 + offset),
+            // damping sqrt(2): Butterworth, flat up to the cutoff
+            resonance: 1.0 - std::f32::consts::FRAC_1_SQRT_2,
+            mode: FilterMode::LowPass,
+            key_track: 1.0,
+            center_note: 60.0,
+            drive: 1.0,
+        })
+    }
+
+    fn filter2_params(&self) -> FilterParams {
+        FilterParams {
+            cutoff: load_f32(&self.filter2_cutoff),
+            resonance: load_f32(&self.filter2_resonance),
+            mode: FilterMode::from_u8(self.filter2_mode.load(Ordering::SeqCst)),
+            ..self.filter_params()
+        }
+    }
+
+    fn delay_params(&self) -> DelayParams {
+        let time = if self.delay_sync.load(Ordering::SeqCst) {
+            let bpm = load_f32(&self.tempo_bpm);
+            self.delay_division
+                .each_ref()
+                .map(|d| 1.0 / division_hz(bpm, d.load(Ordering::SeqCst)))
+        } else {
+            [load_f32(&self.delay_time_l), load_f32(&self.delay_time_r)]
+        };
+        DelayParams {
+            time,
+            feedback: load_f32(&self.delay_feedback),
+            mix: load_f32(&self.delay_mix),
+            ping_pong: self.delay_ping_pong.load(Ordering::SeqCst),
+        }
+    }
+
+    fn reverb_params(&self) -> ReverbParams {
+        ReverbParams {
+            size: load_f32(&self.reverb_size),
+            damping: load_f32(&self.reverb_damping),
+            send: load_f32(&self.reverb_send),
+            freeze: self.reverb_freeze.load(Ordering::SeqCst),
+            quality: ReverbQuality::from_u8(self.reverb_quality.load(Ordering::SeqCst)),
+        }
+    }
+
+    fn tremolo_params(&self) -> TremoloParams {
+        let rate = if self.tremolo_sync.load(Ordering::SeqCst) {
+            division_hz(
+                load_f32(&self.tempo_bpm),
+                self.tremolo_division.load(Ordering::SeqCst),
+            )
+        } else {
+            load_f32(&self.tremolo_rate)
+        };
+        TremoloParams {
+            rate,
+            depth: load_f32(&self.tremolo_depth),
+            shape: LfoShape::from_u8(self.tremolo_shape.load(Ordering::SeqCst)),
+            fast_math: false,
+        }
+    }
+
+    fn sh_params(&self) -> ShParams {
+        ShParams {
+            rate: division_hz(
+                load_f32(&self.tempo_bpm),
+                self.sh_division.load(Ordering::SeqCst),
+            ),
+            smoothing: load_f32(&self.sh_smoothing),
+            depth: load_f32(&self.sh_depth),
+            target: ShTarget::from_u8(self.sh_target.load(Ordering::SeqCst)),
+        }
+    }
+
+    fn grain_params(&self) -> GrainParams {
+        GrainParams {
+            size: load_f32(&self.grain_size),
+            density: load_f32(&self.grain_density),
+            position: load_f32(&self.grain_position),
+            pitch: load_f32(&self.grain_pitch),
+            spray: load_f32(&self.grain_spray),
+            pitch_rand: load_f32(&self.grain_pitch_rand),
+        }
+    }
+}
+
+// ---------- GUI + App ----------
+
+/// Move captured audio from the callback's tap into `rec`. `scratch` is empty with the tap's
+/// capacity before and after, so neither side ever reallocates.
+fn drain_tap(state: &SharedState, scratch: &mut Vec<f32>, rec: &mut Recorder) {
+    std::mem::swap(&mut *state.record_tap.lock().unwrap(), scratch);
+    rec.push(scratch);
+    scratch.clear();
+}
+
+struct SynthApp {
+    state: Arc<SharedState>,
+    // UI-only: show level sliders in dB instead of linear gain
+    db_sliders: bool,
+    // UI-only: theme and open sections, saved to `UI_SETTINGS_FILE` when changed
+    settings: UiSettings,
+    // UI-only: granular sample path and the result of the last load
+    grain_path: String,
+    grain_status: String,
+    // UI-only: wavetable path and load result
+    wt_path: String,
+    wt_status: String,
+    // UI-only: which MIDI port we're listening on (or why not)
+    midi_status: String,
+    // UI-only: output devices offered in the device picker
+    output_devices: Vec<String>,
+    // UI-only: scratch copy of the scope ring, reused every frame
+    scope_buf: Vec<f32>,
+    // UI-only: scratch copy of the input capture, for the pitch detector
+    pitch_buf: Vec<f32>,
+    // UI-only: spectrum snapshot shown behind the live one for comparison
+    spectrum_frozen: Option<[f32; spectrum::BANDS]>,
+    // UI-only: XY pad returns to the center when released
+    xy_snap_back: bool,
+    // UI-only: recorder settings, the take in progress, and the last result
+    rec_path: String,
+    rec_depth: BitDepth,
+    rec_mono: bool,
+    recorder: Option<Recorder>,
+    rec_status: String,
+    // UI-only: swapped with the tap so the callback's lock is held only for the swap
+    rec_scratch: Vec<f32>,
+    // UI-only: automation lane for the assignable parameters, its loop length, and the values
+    // last seen, to spot changes
+    automation: Automation<ParamId>,
+    auto_beats: u8,
+    auto_seen: [f32; ParamId::ALL.len()],
+    // UI-only: on-screen keyboard key under the pointer, and whether click height sets velocity
+    kb_held: Option<u8>,
+    kb_velocity_from_y: bool,
+    // UI-only: notes held down on the computer keyboard
+    computer_held: Vec<u8>,
+    // UI-only: presets folder, the presets found there, the name to save under, and the result
+    // of the last load or save
+    preset_dir: String,
+    preset_entries: Vec<PresetEntry>,
+    preset_save_name: String,
+    preset_status: String,
+    // UI-only: the tag the browser is narrowed to, and the current preset's tags as typed
+    preset_tag_filter: Option<String>,
+    preset_tags_text: String,
+    // UI-only: a patch code pasted in to load
+    patch_code: String,
+    // UI-only: MIDI file path, the loaded song and load result, whether it plays at its own
+    // tempo, and the playback in progress
+    song_path: String,
+    song: Option<Arc<Song>>,
+    song_status: String,
+    song_file_tempo: bool,
+    song_playback: Option<SongPlayback>,
+    // UI-only: watchdog conditions already written to the log, so each is logged once
+    fault_logged: bool,
+    silence_logged: bool,
+}
+
+impl SynthApp {
+    fn save_settings(&self) {
+        if let Err(e) = self.settings.save(std::path::Path::new(UI_SETTINGS_FILE)) {
+            eprintln!("couldn't save the UI settings: {:?}", e);
+        }
+    }
+
+    /// One collapsible section; opening or closing it is saved straight away
+    fn section(&mut self, ui: &mut egui::Ui, section: Section) {
+        let open = self.settings.open.contains(&section);
+        let shown = egui::CollapsingHeader::new(section.label())
+            .open(Some(open))
+            .show(ui, |ui| match section {
+                Section::Patches => self.preset_browser(ui),
+                Section::Oscillators => self.oscillators_ui(ui),
+                Section::Filter => self.filter_ui(ui),
+                Section::Envelope => self.envelope_ui(ui),
+                Section::Modulation => self.modulation_ui(ui),
+                Section::Effects => self.effects_ui(ui),
+                Section::Master => self.master_ui(ui),
+                Section::Sequencing => self.sequencing_ui(ui),
+                Section::Analysis => self.analysis_ui(ui),
+            });
+        if shown.header_response.clicked() {
+            if open {
+                self.settings.open.retain(|&s| s != section);
+            } else {
+                self.settings.open.push(section);
+            }
+            self.save_settings();
+        }
+    }
+
+    /// Surface (and log once) what the audio watchdog has seen
+    fn watchdog_ui(&mut self, ui: &mut egui::Ui) {
+        if self.state.output_fault.load(Ordering::SeqCst) {
+            if !self.fault_logged {
+                eprintln!("watchdog: non-finite output replaced by silence, effects reset");
+                self.fault_logged = true;
+            }
+            ui.horizontal(|ui| {
+                ui.colored_label(
+                    Color32::RED,
+                    "⚠ NaN/Inf in the output was silenced and the effects were reset",
+                );
+                if ui.button("Dismiss").clicked() {
+                    self.state.output_fault.store(false, Ordering::SeqCst);
+                    self.fault_logged = false;
+                }
+            });
+        }
+        let silent = self.state.silent_while_gated.load(Ordering::SeqCst);
+        if silent {
+            if !self.silence_logged {
+                eprintln!("watchdog: notes held but output silent");
+            }
+            ui.colored_label(
+                Color32::YELLOW,
+                "⚠ notes are held but the output is silent (check gain, sustain, mutes)",
+            );
+        }
+        self.silence_logged = silent;
+        let late = self.state.late_callbacks.load(Ordering::SeqCst);
+        if late > 0 {
+            ui.horizontal(|ui| {
+                ui.colored_label(
+                    Color32::YELLOW,
+                    format!("⚠ {} audio callbacks overran their buffer", late),
+                );
+                if ui.button("Reset").clicked() {
+                    self.state.late_callbacks.store(0, Ordering::SeqCst);
+                }
+            });
+        }
+        if cfg!(debug_assertions) {
+            ui.horizontal(|ui| {
+                let modes = StressMode::ALL.map(StressMode::label);
+                atomic_combo(ui, &self.state.stress_mode, "stress test (debug)", &modes);
+                let mut interval = self.state.stress_interval.load(Ordering::SeqCst);
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut interval)
+                            .clamp_range(1..=10_000)
+                            .prefix("every ")
+                            .suffix(" buffers"),
+                    )
+                    .changed()
+                {
+                    self.state.stress_interval.store(interval, Ordering::SeqCst);
+                }
+            });
+        }
+    }
+
+    /// Recorder settings and record/stop. Also moves captured audio out of the tap, so it
+    /// has to run every frame.
+    fn recorder_ui(&mut self, ui: &mut egui::Ui) {
+        if let Some(rec) = &mut self.recorder {
+            drain_tap(&self.state, &mut self.rec_scratch, rec);
+        }
+
+        let recording = self.recorder.is_some();
+        ui.add_enabled_ui(!recording, |ui| {
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.rec_path);
+                egui::ComboBox::from_label("format")
+                    .selected_text(self.rec_depth.label())
+                    .show_ui(ui, |ui| {
+                        for d in BitDepth::ALL {
+                            ui.selectable_value(&mut self.rec_depth, d, d.label());
+                        }
+                    });
+                ui.checkbox(&mut self.rec_mono, "Mono");
+            });
+        });
+        ui.horizontal(|ui| match self.recorder.take() {
+            None => {
+                if ui.button("Record").clicked() {
+                    self.state.record_tap.lock().unwrap().clear();
+                    self.state.record_overrun.store(false, Ordering::SeqCst);
+                    self.recorder =
+                        Some(Recorder::new(self.state.sample_rate.load(Ordering::SeqCst)));
+                    self.state.recording.store(true, Ordering::SeqCst);
+                }
+            }
+            Some(mut rec) => {
+                ui.label(format!("recording {:.1} s", rec.seconds()));
+                if !ui.button("Stop").clicked() {
+                    self.recorder = Some(rec);
+                    return;
+                }
+                self.state.recording.store(false, Ordering::SeqCst);
+                drain_tap(&self.state, &mut self.rec_scratch, &mut rec);
+                let secs = rec.seconds();
+                self.rec_status =
+                    match rec.finish(self.rec_path.trim(), self.rec_depth, self.rec_mono) {
+                        Ok(()) => format!("wrote {:.1} s to {}", secs, self.rec_path.trim()),
+                        Err(e) => format!("write failed: {}", e),
+                    };
+                if self.state.record_overrun.load(Ordering::SeqCst) {
+                    self.rec_status.push_str(" (some audio was dropped)");
+                }
+            }
+        });
+        if !self.rec_status.is_empty() {
+            ui.small(&self.rec_status);
+        }
+    }
+
+    /// Loop length, record/overdub/clear, and where playback is in the loop
+    fn looper_ui(&mut self, ui: &mut egui::Ui) {
+        let loop_state = LoopState::from_u8(self.state.loop_state.load(Ordering::SeqCst));
+        ui.horizontal(|ui| {
+            let mut beats = self.state.loop_beats.load(Ordering::SeqCst);
+            // the length is fixed per take; clear to record a different one
+            let resp = ui.add_enabled(
+                loop_state == LoopState::Empty,
+                egui::Slider::new(&mut beats, 1..=16).text("length (beats)"),
+            );
+            if resp.changed() {
+                self.state.loop_beats.store(beats, Ordering::SeqCst);
+            }
+            ui.small(format!(
+                "{:.2} s",
+                beats as f32 * 60.0 / load_f32(&self.state.tempo_bpm)
+            ));
+        });
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    loop_state == LoopState::Empty,
+                    egui::Button::new("Record loop"),
+                )
+                .clicked()
+            {
+                self.state.loop_record.store(true, Ordering::SeqCst);
+            }
+            let mut overdub = self.state.loop_overdub.load(Ordering::SeqCst);
+            if ui.checkbox(&mut overdub, "Overdub").changed() {
+                self.state.loop_overdub.store(overdub, Ordering::SeqCst);
+            }
+            if ui
+                .add_enabled(loop_state != LoopState::Empty, egui::Button::new("Clear"))
+                .clicked()
+            {
+                self.state.loop_clear.store(true, Ordering::SeqCst);
+            }
+            ui.label(loop_state.label());
+        });
+        ui.add(egui::ProgressBar::new(load_f32(&self.state.loop_position)).desired_height(6.0));
+    }
+
+    /// Load a preset file dropped on the window, highlighting the window while one hovers
+    fn preset_drop(&mut self, ctx: &egui::Context) {
+        let (hovering, dropped) = ctx.input(|i| {
+            let dropped = i.raw.dropped_files.iter().find_map(|f| f.path.clone());
+            (!i.raw.hovered_files.is_empty(), dropped)
+        });
+        if hovering {
+            let painter = ctx.layer_painter(egui::LayerId::new(
+                egui::Order::Foreground,
+                egui::Id::new("preset_drop"),
+            ));
+            let screen = ctx.screen_rect();
+            painter.rect_filled(screen, 0.0, Color32::from_black_alpha(160));
+            painter.rect_stroke(screen.shrink(4.0), 8.0, (3.0, Color32::LIGHT_BLUE));
+            painter.text(
+                screen.center(),
+                egui::Align2::CENTER_CENTER,
+                "Drop a .json preset to load it",
+                egui::FontId::proportional(24.0),
+                Color32::WHITE,
+            );
+        }
+        if let Some(path) = dropped {
+            self.preset_status = match Preset::load_dropped(&path) {
+                Ok(preset) => {
+                    self.state.apply_preset(&preset);
+                    format!("loaded {}", preset.name)
+                }
+                Err(e) => format!("load failed: {}", e),
+            };
+        }
+    }
+
+    /// Play notes from the computer keyboard (`COMPUTER_KEYS`), except into a focused text field
+    fn computer_keyboard(&mut self, ctx: &egui::Context) {
+        let typing = ctx.wants_keyboard_input();
+        let keys: Vec<(egui::Key, bool)> = ctx.input(|i| {
+            i.events
+                .iter()
+                .filter_map(|ev| match *ev {
+                    egui::Event::Key { key, pressed, .. } => Some((key, pressed)),
+                    _ => None,
+                })
+                .collect()
+        });
+        for (key, pressed) in keys {
+            match computer_key(&mut self.computer_held, key, pressed, typing) {
+                Some((note, true)) => self.state.key_down(note, COMPUTER_KEY_VELOCITY),
+                Some((note, false)) => self.state.key_up(note, 0.5),
+                None => {}
+            }
+        }
+    }
+
+    /// Clickable two-octave keyboard; dragging across the keys moves the note with the pointer
+    fn keyboard(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(
+                &mut self.kb_velocity_from_y,
+                "Velocity from click height (bottom of a key = full)",
+            );
+            let mut latch = self.state.latch.load(Ordering::SeqCst);
+            if ui
+                .checkbox(&mut latch, "Latch")
+                .on_hover_text("a press toggles the note on or off, keyboard and MIDI alike")
+                .changed()
+            {
+                self.state.set_latch(latch);
+            }
+        });
+        let whites = (KB_LOW..=KB_HIGH).filter(|&n| !is_black_key(n)).count();
+        let (rect, resp) = ui.allocate_exact_size(
+            egui::vec2(ui.available_width().min(480.0), 90.0),
+            egui::Sense::click_and_drag(),
+        );
+        let white_w = rect.width() / whites as f32;
+        let key_rect = |note: u8| {
+            // white keys to the left of this key's left edge (or center, for black keys)
+            let before = (KB_LOW..note).filter(|&n| !is_black_key(n)).count() as f32;
+            let x = rect.left() + before * white_w;
+            if is_black_key(note) {
+                egui::Rect::from_min_max(
+                    egui::pos2(x - 0.3 * white_w, rect.top()),
+                    egui::pos2(x + 0.3 * white_w, rect.top() + 0.6 * rect.height()),
+                )
+            } else {
+                egui::Rect::from_min_max(
+                    egui::pos2(x, rect.top()),
+                    egui::pos2(x + white_w, rect.bottom()),
+                )
+            }
+        };
+        // black keys sit on top, so they win the hit test
+        let key_at = |pos: egui::Pos2| {
+            (KB_LOW..=KB_HIGH)
+                .filter(|&n| is_black_key(n))
+                .chain((KB_LOW..=KB_HIGH).filter(|&n| !is_black_key(n)))
+                .find(|&n| key_rect(n).contains(pos))
+        };
+
+        let pressed = resp
+            .interact_pointer_pos()
+            .filter(|_| resp.is_pointer_button_down_on())
+            .and_then(|pos| key_at(pos).map(|n| (n, pos)));
+        let now = pressed.map(|(n, _)| n);
+        if now != self.kb_held {
+            if let Some(old) = self.kb_held {
+                self.state.key_up(old, 0.5);
+            }
+            if let Some((note, pos)) = pressed {
+                let velocity = if self.kb_velocity_from_y {
+                    let r = key_rect(note);
+                    key_velocity((pos.y - r.top()) / r.height())
+                } else {
+                    127
+                };
+                self.state.key_down(note, velocity);
+            }
+            self.kb_held = now;
+        }
+
+        let painter = ui.painter_at(rect);
+        let outline = egui::Stroke::new(1.0, Color32::from_gray(60));
+        for black in [false, true] {
+            for note in (KB_LOW..=KB_HIGH).filter(|&n| is_black_key(n) == black) {
+                let lit = Some(note) == self.kb_held
+                    || self.state.latched[note as usize].load(Ordering::SeqCst);
+                let fill = match (lit, black) {
+                    (true, _) => Color32::LIGHT_BLUE,
+                    (false, true) => Color32::from_gray(25),
+                    (false, false) => Color32::from_gray(230),
+                };
+                painter.rect(key_rect(note), 2.0, fill, outline);
+            }
+        }
+        // what the hovered key actually plays, after the scale and transpose
+        if let Some(note) = resp.hover_pos().and_then(key_at) {
+            let hz = note_to_freq(self.state.played_note(note) as f32);
+            let (played, _) = freq_to_note_name(hz);
+            let key = note_name(note);
+            resp.on_hover_text(if played == key {
+                format!("{}: {:.2} Hz", key, hz)
+            } else {
+                format!("{} (plays {}): {:.2} Hz", key, played, hz)
+            });
+        }
+    }
+
+    fn scan_presets(&mut self) {
+        match presets::scan(std::path::Path::new(self.preset_dir.trim())) {
+            Ok(entries) => self.preset_entries = entries,
+            Err(e) => {
+                self.preset_entries.clear();
+                self.preset_status = format!("can't read the folder: {}", e);
+            }
+        }
+    }
+
+    /// The presets in a folder, loaded with a click, and saving the current sound there
+    fn preset_browser(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("folder");
+            ui.text_edit_singleline(&mut self.preset_dir);
+            if ui.button("Refresh").clicked() {
+                self.preset_status.clear();
+                self.scan_presets();
+            }
+        });
+        let tags = presets::all_tags(&self.preset_entries);
+        // a tag that's gone from the folder (after a refresh) would hide everything
+        if self
+            .preset_tag_filter
+            .as_ref()
+            .is_some_and(|t| !tags.contains(t))
+        {
+            self.preset_tag_filter = None;
+        }
+        if !tags.is_empty() {
+            egui::ComboBox::from_label("tag")
+                .selected_text(self.preset_tag_filter.as_deref().unwrap_or("all"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.preset_tag_filter, None, "all");
+                    for tag in tags {
+                        let label = tag.clone();
+                        ui.selectable_value(&mut self.preset_tag_filter, Some(tag), label);
+                    }
+                });
+        }
+        let current = self.state.preset_name.lock().unwrap().clone();
+        let filter = self.preset_tag_filter.as_deref();
+        let mut load = None;
+        if self.preset_entries.is_empty() {
+            ui.small("no presets in this folder");
+        }
+        egui::ScrollArea::vertical()
+            .id_source("preset_browser")
+            .max_height(120.0)
+            .show(ui, |ui| {
+                for entry in self.preset_entries.iter().filter(|e| e.matches(filter)) {
+                    match &entry.error {
+                        None => {
+                            if ui
+                                .selectable_label(entry.name == current, &entry.name)
+                                .clicked()
+                            {
+                                load = Some(entry.path.clone());
+                            }
+                        }
+                        Some(e) => {
+                            ui.colored_label(Color32::LIGHT_RED, format!("{}: {}", entry.name, e));
+                        }
+                    }
+                }
+            });
+        if let Some(path) = load {
+            match Preset::load(&path) {
+                Ok(preset) => {
+                    self.state.apply_preset(&preset);
+                    self.preset_status = format!("loaded {}", preset.name);
+                }
+                Err(e) => self.preset_status = format!("load failed: {}", e),
+            }
+        }
+        ui.horizontal(|ui| {
+            ui.label("description");
+            ui.text_edit_singleline(&mut *self.state.preset_description.lock().unwrap());
+        });
+        ui.horizontal(|ui| {
+            // retyped from the preset's tags whenever they change underneath (a load)
+            let mut tags = self.state.preset_tags.lock().unwrap();
+            if presets::parse_tags(&self.preset_tags_text) != *tags {
+                self.preset_tags_text = tags.join(", ");
+            }
+            ui.label("tags");
+            if ui
+                .add(
+                    egui::TextEdit::singleline(&mut self.preset_tags_text)
+                        .hint_text("bass, pad, aggressive"),
+                )
+                .changed()
+            {
+                *tags = presets::parse_tags(&self.preset_tags_text);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.preset_save_name);
+            let name = self.preset_save_name.trim().to_string();
+            if ui
+                .add_enabled(!name.is_empty(), egui::Button::new("Save current"))
+                .clicked()
+            {
+                let dir = std::path::PathBuf::from(self.preset_dir.trim());
+                let preset = self.state.current_preset(&name);
+                let saved = std::fs::create_dir_all(&dir)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|()| preset.save(&dir.join(presets::file_name(&name))));
+                self.preset_status = match saved {
+                    Ok(()) => {
+                        self.state.apply_preset(&preset);
+                        format!("saved {}", name)
+                    }
+                    Err(e) => format!("save failed: {}", e),
+                };
+                self.scan_presets();
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui
+                .button("Copy code")
+                .on_hover_text("copy the current sound as a short code to paste anywhere")
+                .clicked()
+            {
+                let name = self.state.preset_name.lock().unwrap().clone();
+                let code = self.state.current_preset(&name).to_code();
+                ui.output_mut(|o| o.copied_text = code);
+                self.preset_status = "patch code copied".to_string();
+            }
+            ui.add(egui::TextEdit::singleline(&mut self.patch_code).hint_text("paste a code"));
+            if ui
+                .add_enabled(
+                    !self.patch_code.trim().is_empty(),
+                    egui::Button::new("Load code"),
+                )
+                .clicked()
+            {
+                self.preset_status = match Preset::from_code(&self.patch_code) {
+                    Ok(preset) => {
+                        self.state.apply_preset(&preset);
+                        self.patch_code.clear();
+                        format!("loaded {}", preset.name)
+                    }
+                    Err(e) => format!("load failed: {}", e),
+                };
+            }
+        });
+        if !self.preset_status.is_empty() {
+            ui.small(&self.preset_status);
+        }
+    }
+
+    /// Master output meter on a dB scale with a reference line (right-click to move it) and
+    /// how far the peak is above or below it
+    fn output_meter(&self, ui: &mut egui::Ui) {
+        let peak = linear_to_db(load_f32(&self.state.peak_output)).max(MIN_DB);
+        let reference = load_f32(&self.state.meter_reference);
+        ui.horizontal(|ui| {
+            ui.label("output");
+            let (rect, resp) =
+                ui.allocate_exact_size(egui::vec2(240.0, 14.0), egui::Sense::click());
+            let x_at = |db: f32| rect.left() + (1.0 - db / MIN_DB).clamp(0.0, 1.0) * rect.width();
+            if resp.secondary_clicked() {
+                if let Some(pos) = resp.interact_pointer_pos() {
+                    let db = MIN_DB * (1.0 - (pos.x - rect.left()) / rect.width());
+                    store_f32(&self.state.meter_reference, db.clamp(MIN_DB, 0.0).round());
+                }
+            }
+            let painter = ui.painter_at(rect);
+            painter.rect_filled(rect, 2.0, Color32::from_gray(30));
+            let color = if peak >= -0.1 {
+                Color32::RED
+            } else if peak > reference {
+                Color32::YELLOW
+            } else {
+                Color32::GREEN
+            };
+            let mut bar = rect;
+            bar.set_right(x_at(peak));
+            painter.rect_filled(bar, 2.0, color);
+            painter.vline(
+                x_at(reference),
+                rect.y_range(),
+                egui::Stroke::new(2.0, Color32::WHITE),
+            );
+            resp.on_hover_text("right-click to set the reference level");
+            if peak <= MIN_DB {
+                ui.label(format!("-inf (ref {:.0} dBFS)", reference));
+            } else {
+                ui.label(format!(
+                    "{:+.1} dB vs ref {:.0} dBFS",
+                    peak - reference,
+                    reference
+                ));
+            }
+        });
+    }
+
+    /// Output spectrum as log-spaced bars, from the scope capture. "Freeze" keeps a copy
+    /// drawn behind the live bars, to compare against after changing the sound.
+    fn spectrum_ui(&mut self, ui: &mut egui::Ui) {
+        let sample_rate = self.state.sample_rate.load(Ordering::SeqCst) as f32;
+        let live = spectrum::bands_db(&self.scope_buf, sample_rate);
+        let (rect, resp) = ui.allocate_exact_size(
+            egui::vec2(ui.available_width(), 120.0),
+            egui::Sense::hover(),
+        );
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, Color32::from_gray(20));
+        let band_w = rect.width() / spectrum::BANDS as f32;
+        let bar = |band: usize, db: f32| {
+            let top = rect.bottom() - rect.height() * (1.0 - db / spectrum::FLOOR_DB);
+            egui::Rect::from_x_y_ranges(
+                rect.left() + band as f32 * band_w..=rect.left() + (band + 1) as f32 * band_w,
+                top..=rect.bottom(),
+            )
+        };
+        if let Some(frozen) = &self.spectrum_frozen {
+            for (band, &db) in frozen.iter().enumerate() {
+                painter.rect_filled(bar(band, db), 0.0, Color32::from_rgb(140, 60, 160));
+            }
+        }
+        for (band, &db) in live.iter().enumerate() {
+            // narrower, so the frozen bars stay visible on either side
+            painter.rect_filled(
+                bar(band, db).shrink2(egui::vec2(band_w * 0.25, 0.0)),
+                0.0,
+                Color32::LIGHT_GREEN,
+            );
+        }
+        if let Some(pos) = resp.hover_pos() {
+            let band = (((pos.x - rect.left()) / band_w) as usize).min(spectrum::BANDS - 1);
+            resp.on_hover_text(format!(
+                "{:.0} Hz: {:.0} dB",
+                spectrum::band_hz(band),
+                live[band]
+            ));
+        }
+        ui.horizontal(|ui| {
+            if ui.button("Freeze").clicked() {
+                self.spectrum_frozen = Some(live);
+            }
+            if ui
+                .add_enabled(self.spectrum_frozen.is_some(), egui::Button::new("Clear"))
+                .clicked()
+            {
+                self.spectrum_frozen = None;
+            }
+        });
+    }
+
+    /// Phase correlation of the output, -1..+1, as a needle: green toward +1 (mono-safe), red
+    /// toward -1 (cancels in mono)
+    fn correlation_meter(&self, ui: &mut egui::Ui) {
+        let c = load_f32(&self.state.correlation);
+        ui.horizontal(|ui| {
+            ui.label("phase");
+            let (rect, _) = ui.allocate_exact_size(egui::vec2(240.0, 14.0), egui::Sense::hover());
+            let painter = ui.painter_at(rect);
+            painter.rect_filled(rect, 2.0, Color32::from_gray(30));
+            painter.vline(
+                rect.center().x,
+                rect.y_range(),
+                egui::Stroke::new(1.0, Color32::from_gray(70)),
+            );
+            let color = if c > 0.3 {
+                Color32::GREEN
+            } else if c > -0.3 {
+                Color32::YELLOW
+            } else {
+                Color32::RED
+            };
+            let x = rect.center().x + c * rect.width() / 2.0;
+            painter.vline(x, rect.y_range(), egui::Stroke::new(3.0, color));
+            ui.label(format!("{:+.2}", c));
+        });
+    }
+
+    /// On/off switch and a 16-cell pattern editor; the step playing is outlined
+    fn trance_gate_ui(&mut self, ui: &mut egui::Ui) {
+        let state = &self.state;
+        let mut enabled = state.trance_enabled.load(Ordering::SeqCst);
+        if ui.checkbox(&mut enabled, "Enabled").changed() {
+            state.trance_enabled.store(enabled, Ordering::SeqCst);
+        }
+        let mut pattern = state.trance_pattern.load(Ordering::SeqCst);
+        let playing = state.trance_step.load(Ordering::SeqCst) as usize;
+        ui.horizontal(|ui| {
+            ui.spacing_mut().item_spacing.x = 2.0;
+            for step in 0..trance_gate::STEPS {
+                let open = pattern & (1 << step) != 0;
+                let fill = match (open, step.is_multiple_of(4)) {
+                    (true, _) => Color32::LIGHT_GREEN,
+                    (false, true) => Color32::from_gray(70),
+                    (false, false) => Color32::from_gray(45),
+                };
+                let stroke = if enabled && step == playing {
+                    egui::Stroke::new(2.0, Color32::WHITE)
+                } else {
+                    egui::Stroke::NONE
+                };
+                let cell = egui::Button::new("")
+                    .fill(fill)
+                    .stroke(stroke)
+                    .min_size(egui::vec2(18.0, 18.0));
+                if ui.add(cell).clicked() {
+                    pattern ^= 1 << step;
+                }
+            }
+        });
+        state.trance_pattern.store(pattern, Ordering::SeqCst);
+    }
+
+    /// Run switch, slide time, and one column per step: on, note, slide ("S")
+    fn sequencer_ui(&mut self, ui: &mut egui::Ui) {
+        let state = &self.state;
+        let running = state.seq_running.load(Ordering::SeqCst);
+        ui.horizontal(|ui| {
+            let mut run = running;
+            if ui.toggle_value(&mut run, "Run").changed() {
+                state.seq_running.store(run, Ordering::SeqCst);
+            }
+            atomic_slider(ui, &state.seq_slide_time, 0.5, "slide time (s/octave)");
+            let mut reset = state.phase_reset_on_step.load(Ordering::SeqCst);
+            if ui
+                .checkbox(&mut reset, "Phase reset")
+                .on_hover_text("restart the oscillators on every step, for a pulsing timbre")
+                .changed()
+            {
+                state.phase_reset_on_step.store(reset, Ordering::SeqCst);
+            }
+        });
+        let playing = state.seq_step.load(Ordering::SeqCst) as usize;
+        let mut steps = *state.seq_steps.lock().unwrap();
+        egui::Grid::new("sequencer")
+            .spacing(egui::vec2(2.0, 2.0))
+            .show(ui, |ui| {
+                for (i, step) in steps.iter_mut().enumerate() {
+                    let text = if running && i == playing {
+                        egui::RichText::new((i + 1).to_string()).strong()
+                    } else {
+                        egui::RichText::new((i + 1).to_string())
+                    };
+                    ui.toggle_value(&mut step.on, text);
+                }
+                ui.end_row();
+                for step in steps.iter_mut() {
+                    ui.add(
+                        egui::DragValue::new(&mut step.note)
+                            .clamp_range(24..=96)
+                            .custom_formatter(|n, _| note_name(n as u8)),
+                    );
+                }
+                ui.end_row();
+                for step in steps.iter_mut() {
+                    ui.toggle_value(&mut step.slide, "S")
+                        .on_hover_text("glide into this note at the slide time");
+                }
+                ui.end_row();
+                for step in steps.iter_mut() {
+                    ui.add(
+                        egui::DragValue::new(&mut step.gate)
+                            .clamp_range(MIN_GATE..=1.0)
+                            .speed(0.01)
+                            .custom_formatter(|g, _| format!("{:.0}%", g * 100.0))
+                            .custom_parser(|s| {
+                                s.trim_end_matches('%')
+                                    .trim()
+                                    .parse::<f64>()
+                                    .ok()
+                                    .map(|g| g / 100.0)
+                            }),
+                    )
+                    .on_hover_text("gate: how much of the step the note is held for");
+                }
+                ui.end_row();
+            });
+        *state.seq_steps.lock().unwrap() = steps;
+    }
+
+    /// Load a MIDI file and play it through the synth
+    fn song_ui(&mut self, ui: &mut egui::Ui) {
+        if self
+            .song_playback
+            .as_ref()
+            .is_some_and(|p| p.thread.is_finished())
+        {
+            self.song_playback = None;
+        }
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.song_path);
+            if ui.button("Load MIDI file").clicked() {
+                match Song::load(self.song_path.trim()) {
+                    Ok(song) => {
+                        let (beats, secs) = song.length();
+                        self.song_status = format!(
+                            "{} tracks, {} note events, {:.1} beats ({:.1} s at the file's tempo)",
+                            song.tracks,
+                            song.notes.len(),
+                            beats,
+                            secs
+                        );
+                        self.song = Some(Arc::new(song));
+                    }
+                    Err(e) => self.song_status = format!("load failed: {}", e),
+                }
+            }
+        });
+        if !self.song_status.is_empty() {
+            ui.small(&self.song_status);
+        }
+        ui.horizontal(|ui| {
+            ui.add_enabled(
+                self.song_playback.is_none(),
+                egui::Checkbox::new(&mut self.song_file_tempo, "Use the file's tempo"),
+            );
+            let can_play = self.song.is_some() && self.song_playback.is_none();
+            if ui
+                .add_enabled(can_play, egui::Button::new("Play"))
+                .clicked()
+            {
+                if let Some(song) = &self.song {
+                    self.song_playback = Some(SongPlayback::start(
+                        self.state.clone(),
+                        song.clone(),
+                        self.song_file_tempo,
+                    ));
+                }
+            }
+            if ui
+                .add_enabled(self.song_playback.is_some(), egui::Button::new("Stop"))
+                .clicked()
+            {
+                if let Some(p) = &self.song_playback {
+                    p.stop.store(true, Ordering::SeqCst);
+                }
+            }
+        });
+        let progress = self
+            .song_playback
+            .as_ref()
+            .map_or(0.0, |p| load_f32(&p.progress));
+        ui.add(egui::ProgressBar::new(progress).desired_height(6.0));
+    }
+
+    /// Record and loop moves of the assignable parameters (sliders or XY pad). Also captures
+    /// and applies the changes, so it has to run every frame.
+    fn automation_ui(&mut self, ui: &mut egui::Ui) {
+        let now = self.state.sample_clock.load(Ordering::SeqCst) as f32
+            / self.state.sample_rate.load(Ordering::SeqCst) as f32;
+        let length = self.auto_beats as f32 * 60.0 / load_f32(&self.state.tempo_bpm);
+
+        for (i, id) in ParamId::ALL.into_iter().enumerate() {
+            if let Some(a) = id.atomic(&self.state) {
+                let v = load_f32(a);
+                if v != self.auto_seen[i] {
+                    self.automation.capture(now, id, v);
+                    self.auto_seen[i] = v;
+                }
+            }
+        }
+        let (state, seen) = (&self.state, &mut self.auto_seen);
+        self.automation.advance(now, length, |id, v| {
+            if let Some(a) = id.atomic(state) {
+                store_f32(a, v);
+            }
+            // a replayed value isn't a new move
+            seen[id as usize] = v;
+        });
+
+        let auto_state = self.automation.state();
+        ui.horizontal(|ui| {
+            ui.add_enabled(
+                auto_state != AutomationState::Recording,
+                egui::Slider::new(&mut self.auto_beats, 1..=32).text("length (beats)"),
+            );
+            if ui.button("Record").clicked() {
+                self.automation.record(now);
+            }
+            let can_play = auto_state == AutomationState::Idle && self.automation.event_count() > 0;
+            if ui
+                .add_enabled(can_play, egui::Button::new("Play"))
+                .clicked()
+            {
+                self.automation.play(now);
+            }
+            if ui
+                .add_enabled(
+                    auto_state != AutomationState::Idle,
+                    egui::Button::new("Stop"),
+                )
+                .clicked()
+            {
+                self.automation.stop();
+            }
+            if ui.button("Clear").clicked() {
+                self.automation.clear();
+            }
+            ui.label(format!(
+                "{}, {} changes",
+                auto_state.label(),
+                self.automation.event_count()
+            ));
+        });
+        ui.add(egui::ProgressBar::new(self.automation.position(now, length)).desired_height(6.0));
+    }
+
+    /// Move the XY pad to (x, y) and push the values to the assigned parameters
+    fn set_xy(&self, x: f32, y: f32) {
+        store_f32(&self.state.xy_x, x);
+        store_f32(&self.state.xy_y, y);
+        ParamId::from_u8(self.state.xy_x_target.load(Ordering::SeqCst))
+            .set_normalized(&self.state, x);
+        ParamId::from_u8(self.state.xy_y_target.load(Ordering::SeqCst))
+            .set_normalized(&self.state, y);
+    }
+
+    /// Square pad with a draggable dot; X and Y drive two assignable parameters
+    fn xy_pad(&mut self, ui: &mut egui::Ui) {
+        let targets = ParamId::ALL.map(ParamId::label);
+        ui.horizontal(|ui| {
+            atomic_combo(ui, &self.state.xy_x_target, "X", &targets);
+            atomic_combo(ui, &self.state.xy_y_target, "Y", &targets);
+            ui.checkbox(&mut self.xy_snap_back, "Snap back to center");
+        });
+
+        let (rect, resp) =
+            ui.allocate_exact_size(egui::vec2(160.0, 160.0), egui::Sense::click_and_drag());
+        if let Some(pos) = resp.interact_pointer_pos() {
+            if resp.dragged() || resp.clicked() {
+                let x = ((pos.x - rect.left()) / rect.width()).clamp(0.0, 1.0);
+                let y = ((rect.bottom() - pos.y) / rect.height()).clamp(0.0, 1.0);
+                self.set_xy(x, y);
+            }
+        }
+        if resp.drag_stopped() && self.xy_snap_back {
+            self.set_xy(0.5, 0.5);
+        }
+
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 4.0, Color32::from_gray(30));
+        let grid = egui::Stroke::new(1.0, Color32::from_gray(60));
+        painter.hline(rect.x_range(), rect.center().y, grid);
+        painter.vline(rect.center().x, rect.y_range(), grid);
+        let dot = egui::pos2(
+            rect.left() + load_f32(&self.state.xy_x) * rect.width(),
+            rect.bottom() - load_f32(&self.state.xy_y) * rect.height(),
+        );
+        painter.circle_filled(dot, 6.0, Color32::LIGHT_BLUE);
+    }
+
+    /// Oscillators: tuning, voicing and the other sound sources
+    fn oscillators_ui(&mut self, ui: &mut egui::Ui) {
+        // controls edit the stored settings and apply immediately; modulation (S&H,
+        // envelopes) works on the callback's own snapshot and is never written back, so a
+        // slider only moves when the user (or the XY pad) moves it
+        let mut sound_edited = false;
+
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "Preset: {}",
+                self.state.preset_name.lock().unwrap()
+            ));
+            let mut norm = self.state.normalize_loudness.load(Ordering::SeqCst);
+            if ui.checkbox(&mut norm, "Normalize loudness").changed() {
+                self.state.normalize_loudness.store(norm, Ordering::SeqCst);
+            }
+            ui.label(format!(
+                "trim {}",
+                db_label(load_f32(&self.state.preset_trim))
+            ));
+        });
+        let waves = Waveform::ALL.map(Waveform::label);
+        let waveform = self.state.waveform.load(Ordering::SeqCst);
+        atomic_combo(ui, &self.state.waveform, "waveform", &waves);
+        sound_edited |= self.state.waveform.load(Ordering::SeqCst) != waveform;
+        ui.horizontal(|ui| {
+            atomic_slider(ui, &self.state.sub_level, 1.0, "sub level")
+                .on_hover_text("oscillator A divided down, for weight under the bass");
+            let mut octaves = self.state.sub_octave.load(Ordering::SeqCst);
+            for n in [1, 2] {
+                ui.radio_value(&mut octaves, n, format!("-{} oct", n));
+            }
+            self.state.sub_octave.store(octaves, Ordering::SeqCst);
+            let subs = SubWaveform::ALL.map(SubWaveform::label);
+            atomic_combo(ui, &self.state.sub_waveform, "sub wave", &subs);
+        });
+        atomic_slider(ui, &self.state.warmth, 1.0, "warmth")
+            .on_hover_text("a gentle low-pass following each note: 0 = open, 1 = an octave above");
+        sound_edited |= atomic_slider(ui, &self.state.osc_mix, 1.0, "osc mix").changed();
+        for (src, name, peak) in [
+            (SRC_OSC_A, "osc A", &self.state.peak_osc_a),
+            (SRC_OSC_B, "osc B", &self.state.peak_osc_b),
+        ] {
+            ui.horizontal(|ui| {
+                level_meter(ui, name, load_f32(peak));
+                mute_solo(
+                    ui,
+                    &self.state.source_mute[src],
+                    &self.state.source_solo[src],
+                );
+            });
+            ui.horizontal(|ui| {
+                let mut octave = self.state.osc_octave[src].load(Ordering::SeqCst);
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut octave)
+                            .clamp_range(-3..=3)
+                            .prefix("oct "),
+                    )
+                    .changed()
+                {
+                    self.state.osc_octave[src].store(octave, Ordering::SeqCst);
+                }
+                let mut coarse = self.state.osc_coarse[src].load(Ordering::SeqCst);
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut coarse)
+                            .clamp_range(-12..=12)
+                            .prefix("st "),
+                    )
+                    .changed()
+                {
+                    self.state.osc_coarse[src].store(coarse, Ordering::SeqCst);
+                }
+                let mut fine = load_f32(&self.state.osc_fine[src]);
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut fine)
+                            .speed(0.5)
+                            .clamp_range(-100.0..=100.0)
+                            .prefix("fine ")
+                            .suffix(" ct"),
+                    )
+                    .changed()
+                {
+                    store_f32(&self.state.osc_fine[src], fine);
+                }
+                // what the demo tone's oscillator actually sounds at
+                let hz = self.state.key_track(src).hz(load_f32(&self.state.freq_hz))
+                    * 2f32.powf(self.state.osc_pitch(src) / 12.0);
+                let (name, cents) = freq_to_note_name(hz);
+                ui.label(format!("sounds {} {:+.1} ct ({:.1} Hz)", name, cents, hz));
+            });
+            ui.horizontal(|ui| {
+                atomic_slider(ui, &self.state.osc_key_track[src], 1.0, "key track")
+                    .on_hover_text("1 follows the keyboard; 0 holds the fixed pitch (a drone)");
+                if load_f32(&self.state.osc_key_track[src]) < 1.0 {
+                    atomic_slider_range(
+                        ui,
+                        &self.state.osc_fixed_hz[src],
+                        20.0..=2_000.0,
+                        "fixed (Hz)",
+                    );
+                }
+            });
+        }
+        let mut detune = load_f32(&self.state.detune);
+        if ui
+            .add(
+                egui::DragValue::new(&mut detune)
+                    .speed(0.1)
+                    .clamp_range(-100.0..=100.0)
+                    .prefix("detune: "),
+            )
+            .changed()
+        {
+            store_f32(&self.state.detune, detune);
+            sound_edited = true;
+        }
+        ui.horizontal(|ui| {
+            sound_edited |= atomic_slider(ui, &self.state.gain, 2.0, "gain").changed();
+            ui.label(db_label(load_f32(&self.state.gain)));
+        });
+        ui.horizontal(|ui| {
+            atomic_slider(ui, &self.state.analog_drift, 25.0, "analog drift (cents)");
+            let mut precision = self.state.precision.load(Ordering::SeqCst);
+            if ui
+                .checkbox(&mut precision, "Precision")
+                .on_hover_text("no drift, humanize, pitch modulation or tremolo: exact tuning")
+                .changed()
+            {
+                self.state.precision.store(precision, Ordering::SeqCst);
+            }
+        });
+        atomic_slider(ui, &self.state.humanize, 20.0, "humanize (cents)")
+            .on_hover_text("each note starts up to this far out of tune, at random");
+        ui.horizontal(|ui| {
+            let mut seed = self.state.random_seed.load(Ordering::SeqCst);
+            if ui
+                .add(egui::DragValue::new(&mut seed).prefix("random seed "))
+                .on_hover_text(
+                    "drift, humanize, noise, strings, S&H and grains all start from this: \
+                     the same seed plays back the same",
+                )
+                .changed()
+            {
+                self.state.random_seed.store(seed, Ordering::SeqCst);
+            }
+        });
+        let mut reset = self.state.reset_phase_on_note.load(Ordering::SeqCst);
+        if ui
+            .checkbox(&mut reset, "Reset phase on note")
+            .on_hover_text(
+                "every note starts the waveform from zero, for identical attacks; \
+                 off, a retriggered note carries on where it was",
+            )
+            .changed()
+        {
+            self.state
+                .reset_phase_on_note
+                .store(reset, Ordering::SeqCst);
+        }
+        atomic_slider(ui, &self.state.phase_offset, 1.0, "osc B phase offset");
+        atomic_slider(ui, &self.state.phase_width, 0.5, "stereo phase width")
+            .on_hover_text("beyond about 0.25 the mono sum starts to cancel");
+        ui.horizontal(|ui| {
+            let mut fm = self.state.fm_enabled.load(Ordering::SeqCst);
+            if ui.checkbox(&mut fm, "FM (B modulates A)").changed() {
+                self.state.fm_enabled.store(fm, Ordering::SeqCst);
+            }
+            if fm {
+                atomic_slider(ui, &self.state.fm_index, 10.0, "index");
+                atomic_slider_range(ui, &self.state.fm_ratio, 0.25..=8.0, "ratio B:A");
+            }
+        });
+        atomic_slider(ui, &self.state.voice_spread, 1.0, "voice stereo spread");
+        ui.horizontal(|ui| {
+            let engines = VoiceEngine::ALL.map(VoiceEngine::label);
+            atomic_combo(ui, &self.state.voice_engine, "voice engine", &engines);
+            if VoiceEngine::from_u8(self.state.voice_engine.load(Ordering::SeqCst))
+                == VoiceEngine::String
+            {
+                atomic_slider(ui, &self.state.string_damping, 1.0, "string damping");
+            }
+        });
+        ui.horizontal(|ui| {
+            atomic_slider(ui, &self.state.glide_time, 2.0, "glide (s/octave)");
+            let curves = GlideCurve::ALL.map(GlideCurve::label);
+            atomic_combo(ui, &self.state.glide_curve, "curve", &curves);
+            let modes = GlideMode::ALL.map(GlideMode::label);
+            atomic_combo(ui, &self.state.glide_mode, "glide", &modes);
+        });
+
+        if sound_edited {
+            // the sound changed, so its loudness did too
+            store_f32(&self.state.preset_trim, self.state.current_preset("").trim);
+        }
+
+        ui.separator();
+
+        ui.label("Wavetable (2048 samples/frame)");
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.wt_path);
+            if ui.button("Load WAV").clicked() {
+                match sample::load_wav_mono(self.wt_path.trim()).and_then(Wavetable::from_samples) {
+                    Ok(wt) => {
+                        self.wt_status = format!("{} frames loaded", wt.frames());
+                        *self.state.wavetable.lock().unwrap() = Some(Arc::new(wt));
+                        self.state.wt_enabled.store(true, Ordering::SeqCst);
+                    }
+                    Err(e) => self.wt_status = format!("load failed: {}", e),
+                }
+            }
+            let mut wt_on = self.state.wt_enabled.load(Ordering::SeqCst);
+            if ui.checkbox(&mut wt_on, "Use wavetable").changed() {
+                self.state.wt_enabled.store(wt_on, Ordering::SeqCst);
+            }
+        });
+        if !self.wt_status.is_empty() {
+            ui.small(&self.wt_status);
+        }
+        atomic_slider(ui, &self.state.wt_position, 1.0, "position");
+
+        ui.separator();
+
+        ui.label("Noise (band-passed, per voice)");
+        ui.horizontal(|ui| {
+            atomic_slider(ui, &self.state.noise_level, 1.0, "level");
+            ui.label(db_label(load_f32(&self.state.noise_level)));
+        });
+        cutoff_slider(ui, &self.state.noise_freq, "band center (Hz)");
+        atomic_slider(ui, &self.state.noise_resonance, 0.98, "resonance");
+
+        ui.separator();
+
+        ui.label("Granular");
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.grain_path);
+            if ui.button("Load WAV").clicked() {
+                match sample::load_wav_mono(self.grain_path.trim()) {
+                    Ok(buf) => {
+                        self.grain_status = format!("{} samples loaded", buf.len());
+                        *self.state.grain_source.lock().unwrap() = Some(Arc::new(buf));
+                    }
+                    Err(e) => self.grain_status = format!("load failed: {}", e),
+                }
+            }
+        });
+        if !self.grain_status.is_empty() {
+            ui.small(&self.grain_status);
+        }
+        for (atomic, label, max) in [
+            (&self.state.grain_size, "grain size (s)", 0.5),
+            (&self.state.grain_density, "density (grains/s)", 100.0),
+            (&self.state.grain_position, "position", 1.0),
+            (&self.state.grain_spray, "position spray", 1.0),
+            (&self.state.grain_pitch_rand, "pitch random (st)", 12.0),
+        ] {
+            atomic_slider(ui, atomic, max, label);
+        }
+        atomic_slider_range(ui, &self.state.grain_pitch, -24.0..=24.0, "pitch (st)");
+        ui.horizontal(|ui| {
+            atomic_slider(ui, &self.state.grain_level, 1.0, "level");
+            ui.label(db_label(load_f32(&self.state.grain_level)));
+        });
+        ui.horizontal(|ui| {
+            level_meter(ui, "grains", load_f32(&self.state.peak_grains));
+            mute_solo(
+                ui,
+                &self.state.source_mute[SRC_GRAINS],
+                &self.state.source_solo[SRC_GRAINS],
+            );
+        });
+    }
+
+    /// Filter mode, cutoff and what modulates it
+    fn filter_ui(&mut self, ui: &mut egui::Ui) {
+        effect_header(ui, &self.state, Effect::Filter);
+        let modes = FilterMode::ALL.map(FilterMode::label);
+        atomic_combo(ui, &self.state.filter_mode, "mode", &modes);
+        cutoff_slider(ui, &self.state.filter_cutoff, "cutoff (Hz)");
+        atomic_slider(ui, &self.state.filter_resonance, 0.98, "resonance");
+        atomic_slider_range(ui, &self.state.filter_drive, 1.0..=10.0, "drive");
+        atomic_slider(ui, &self.state.filter_key_track, 1.0, "key track");
+        let mut center = load_f32(&self.state.filter_center_note);
+        if ui
+            .add(
+                egui::Slider::new(&mut center, 0.0..=127.0)
+                    .step_by(1.0)
+                    .text("key track center (MIDI note)"),
+            )
+            .changed()
+        {
+            store_f32(&self.state.filter_center_note, center);
+        }
+        atomic_slider(
+            ui,
+            &self.state.pressure_to_cutoff,
+            1.0,
+            "poly pressure -> cutoff",
+        )
+        .on_hover_text("pressing harder on a held key opens that note's filter alone");
+        let routings = FilterRouting::ALL.map(FilterRouting::label);
+        atomic_combo(ui, &self.state.filter_routing, "routing", &routings);
+        if FilterRouting::from_u8(self.state.filter_routing.load(Ordering::SeqCst))
+            != FilterRouting::Single
+        {
+            ui.label("Filter 2");
+            // own id scope: the combo's label matches filter 1's
+            ui.push_id("filter2", |ui| {
+                atomic_combo(ui, &self.state.filter2_mode, "mode", &modes);
+            });
+            cutoff_slider(ui, &self.state.filter2_cutoff, "cutoff (Hz)");
+            atomic_slider(ui, &self.state.filter2_resonance, 0.98, "resonance");
+        }
+    }
+
+    /// Amp and mod envelopes
+    fn envelope_ui(&mut self, ui: &mut egui::Ui) {
+        // envelope: six stages, written straight to the atomics on change
+        ui.label("Envelope (DAHDSR)");
+        for (atomic, label, max) in [
+            (&self.state.env_delay, "delay (s)", 5.0),
+            (&self.state.env_attack, "attack (s)", 5.0),
+            (&self.state.env_hold, "hold (s)", 5.0),
+            (&self.state.env_decay, "decay (s)", 5.0),
+            (&self.state.env_sustain, "sustain", 1.0),
+            (&self.state.env_release, "release (s)", 10.0),
+        ] {
+            atomic_slider(ui, atomic, max, label);
+        }
+        atomic_slider(
+            ui,
+            &self.state.vel_to_release,
+            1.0,
+            "release velocity -> release time",
+        );
+        let mut declick = self.state.declick.load(Ordering::SeqCst);
+        if ui
+            .checkbox(&mut declick, "Anti-click fade (2 ms)")
+            .on_hover_text("off lets zero attack and release click")
+            .changed()
+        {
+            self.state.declick.store(declick, Ordering::SeqCst);
+        }
+
+        ui.separator();
+
+        ui.label("Mod envelope (ADSR)");
+        for (atomic, label, max) in [
+            (&self.state.mod_attack, "attack (s)", 5.0),
+            (&self.state.mod_decay, "decay (s)", 5.0),
+            (&self.state.mod_sustain, "sustain", 1.0),
+            (&self.state.mod_release, "release (s)", 10.0),
+        ] {
+            atomic_slider(ui, atomic, max, label);
+        }
+        ui.horizontal(|ui| {
+            let targets = ModEnvTarget::ALL.map(ModEnvTarget::label);
+            ui.push_id("mod_env", |ui| {
+                atomic_combo(ui, &self.state.mod_env_target, "target", &targets);
+            });
+            atomic_slider_range(ui, &self.state.mod_env_amount, -1.0..=1.0, "amount");
+        });
+    }
+
+    /// Modulation: tremolo, trance gate, sample & hold, XY pad and automation
+    fn modulation_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Tremolo");
+        ui.horizontal(|ui| {
+            let mut sync = self.state.tremolo_sync.load(Ordering::SeqCst);
+            if ui.checkbox(&mut sync, "Tempo sync").changed() {
+                self.state.tremolo_sync.store(sync, Ordering::SeqCst);
+            }
+            if sync {
+                let names = DIVISIONS.map(|(name, _)| name);
+                atomic_combo(ui, &self.state.tremolo_division, "rate", &names);
+            } else {
+                atomic_slider(ui, &self.state.tremolo_rate, 20.0, "rate (Hz)");
+            }
+        });
+        atomic_slider(ui, &self.state.tremolo_depth, 1.0, "depth");
+        let shapes = LfoShape::ALL.map(LfoShape::label);
+        atomic_combo(ui, &self.state.tremolo_shape, "shape", &shapes);
+
+        ui.separator();
+
+        ui.label("Trance gate (16ths)");
+        self.trance_gate_ui(ui);
+
+        ui.separator();
+
+        ui.label("Sample & hold");
+        ui.horizontal(|ui| {
+            let targets = ShTarget::ALL.map(ShTarget::label);
+            atomic_combo(ui, &self.state.sh_target, "target", &targets);
+            let names = DIVISIONS.map(|(name, _)| name);
+            ui.push_id("sh", |ui| {
+                atomic_combo(ui, &self.state.sh_division, "rate", &names);
+            });
+        });
+        atomic_slider(ui, &self.state.sh_depth, 48.0, "depth (st)");
+        atomic_slider(ui, &self.state.sh_smoothing, 1.0, "smoothing");
+
+        ui.separator();
+
+        ui.label("XY pad");
+        self.xy_pad(ui);
+
+        ui.separator();
+
+        ui.label("Automation (assignable parameters)");
+        self.automation_ui(ui);
+    }
+
+    /// Effects chain
+    fn effects_ui(&mut self, ui: &mut egui::Ui) {
+        effect_header(ui, &self.state, Effect::Distortion);
+        ui.horizontal(|ui| {
+            let mut multiband = self.state.dist_multiband.load(Ordering::SeqCst);
+            if ui.checkbox(&mut multiband, "Multiband").changed() {
+                self.state.dist_multiband.store(multiband, Ordering::SeqCst);
+            }
+            if !multiband {
+                atomic_slider(ui, &self.state.dist_drive, 36.0, "drive (dB)");
+            }
+            let factors = Oversample::ALL.map(Oversample::label);
+            atomic_combo(ui, &self.state.dist_oversample, "oversampling", &factors);
+        });
+        if self.state.dist_multiband.load(Ordering::SeqCst) {
+            ui.horizontal(|ui| {
+                atomic_slider_range(
+                    ui,
+                    &self.state.dist_crossover,
+                    100.0..=10_000.0,
+                    "crossover (Hz)",
+                );
+                atomic_slider(ui, &self.state.dist_low_drive, 36.0, "low drive (dB)");
+                atomic_slider(ui, &self.state.dist_high_drive, 36.0, "high drive (dB)");
+            });
+        }
+
+        ui.separator();
+
+        effect_header(ui, &self.state, Effect::Delay);
+        ui.horizontal(|ui| {
+            let mut sync = self.state.delay_sync.load(Ordering::SeqCst);
+            if ui.checkbox(&mut sync, "Tempo sync").changed() {
+                self.state.delay_sync.store(sync, Ordering::SeqCst);
+            }
+            if sync {
+                let names = DIVISIONS.map(|(name, _)| name);
+                for (division, label) in self.state.delay_division.iter().zip(["L", "R"]) {
+                    atomic_combo(ui, division, label, &names);
+                }
+            } else {
+                atomic_slider(ui, &self.state.delay_time_l, MAX_DELAY_SECS, "L (s)");
+                atomic_slider(ui, &self.state.delay_time_r, MAX_DELAY_SECS, "R (s)");
+            }
+        });
+        atomic_slider(ui, &self.state.delay_feedback, 0.95, "feedback");
+        ui.horizontal(|ui| {
+            atomic_slider(ui, &self.state.delay_mix, 1.0, "mix");
+            ui.label(db_label(load_f32(&self.state.delay_mix)));
+        });
+        let mut ping_pong = self.state.delay_ping_pong.load(Ordering::SeqCst);
+        if ui.checkbox(&mut ping_pong, "Ping-pong").changed() {
+            self.state
+                .delay_ping_pong
+                .store(ping_pong, Ordering::SeqCst);
+        }
+
+        ui.separator();
+
+        effect_header(ui, &self.state, Effect::Reverb);
+        for (atomic, label) in [
+            (&self.state.reverb_size, "size"),
+            (&self.state.reverb_damping, "damping"),
+            (&self.state.reverb_send, "send"),
+        ] {
+            atomic_slider(ui, atomic, 1.0, label);
+        }
+        ui.horizontal(|ui| {
+            let mut freeze = self.state.reverb_freeze.load(Ordering::SeqCst);
+            if ui
+                .toggle_value(&mut freeze, "Freeze")
+                .on_hover_text("hold the current tail indefinitely")
+                .changed()
+            {
+                self.state.reverb_freeze.store(freeze, Ordering::SeqCst);
+            }
+            let qualities = ReverbQuality::ALL.map(ReverbQuality::label);
+            atomic_combo(ui, &self.state.reverb_quality, "quality", &qualities);
+        });
+        atomic_slider(ui, &self.state.stereoize, 1.0, "stereoize").on_hover_text(format!(
+            "widen with copies {} cents up (left) and down (right)",
+            stereoize::SHIFT_CENTS
+        ));
+
+        ui.separator();
+
+        effect_header(ui, &self.state, Effect::Eq);
+        for (gain, freq, band) in [
+            (
+                &self.state.eq_low_gain,
+                &self.state.eq_low_freq,
+                "low shelf",
+            ),
+            (&self.state.eq_mid_gain, &self.state.eq_mid_freq, "mid peak"),
+            (
+                &self.state.eq_high_gain,
+                &self.state.eq_high_freq,
+                "high shelf",
+            ),
+        ] {
+            ui.horizontal(|ui| {
+                atomic_slider_range(ui, gain, -15.0..=15.0, &format!("{} (dB)", band));
+                cutoff_slider(ui, freq, "Hz");
+            });
+        }
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            let mut on = self.state.comp_enabled.load(Ordering::SeqCst);
+            if ui
+                .checkbox(&mut on, "Compressor")
+                .on_hover_text(
+                    "RMS detection rides over short transients; a soft knee eases the \
+                     ratio in over 6 dB around the threshold",
+                )
+                .changed()
+            {
+                self.state.comp_enabled.store(on, Ordering::SeqCst);
+            }
+            if on {
+                ui.label(format!(
+                    "reduction {:.1} dB",
+                    load_f32(&self.state.comp_reduction)
+                ));
+            }
+        });
+        atomic_slider_range(
+            ui,
+            &self.state.comp_threshold,
+            -60.0..=0.0,
+            "threshold (dB)",
+        );
+        atomic_slider_range(ui, &self.state.comp_ratio, 1.0..=20.0, "ratio");
+        atomic_slider_range(ui, &self.state.comp_attack, 0.0001..=0.1, "attack (s)");
+        atomic_slider_range(ui, &self.state.comp_release, 0.01..=1.0, "release (s)");
+        atomic_slider_range(ui, &self.state.comp_makeup, 0.0..=24.0, "makeup (dB)");
+        ui.horizontal(|ui| {
+            let detectors = Detector::ALL.map(Detector::label);
+            atomic_combo(ui, &self.state.comp_detector, "detector", &detectors);
+            let knees = Knee::ALL.map(Knee::label);
+            atomic_combo(ui, &self.state.comp_knee, "knee", &knees);
+        });
+        ui.horizontal(|ui| {
+            let triggers = DuckTrigger::ALL.map(DuckTrigger::label);
+            atomic_combo(ui, &self.state.duck_trigger, "sidechain duck", &triggers);
+            let shapes = DuckShape::ALL.map(DuckShape::label);
+            atomic_combo(ui, &self.state.duck_shape, "recovery", &shapes);
+        });
+        atomic_slider_range(ui, &self.state.duck_amount, 0.0..=36.0, "duck amount (dB)")
+            .on_hover_text("pumps the mix in time with the tempo while the compressor is on");
+        atomic_slider_range(ui, &self.state.duck_length, 0.05..=1.0, "duck length")
+            .on_hover_text("recovery time, as a fraction of the time between triggers");
+    }
+
+    /// Output level, limiting, device, and the audio input
+    fn master_ui(&mut self, ui: &mut egui::Ui) {
+        let mut mg = load_f32(&self.state.master_gain);
+        ui.horizontal(|ui| {
+            if self.db_sliders {
+                // -60..+6 dB; the bottom of the range is silence
+                let mut db = linear_to_db(mg).max(MIN_DB);
+                if ui
+                    .add(egui::Slider::new(&mut db, MIN_DB..=6.0).text("master gain (dB)"))
+                    .changed()
+                {
+                    store_f32(&self.state.master_gain, db_to_linear(db));
+                }
+            } else if ui
+                .add(egui::Slider::new(&mut mg, 0.0..=2.0).text("master gain"))
+                .changed()
+            {
+                store_f32(&self.state.master_gain, mg);
+            }
+            ui.label(db_label(load_f32(&self.state.master_gain)));
+            ui.checkbox(&mut self.db_sliders, "dB");
+        });
+        ui.horizontal(|ui| {
+            let mut smoothing = self.state.smoothing_enabled.load(Ordering::SeqCst);
+            if ui
+                .checkbox(&mut smoothing, "Smooth parameters")
+                .on_hover_text("off: gain, mix and width changes land instantly")
+                .changed()
+            {
+                self.state
+                    .smoothing_enabled
+                    .store(smoothing, Ordering::SeqCst);
+            }
+            atomic_slider_range(ui, &self.state.smoothing_time, 0.001..=0.2, "time (s)");
+        });
+
+        self.output_meter(ui);
+        ui.horizontal(|ui| {
+            atomic_slider_range(
+                ui,
+                &self.state.max_output_db,
+                -30.0..=0.0,
+                "max output (dBFS)",
+            )
+            .on_hover_text("hard safety ceiling: the output never goes above this");
+            let clamping = self.state.output_clamping.load(Ordering::SeqCst);
+            let (rect, resp) = ui.allocate_exact_size(egui::vec2(14.0, 14.0), egui::Sense::hover());
+            let led = if clamping {
+                Color32::RED
+            } else {
+                Color32::from_gray(60)
+            };
+            ui.painter().circle_filled(rect.center(), 5.0, led);
+            resp.on_hover_text("lit while the ceiling is clamping the output");
+            let mut lookahead = self.state.limiter_lookahead.load(Ordering::SeqCst);
+            if ui
+                .checkbox(&mut lookahead, "Lookahead")
+                .on_hover_text(
+                    "a limiter turns peaks down just before they arrive, so the ceiling \
+                     never has to clip them",
+                )
+                .changed()
+            {
+                self.state
+                    .limiter_lookahead
+                    .store(lookahead, Ordering::SeqCst);
+            }
+            if lookahead {
+                ui.label(format!("+{:.0} ms latency", LOOKAHEAD_SECS * 1000.0));
+            }
+        });
+        self.correlation_meter(ui);
+        ui.horizontal(|ui| {
+            let chosen = self.state.output_device.lock().unwrap().clone();
+            let mut picked = chosen.clone();
+            egui::ComboBox::from_label("output device")
+                .selected_text(chosen.as_deref().unwrap_or("system default"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut picked, None, "system default");
+                    for name in &self.output_devices {
+                        let label = if is_virtual_device(name) {
+                            format!("{} (virtual)", name)
+                        } else {
+                            name.clone()
+                        };
+                        ui.selectable_value(&mut picked, Some(name.clone()), label);
+                    }
+                });
+            if picked != chosen {
+                *self.state.output_device.lock().unwrap() = picked;
+            }
+            if ui.button("Refresh").clicked() {
+                self.output_devices = output_device_names();
+            }
+        })
+        .response
+        .on_hover_text(
+            "pick a loopback device (BlackHole, VB-Cable, a PulseAudio monitor...) to route \
+             the synth into OBS or a DAW",
+        );
+        ui.label(format!(
+            "playing on: {}",
+            self.state.output_status.lock().unwrap()
+        ));
+        let channels = self.state.out_channels.load(Ordering::SeqCst);
+        ui.horizontal(|ui| {
+            ui.label(format!("output: {} ch", channels));
+            // only matters past stereo, but keep it visible so the setting is discoverable
+            let mut routing =
+                OutputRouting::from_u8(self.state.output_routing.load(Ordering::SeqCst));
+            egui::ComboBox::from_label("extra channels")
+                .selected_text(routing.label())
+                .show_ui(ui, |ui| {
+                    for r in OutputRouting::ALL {
+                        ui.selectable_value(&mut routing, r, r.label());
+                    }
+                });
+            self.state
+                .output_routing
+                .store(routing as u8, Ordering::SeqCst);
+            let qualities = Quality::ALL.map(Quality::label);
+            atomic_combo(ui, &self.state.quality, "mode", &qualities);
+            let mut fast_math = self.state.fast_math.load(Ordering::SeqCst);
+            if ui
+                .checkbox(&mut fast_math, "Fast sine")
+                .on_hover_text(
+                    "polynomial sine (within 0.0011) in the oscillators and LFOs; \
+                     always on in low quality",
+                )
+                .changed()
+            {
+                self.state.fast_math.store(fast_math, Ordering::SeqCst);
+            }
+        });
+
+        ui.separator();
+
+        ui.label(format!(
+            "Audio input: {}",
+            self.state.input_status.lock().unwrap()
+        ));
+        ui.horizontal(|ui| {
+            let mut monitor = self.state.input_monitor.load(Ordering::SeqCst);
+            if ui
+                .checkbox(&mut monitor, "Monitor through the effects")
+                .changed()
+            {
+                self.state.input_monitor.store(monitor, Ordering::SeqCst);
+            }
+            atomic_slider(ui, &self.state.input_gain, 4.0, "input trim");
+            ui.label(db_label(load_f32(&self.state.input_gain)));
+        });
+        level_meter(ui, "input", load_f32(&self.state.peak_input));
+        ui.horizontal(|ui| match input_pitch(&self.state, &mut self.pitch_buf) {
+            Some(hz) => {
+                let (name, cents) = freq_to_note_name(hz);
+                ui.label(format!(
+                    "input pitch: {:.1} Hz ({} {:+.0} ct)",
+                    hz, name, cents
+                ));
+                if ui
+                    .button("Tune to input")
+                    .on_hover_text("set the demo tone's frequency to the detected pitch")
+                    .clicked()
+                {
+                    store_f32(&self.state.freq_hz, hz);
+                }
+            }
+            None => {
+                ui.label("input pitch: no pitch detected");
+            }
+        });
+    }
+
+    /// Tempo, step sequencer, MIDI file player, looper and recording
+    fn sequencing_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(format!("MIDI: {}", self.midi_status));
+            let mut rt = self.state.rt_priority.load(Ordering::SeqCst);
+            if ui.checkbox(&mut rt, "Real-time audio priority").changed() {
+                self.state.rt_priority.store(rt, Ordering::SeqCst);
+            }
+            ui.small(RtStatus::from_u8(self.state.rt_status.load(Ordering::SeqCst)).label());
+            let mut ext = self.state.external_sync.load(Ordering::SeqCst);
+            if ui.checkbox(&mut ext, "External MIDI clock").changed() {
+                self.state.external_sync.store(ext, Ordering::SeqCst);
+            }
+        });
+        if self.state.external_sync.load(Ordering::SeqCst) {
+            let bpm = load_f32(&self.state.external_bpm);
+            let running = self.state.clock_running.load(Ordering::SeqCst);
+            ui.label(if bpm > 0.0 {
+                format!(
+                    "tempo: {:.1} BPM (external, {})",
+                    bpm,
+                    if running { "running" } else { "stopped" }
+                )
+            } else {
+                "tempo: waiting for MIDI clock...".to_string()
+            });
+        } else {
+            let mut bpm = load_f32(&self.state.tempo_bpm);
+            if ui
+                .add(egui::Slider::new(&mut bpm, 40.0..=240.0).text("tempo (BPM)"))
+                .changed()
+            {
+                store_f32(&self.state.tempo_bpm, bpm);
+            }
+        }
+
+        ui.separator();
+
+        ui.label("Step sequencer (16ths)");
+        self.sequencer_ui(ui);
+
+        ui.separator();
+
+        ui.label("MIDI file player");
+        self.song_ui(ui);
+
+        ui.separator();
+
+        ui.label("Looper");
+        self.looper_ui(ui);
+
+        ui.separator();
+
+        ui.label("Recording");
+        self.recorder_ui(ui);
+    }
+
+    /// Oscilloscope and spectrum
+    fn analysis_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Oscilloscope");
+        self.state.scope.snapshot(&mut self.scope_buf);
+        let level = load_f32(&self.state.scope_trigger_level);
+        let window = scope::window_len(
+            load_f32(&self.state.scope_window_ms),
+            self.state.sample_rate.load(Ordering::SeqCst) as f32,
+        );
+        // without a crossing, free-run on the newest samples
+        let trigger = scope::find_trigger(&self.scope_buf, level, window);
+        let start = trigger.unwrap_or(self.scope_buf.len() - window);
+        let (rect, _) = ui.allocate_exact_size(
+            egui::vec2(ui.available_width(), 120.0),
+            egui::Sense::hover(),
+        );
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, Color32::from_gray(20));
+        let y_of = |v: f32| rect.center().y - v.clamp(-1.0, 1.0) * rect.height() * 0.5;
+        painter.hline(
+            rect.x_range(),
+            y_of(level),
+            egui::Stroke::new(1.0, Color32::from_gray(70)),
+        );
+        // long windows are thinned to about 2048 points; there are fewer pixels than that
+        let points: Vec<egui::Pos2> = self.scope_buf[start..start + window]
+            .iter()
+            .enumerate()
+            .step_by(window.div_ceil(2048))
+            .map(|(i, &v)| {
+                egui::pos2(
+                    rect.left() + rect.width() * i as f32 / (window - 1) as f32,
+                    y_of(v),
+                )
+            })
+            .collect();
+        painter.add(egui::Shape::line(
+            points,
+            egui::Stroke::new(1.5, Color32::LIGHT_GREEN),
+        ));
+        ui.horizontal(|ui| {
+            atomic_slider_range(
+                ui,
+                &self.state.scope_trigger_level,
+                -1.0..=1.0,
+                "trigger level",
+            );
+            ui.label(if trigger.is_some() {
+                "triggered"
+            } else {
+                "free-run"
+            });
+        });
+        atomic_slider_range(
+            ui,
+            &self.state.scope_window_ms,
+            MIN_WINDOW_MS..=MAX_WINDOW_MS,
+            "time base (ms)",
+        )
+        .on_hover_text("short windows for high notes, long ones for bass");
+
+        ui.separator();
+
+        ui.label("Spectrum");
+        self.spectrum_ui(ui);
+    }
+}
+
+impl eframe::App for SynthApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.computer_keyboard(ctx);
+        self.preset_drop(ctx);
+
+        // Disco color cycling if enabled, over the chosen theme so switching it off restores
+        // the theme as it was
+        let disco_on = self.state.disco.load(Ordering::SeqCst);
+        let accent = disco_on.then(|| {
+            // increment ad tick
+            self.state.ad_tick.fetch_add(1, Ordering::SeqCst);
+
+            let t = (Instant::now().elapsed().as_millis() as f32 / 200.0).sin();
+            Color32::from_rgb(
+                ((t * 0.5 + 0.5) * 255.0) as u8,
+                ((-t * 0.8 + 0.5) * 255.0) as u8,
+                ((t * 0.2 + 0.4) * 255.0) as u8,
+            )
+        });
+        ctx.set_visuals(self.settings.theme.visuals(accent));
+
+        // pinned under the sections so scrolling them never hides it
+        egui::TopBottomPanel::bottom("footer").show(ctx, |ui| {
+            ui.small("Demo: the gate holds a continuous tone; MIDI notes play polyphonically.");
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Rust Synth Prototype — built-in presets (egui) ");
+            self.watchdog_ui(ui);
+            ui.horizontal(|ui| {
+                if ui.button("Load: Ryan & Josh Allen (romantic)").clicked() {
+                    self.state.apply_preset(&Preset::ryan_josh());
+                }
+                if ui.button("Load: Laura Les (fast hyperpopish)").clicked() {
+                    self.state.apply_preset(&Preset::laura_les());
+                }
+                if ui.button("Load: Filter sweep (demo)").clicked() {
+                    self.state.apply_preset(&Preset::filter_sweep());
+                }
+                let mut program_change = self.state.program_change.load(Ordering::SeqCst);
+                if ui
+                    .checkbox(&mut program_change, "MIDI program change")
+                    .on_hover_text("programs 1 to 3 recall these presets")
+                    .changed()
+                {
+                    self.state
+                        .program_change
+                        .store(program_change, Ordering::SeqCst);
+                }
+                let disco_val = self.state.disco.load(Ordering::SeqCst);
+                let mut disco_bool = disco_val;
+                if ui.checkbox(&mut disco_bool, "Disco mode").changed() {
+                    self.state.disco.store(disco_bool, Ordering::SeqCst);
+                }
+                let theme = self.settings.theme;
+                egui::ComboBox::from_label("theme")
+                    .selected_text(self.settings.theme.label())
+                    .show_ui(ui, |ui| {
+                        for t in Theme::ALL {
+                            ui.selectable_value(&mut self.settings.theme, t, t.label());
+                        }
+                    });
+                if self.settings.theme != theme {
+                    self.save_settings();
+                }
+                let mut gate = self.state.gate.load(Ordering::SeqCst);
+                if ui.checkbox(&mut gate, "Gate (hold note)").changed() {
+                    self.state.gate.store(gate, Ordering::SeqCst);
+                }
+                ui.label(format!(
+                    "voices: {}",
+                    self.state.active_voices.load(Ordering::SeqCst)
+                ));
+                let chords = ChordType::ALL.map(ChordType::label);
+                atomic_combo(ui, &self.state.chord_mode, "chord", &chords);
+                let mut mono = self.state.mono.load(Ordering::SeqCst);
+                if ui.checkbox(&mut mono, "Mono").changed() {
+                    self.state.mono.store(mono, Ordering::SeqCst);
+                }
+                if mono {
+                    let priorities = NotePriority::ALL.map(NotePriority::label);
+                    atomic_combo(ui, &self.state.note_priority, "priority", &priorities);
+                } else {
+                    let steals = VoiceSteal::ALL.map(VoiceSteal::label);
+                    atomic_combo(ui, &self.state.voice_steal, "steal", &steals);
+                }
+                let curves = VelocityCurve::ALL.map(VelocityCurve::label);
+                atomic_combo(ui, &self.state.velocity_curve, "velocity", &curves);
+            });
+            ui.horizontal(|ui| {
+                atomic_slider(ui, &self.state.vel_min, 1.0, "velocity floor").on_hover_text(
+                    "the softest velocity your controller sends: it and anything under it play \
+                     silent",
+                );
+                atomic_slider(ui, &self.state.vel_max, 1.0, "velocity ceiling")
+                    .on_hover_text("the hardest velocity your controller sends: it plays full");
+            });
+            ui.horizontal(|ui| {
+                let mut tuner = self.state.tuner.load(Ordering::SeqCst);
+                if ui.toggle_value(&mut tuner, "Tuner").changed() {
+                    self.state.tuner.store(tuner, Ordering::SeqCst);
+                }
+                let mut note = self.state.tuner_note.load(Ordering::SeqCst);
+                let hz = note_to_freq(note as f32);
+                if ui
+                    .add(
+                        egui::Slider::new(&mut note, 21..=108)
+                            .custom_formatter(|n, _| note_name(n as u8))
+                            .text(format!("{:.2} Hz", hz)),
+                    )
+                    .changed()
+                {
+                    self.state.tuner_note.store(note, Ordering::SeqCst);
+                }
+            });
+            ui.horizontal(|ui| {
+                let scales = Scale::ALL.map(Scale::label);
+                atomic_combo(ui, &self.state.scale, "scale", &scales);
+                atomic_combo(ui, &self.state.scale_root, "root", &NOTE_NAMES);
+                let mut transpose = self.state.transpose.load(Ordering::SeqCst);
+                if ui
+                    .add(egui::Slider::new(&mut transpose, -24..=24).text("transpose (st)"))
+                    .changed()
+                {
+                    self.state.transpose.store(transpose, Ordering::SeqCst);
+                }
+            });
+            self.keyboard(ui);
+
+            ui.separator();
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for section in Section::ALL {
+                    self.section(ui, section);
+                }
+
+                ui.separator();
+                ui.label("Advertisement area (disco mode spams this when enabled):");
+                if self.state.disco.load(Ordering::SeqCst) {
+                    for i in 0..6 {
+                        ui.colored_label(
+                            Color32::LIGHT_YELLOW,
+                            format!("🔥 SUPER SYNTH SALE! BUY NOW — LIMITED TIME! ({})", i),
+                        );
+                    }
+                } else {
+                    ui.label("(disco mode disabled)");
+                }
+            });
+        });
+
+        // everything edited this frame goes to the audio thread together
+        self.state.publish();
+
+        ctx.request_repaint_after(Duration::from_millis(16));
+    }
+
+    /// Fade the output out before the stream goes away with the process, so closing the
+    /// window doesn't pop
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.state.exiting.store(true, Ordering::SeqCst);
+        self.state.publish();
+        let started = Instant::now();
+        while !self.state.exit_faded.load(Ordering::SeqCst) && started.elapsed() < EXIT_WAIT {
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
+
+// ---------- Audio: CPAL stream builders ----------
+
+/// Parts of device names that give away a loopback/virtual device (BlackHole, VB-Cable,
+/// Loopback, Soundflower, PulseAudio/PipeWire monitors, ...), matched case-insensitively.
+/// A bare "monitor" would catch HDMI and DisplayPort screens, so only the PulseAudio/PipeWire
+/// "Monitor of ..." form counts.
+const VIRTUAL_DEVICE_HINTS: [&str; 6] = [
+    "blackhole",
+    "vb-audio",
+    "loopback",
+    "soundflower",
+    "monitor of",
+    "virtual",
+];
+
+/// Whether `name` looks like a virtual device that passes audio on to other apps
+fn is_virtual_device(name: &str) -> bool {
+    let name = name.to_lowercase();
+    VIRTUAL_DEVICE_HINTS.iter().any(|hint| name.contains(hint))
+}
+
+/// Names of the output devices the host offers
+fn output_device_names() -> Vec<String> {
+    cpal::default_host()
+        .output_devices()
+        .map(|devices| devices.filter_map(|d| d.name().ok()).collect())
+        .unwrap_or_default()
+}
+
+fn start_audio_thread(state: Arc<SharedState>) -> Result<()> {
+    let host = cpal::default_host();
+    let mut current = state.output_device.lock().unwrap().clone();
+    let (mut _stream, sample_rate) = open_output(&host, current.as_deref(), &state)?;
+
+    // external input is optional; the synth runs the same without it
+    let _input = match audio_input::start(&host, sample_rate, state.input_queue.clone()) {
+        Ok((input, name)) => {
+            *state.input_status.lock().unwrap() = name;
+            Some(input)
+        }
+        Err(e) => {
+            *state.input_status.lock().unwrap() = format!("unavailable: {}", e);
+            None
+        }
+    };
+
+    // keep thread alive while audio plays, moving the stream when another device is picked
+    loop {
+        thread::sleep(Duration::from_millis(200));
+        let wanted = state.output_device.lock().unwrap().clone();
+        if wanted == current {
+            continue;
+        }
+        match open_output(&host, wanted.as_deref(), &state) {
+            // the old stream stops as it's dropped
+            Ok((new, _)) => _stream = new,
+            Err(e) => {
+                let mut status = state.output_status.lock().unwrap();
+                *status = format!(
+                    "couldn't open {}: {}; still on {}",
+                    wanted.as_deref().unwrap_or("the default device"),
+                    e,
+                    status
+                );
+            }
+        }
+        current = wanted;
+    }
+}
+
+/// Start playing on the output device called `name` (or the default one). Returns the
+/// running stream and its sample rate.
+fn open_output(
+    host: &cpal::Host,
+    name: Option<&str>,
+    state: &Arc<SharedState>,
+) -> Result<(cpal::Stream, u32)> {
+    let device = match name {
+        None => host.default_output_device(),
+        Some(name) => host
+            .output_devices()?
+            .find(|d| d.name().is_ok_and(|n| n == name)),
+    }
+    .ok_or_else(|| anyhow::anyhow!("No output device"))?;
+    let cfg = device.default_output_config()?;
+    let sample_rate = cfg.sample_rate().0 as f32;
+    let config: StreamConfig = cfg.clone().into();
+
+    // spawn appropriate stream based on format
+    let stream = match cfg.sample_format() {
+        SampleFormat::F32 => build_stream_f32(&device, &config, sample_rate, state.clone())?,
+        SampleFormat::I16 => build_stream_i16(&device, &config, sample_rate, state.clone())?,
+        SampleFormat::U16 => build_stream_u16(&device, &config, sample_rate, state.clone())?,
+        _ => return Err(anyhow::anyhow!("Unsupported sample format")),
+    };
+
+    stream.play()?;
+    // only now that it plays: if opening failed, the old stream carries on and the state must
+    // keep describing it
+    state
+        .out_channels
+        .store(config.channels as u32, Ordering::SeqCst);
+    state
+        .sample_rate
+        .store(cfg.sample_rate().0, Ordering::SeqCst);
+    let name = device.name().unwrap_or_else(|_| "output".to_string());
+    *state.output_status.lock().unwrap() = if is_virtual_device(&name) {
+        format!("{} (virtual: other apps can record it)", name)
+    } else {
+        name
+    };
+    Ok((stream, cfg.sample_rate().0))
+}
+
+/// Basic oscillator waveform: phase 0..1 -> sine
+fn sine(phase: f32) -> f32 {
+    (phase * TAU).sin()
+}
+
+/// Two copies of `osc` (phase 0..1 -> sample) at their own phases (A, B), B offset further by
+/// the detune plus a fixed `phase_offset` (fraction of a cycle), crossfaded by `osc_mix`.
+/// Returns each oscillator's contribution separately (A, B) so they can be metered; the
+/// output is their sum.
+fn osc_sources(
+    osc: impl Fn(f32) -> f32,
+    phase: [f32; 2],
+    osc_mix: f32,
+    detune: f32,
+    phase_offset: f32,
+    gain: f32,
+    master: f32,
+) -> (f32, f32) {
+    // detune: interpret as cents-ish fraction scaled small
+    let detune_frac = detune * 0.001; // small demo scaling
+    let a = osc(phase[0]);
+    let b = osc((phase[1] + detune_frac + phase_offset).rem_euclid(1.0));
+    (
+        (1.0 - osc_mix) * a * gain * master,
+        osc_mix * b * gain * master,
+    )
+}
+
+/// Two-operator FM: oscillator B, `sine` at its own phase, modulates A's phase by up to
+/// `index` radians. Only the carrier is heard, in A's slot (B returns 0).
+fn fm_sources(
+    osc: impl Fn(f32) -> f32,
+    sine: fn(f32) -> f32,
+    phase: [f32; 2],
+    index: f32,
+    gain: f32,
+    master: f32,
+) -> (f32, f32) {
+    let modulator = sine(phase[1]);
+    let a = osc((phase[0] + index * modulator / TAU).rem_euclid(1.0));
+    (a * gain * master, 0.0)
+}
+
+/// Swap in a newly published shared buffer without ever blocking the callback
+fn pick_up<T>(slot: &Mutex<Option<Arc<T>>>, current: &mut Option<Arc<T>>) {
+    if let Ok(shared) = slot.try_lock() {
+        if let Some(shared) = shared.as_ref() {
+            if !current.as_ref().is_some_and(|cur| Arc::ptr_eq(cur, shared)) {
+                *current = Some(shared.clone());
+            }
+        }
+    }
+}
+
+/// Warmth filter cutoff above the note: eight octaves (effectively open) just above zero
+/// warmth, down to one octave at full warmth
+const WARMTH_OPEN_SEMIS: f32 = 96.0;
+const WARMTH_WARMEST_SEMIS: f32 = 12.0;
+
+/// Modulation-envelope range at full amount
+const MOD_ENV_CUTOFF_SEMIS: f32 = 48.0;
+const MOD_ENV_PITCH_SEMIS: f32 = 12.0;
+
+/// Cutoff rise of a key held at full polyphonic aftertouch, at full amount
+const PRESSURE_CUTOFF_SEMIS: f32 = 36.0;
+
+/// Set a voice's two filters from its per-buffer cutoffs, scaled by `cutoff_scale`
+fn set_filters(
+    v: &mut Voice,
+    f1: &FilterParams,
+    f2: &FilterParams,
+    cutoff_scale: f32,
+    sample_rate: f32,
+) {
+    v.filter.set(
+        v.cutoffs.0 * cutoff_scale,
+        f1.resonance,
+        f1.mode,
+        sample_rate,
+    );
+    v.filter2.set(
+        v.cutoffs.1 * cutoff_scale,
+        f2.resonance,
+        f2.mode,
+        sample_rate,
+    );
+}
+
+/// Two-operator FM settings: B modulates A (see `fm_sources`)
+#[derive(Clone, Copy, Debug)]
+struct FmParams {
+    index: f32, // peak phase deviation, radians
+    ratio: f32, // B's frequency as a multiple of A's
+}
+
+/// How far an oscillator follows the keyboard: at `amount` 1 it plays the note, at 0 it holds
+/// `fixed_hz` whatever is played (a drone), and in between its pitch sits that fraction of the
+/// way from the fixed pitch to the note's
+#[derive(Clone, Copy, Debug)]
+struct KeyTrack {
+    amount: f32,
+    fixed_hz: f32,
+}
+
+impl KeyTrack {
+    /// Frequency for a note sounding at `hz`, before the oscillator's own tuning
+    fn hz(self, hz: f32) -> f32 {
+        if self.amount >= 1.0 {
+            hz
+        } else {
+            self.fixed_hz * (hz / self.fixed_hz).powf(self.amount)
+        }
+    }
+}
+
+/// Velocity of sequencer notes
+const SEQ_VELOCITY: f32 = 0.8;
+
+/// Shortest fade the amplitude envelope is allowed, on every gate transition (see `declick`)
+const DECLICK_SECS: f32 = 0.002;
+
+/// Output fade-in when a stream starts, so the first note can't pop
+const STARTUP_FADE_SECS: f32 = 0.02;
+/// Output fade-out when the app closes, before the stream is dropped
+const EXIT_FADE_SECS: f32 = 0.05;
+/// Longest the window waits for the exit fade (the audio thread may be stuck or gone)
+const EXIT_WAIT: Duration = Duration::from_millis(200);
+
+/// Tuner reference level (-18 dBFS), independent of every gain setting
+const TUNER_LEVEL: f32 = 0.125;
+
+/// Write one stereo frame to an interleaved device frame: mono devices get the sum, extra
+/// channels past front L/R follow `routing`
+fn write_frame<T: Copy>(
+    frame: &mut [T],
+    l: f32,
+    r: f32,
+    routing: OutputRouting,
+    convert: &impl Fn(f32) -> T,
+) {
+    match frame {
+        [mono] => *mono = convert(0.5 * (l + r)),
+        [fl, fr, rest @ ..] => {
+            *fl = convert(l);
+            *fr = convert(r);
+            let extra = match routing {
+                OutputRouting::FrontOnly => convert(0.0),
+                OutputRouting::MidToAll => convert(0.5 * (l + r)),
+            };
+            for sample in rest.iter_mut() {
+                *sample = extra;
+            }
+        }
+        [] => {}
+    }
+}
+
+/// Output below this (about -120 dBFS) counts as silence for the watchdog
+const SILENCE_LEVEL: f32 = 1e-6;
+/// Held notes producing silence for this long are reported
+const SILENCE_ALARM_SECS: f32 = 2.0;
+
+/// Per-stream synth/effect state, owned by the audio callback closure
+struct Engine {
+    sample_rate: f32,
+    voices: VoicePool,
+    // last seen UI gate, to turn its edges into note on/off for the demo tone
+    gate_was: bool,
+    distortion: Distortion,
+    delay: Delay,
+    reverb: Reverb,
+    stereoize: Stereoize,
+    limiter: Limiter,
+    tremolo: Tremolo,
+    trance_gate: TranceGate,
+    sequencer: Sequencer,
+    eq: Eq3,
+    compressor: Compressor,
+    looper: Looper,
+    sample_hold: SampleHold,
+    granular: Granular,
+    grain_src: Option<Arc<Vec<f32>>>,
+    // this buffer's external input, taken from the queue in one go
+    input_buf: Vec<f32>,
+    wavetable: Option<Arc<Wavetable>>,
+    // the built-in sawtooth, built once here rather than in the callback
+    saw: Wavetable,
+    // last parameter snapshot picked up from the UI
+    params: Params,
+    // how long notes have been held with silent output (watchdog)
+    silent_secs: f32,
+    // master gain ramping 0 -> 1 over `STARTUP_FADE_SECS` after the stream starts
+    startup_gain: f32,
+    // master gain ramping 1 -> 0 over `EXIT_FADE_SECS` once the app is closing
+    exit_gain: f32,
+    tuner_phase: f32,
+    // scheduling last requested for the callback thread
+    rt_applied: bool,
+    // buffers rendered, for the stress test's interval
+    stress_count: u32,
+    // `fx_on` as of the last buffer, to catch effects being switched off
+    fx_was: [bool; EFFECTS],
+    // whether the lookahead limiter ran last buffer, to catch it being switched back on
+    lookahead_was: bool,
+    // gain, master, osc mix, phase offset and stereoize, ramped per sample
+    smoothed: [SmoothedParam; 5],
+    // global seed the random sources were last started from
+    seed: u32,
+}
+
+impl Engine {
+    fn new(sample_rate: f32, state: &SharedState) -> Self {
+        let mut engine = Self {
+            sample_rate,
+            voices: VoicePool::new(),
+            gate_was: false,
+            distortion: Distortion::new(sample_rate),
+            delay: Delay::new(sample_rate),
+            reverb: Reverb::new(sample_rate),
+            stereoize: Stereoize::new(sample_rate),
+            limiter: Limiter::new(sample_rate),
+            tremolo: Tremolo::new(sample_rate),
+            trance_gate: TranceGate::new(sample_rate),
+            sequencer: Sequencer::new(sample_rate),
+            eq: Eq3::new(sample_rate),
+            compressor: Compressor::new(sample_rate),
+            looper: Looper::new(sample_rate),
+            sample_hold: SampleHold::new(sample_rate),
+            granular: Granular::new(sample_rate),
+            grain_src: None,
+            input_buf: Vec::with_capacity(INPUT_QUEUE),
+            wavetable: None,
+            saw: Wavetable::saw(),
+            params: state.snapshot(),
+            silent_secs: 0.0,
+            startup_gain: 0.0,
+            exit_gain: 1.0,
+            tuner_phase: 0.0,
+            rt_applied: false,
+            stress_count: 0,
+            fx_was: [true; EFFECTS],
+            lookahead_was: false,
+            smoothed: {
+                let p = state.snapshot();
+                [p.gain, p.master, p.osc_mix, p.phase_offset, p.stereoize].map(SmoothedParam::new)
+            },
+            seed: 0,
+        };
+        engine.reseed(engine.params.seed);
+        engine
+    }
+
+    /// Restart every random source from the global `seed`
+    fn reseed(&mut self, seed: u32) {
+        self.seed = seed;
+        self.voices.reseed(seed);
+        self.sample_hold.reseed(seed);
+        self.granular.reseed(seed);
+    }
+
+    /// Debug builds only: on every `stress_interval`th buffer, apply the stress mode. Sleeps
+    /// here for an overrun; returns whether to put a NaN on the bus.
+    fn stress(&mut self, mode: StressMode, interval: u32, buffer_secs: f32) -> bool {
+        if !cfg!(debug_assertions) || mode == StressMode::Off {
+            return false;
+        }
+        self.stress_count = self.stress_count.wrapping_add(1);
+        if !self.stress_count.is_multiple_of(interval.max(1)) {
+            return false;
+        }
+        match mode {
+            StressMode::Off => false,
+            StressMode::Overrun => {
+                thread::sleep(Duration::from_secs_f32(2.0 * buffer_secs));
+                false
+            }
+            StressMode::Nan => true,
+        }
+    }
+
+    /// Bring an effect's internal state to rest, so it doesn't replay a stale tail when it
+    /// next runs
+    fn reset_effect(&mut self, effect: Effect) {
+        match effect {
+            Effect::Filter => {
+                for v in &mut self.voices.voices {
+                    v.filter = Svf::new();
+                    v.filter2 = Svf::new();
+                    v.filters_r = (Svf::new(), Svf::new());
+                    v.warmth = (Svf::new(), Svf::new());
+                }
+            }
+            Effect::Distortion => self.distortion = Distortion::new(self.sample_rate),
+            Effect::Delay => self.delay.clear(),
+            Effect::Reverb => self.reverb.clear(),
+            Effect::Eq => self.eq = Eq3::new(self.sample_rate),
+        }
+    }
+
+    /// Fill one interleaved output buffer. `convert` maps a [-1,1] sample to the device format.
+    fn render<T: Copy>(
+        &mut self,
+        state: &SharedState,
+        data: &mut [T],
+        channels: usize,
+        convert: impl Fn(f32) -> T,
+    ) {
+        let started = Instant::now();
+        state
+            .sample_clock
+            .fetch_add((data.len() / channels.max(1)) as u64, Ordering::SeqCst);
+
+        // one consistent snapshot per buffer: the latest published, else the one before
+        if let Some(p) = state.published.take() {
+            self.params = p;
+        }
+        let Params {
+            osc_mix,
+            detune,
+            osc_ratio,
+            key_track,
+            gain,
+            trim,
+            master,
+            max_output,
+            lookahead,
+            smoothing,
+            freq,
+            gate,
+            analog_drift: drift_cents,
+            humanize,
+            seed,
+            reset_phase,
+            precision,
+            phase_offset,
+            phase_width,
+            fm,
+            voice_spread,
+            voice_engine,
+            string_damping,
+            noise: noise_params,
+            glide: glide_params,
+            glide_mode,
+            voice_steal,
+            tuner,
+            vel_to_release,
+            pressure_to_cutoff,
+            declick,
+            env: env_params,
+            mod_env: mod_env_params,
+            mod_env_target,
+            mod_env_amount,
+            filter: filter_params,
+            filter2: filter2_params,
+            warmth: warmth_params,
+            filter_routing,
+            distortion: dist_params,
+            delay: delay_params,
+            reverb: reverb_params,
+            stereoize,
+            tremolo: tremolo_params,
+            trance_gate: trance_params,
+            sequencer: seq_params,
+            eq: eq_params,
+            compressor: comp_params,
+            looper: looper_params,
+            sample_hold: sh_params,
+            grains: grain_params,
+            grain_level,
+            input_gain,
+            input_monitor,
+            waveform,
+            sub,
+            wt_enabled,
+            wt_position,
+            chord,
+            mono,
+            output_routing: routing,
+            source_gains,
+            quality,
+            fast_math,
+            fx_on,
+            note_map,
+            rt_priority: want_rt,
+            exiting,
+            recording,
+            stress_mode,
+            stress_interval,
+        } = self.params;
+
+        // this runs on the host's audio thread, so priority changes are made from here (one
+        // syscall, only when the setting flips)
+        if want_rt != self.rt_applied {
+            self.rt_applied = want_rt;
+            let status = rt_priority::set_current_thread(want_rt);
+            state.rt_status.store(status as u8, Ordering::SeqCst);
+        }
+
+        if seed != self.seed {
+            self.reseed(seed);
+        }
+        let sine_osc: fn(f32) -> f32 = if fast_math { fast_sine } else { sine };
+        self.voices.set_glide_mode(glide_mode);
+        self.voices.set_steal(voice_steal);
+        self.voices.set_humanize(humanize);
+        self.voices.set_reset_phase(reset_phase);
+        self.voices.set_limit(match quality {
+            Quality::Full => MAX_VOICES,
+            Quality::Low => LOW_QUALITY_VOICES,
+        });
+
+        // notes: queued MIDI events, then the UI gate's demo tone
+        if let Ok(mut events) = state.note_events.try_lock() {
+            for ev in events.drain(..) {
+                self.voices.handle(ev, chord, mono);
+            }
+        }
+        if gate != self.gate_was {
+            self.gate_was = gate;
+            self.voices.handle(
+                if gate {
+                    NoteEvent::On {
+                        key: GATE_KEY,
+                        freq,
+                        velocity: 1.0,
+                        slide: false,
+                    }
+                } else {
+                    NoteEvent::Off {
+                        key: GATE_KEY,
+                        velocity: 0.5,
+                    }
+                },
+                chord,
+                mono,
+            );
+        }
+
+        // tuner: a plain sine at a fixed, conservative level, bypassing voices and effects
+        let exit_step = if exiting {
+            1.0 / (EXIT_FADE_SECS * self.sample_rate)
+        } else {
+            0.0
+        };
+        if let Some(hz) = tuner {
+            for frame in data.chunks_mut(channels) {
+                let (s, _) =
+                    osc_sources(sine, [self.tuner_phase; 2], 0.0, 0.0, 0.0, TUNER_LEVEL, 1.0);
+                self.tuner_phase = (self.tuner_phase + hz / self.sample_rate) % 1.0;
+                let exit = self.exit_gain;
+                self.exit_gain = (exit - exit_step).max(0.0);
+                let s = (s * exit).clamp(-max_output, max_output);
+                state.scope.push(s);
+                write_frame(frame, s, s, routing, &convert);
+            }
+            for peak in [&state.peak_osc_a, &state.peak_osc_b, &state.peak_grains] {
+                store_f32(peak, 0.0);
+            }
+            if self.exit_gain == 0.0 {
+                state.exit_faded.store(true, Ordering::SeqCst);
+            }
+            return;
+        }
+
+        if state.loop_record.swap(false, Ordering::SeqCst) {
+            self.looper.record(&looper_params, self.sample_rate);
+        }
+        if state.loop_clear.swap(false, Ordering::SeqCst) {
+            self.looper.clear();
+        }
+        if state.transport_rewind.swap(false, Ordering::SeqCst) {
+            self.sequencer.rewind();
+            self.trance_gate.rewind();
+        }
+
+        // a switched-off effect is skipped entirely; its state is cleared as it goes off
+        for effect in Effect::ALL {
+            let on = fx_on[effect as usize];
+            if !on && self.fx_was[effect as usize] {
+                self.reset_effect(effect);
+            }
+            self.fx_was[effect as usize] = on;
+        }
+        let filter_on = fx_on[Effect::Filter as usize];
+        // the limiter's delay line still holds whatever it had when it was switched off
+        if lookahead && !self.lookahead_was {
+            self.limiter.clear();
+        }
+        self.lookahead_was = lookahead;
+
+        let buffer_secs = (data.len() / channels.max(1)) as f32 / self.sample_rate;
+        let inject_nan = self.stress(stress_mode, stress_interval, buffer_secs);
+        let glide = GlideStep::new(&glide_params, self.sample_rate);
+        // slide steps glide at the sequencer's own time, whatever the global glide
+        let slide = GlideStep::new(
+            &GlideParams {
+                time: seq_params.slide_time,
+                ..glide_params
+            },
+            self.sample_rate,
+        );
+        self.eq.set(&eq_params);
+        // off, the compressor forgets its state, so switching it on starts clean
+        match &comp_params {
+            Some(p) => self.compressor.set(p),
+            None => self.compressor.clear(),
+        }
+        let duck_trigger = comp_params.map_or(DuckTrigger::Off, |p| p.duck.trigger);
+        // filter coefficients are per buffer, so cutoff follows the S&H value at buffer start
+        let sh_cutoff = if sh_params.target == ShTarget::Cutoff {
+            2f32.powf(self.sample_hold.value() * sh_params.depth / 12.0)
+        } else {
+            1.0
+        };
+        for v in self.voices.voices.iter_mut().filter(|v| v.is_active()) {
+            if v.key == GATE_KEY {
+                v.freq = freq * v.chord_ratio;
+            }
+            // faster key lift -> shorter release: x2^-amount at full velocity, x2^amount at zero
+            v.release_scale = 2f32.powf(-vel_to_release * (v.release_velocity - 0.5) * 2.0);
+            v.drift_ratio = v
+                .drift
+                .each_mut()
+                .map(|d| 2f32.powf(d.advance(buffer_secs) * drift_cents / 1200.0));
+            v.pan_gains = pan_gains(v.pan * voice_spread);
+            let note = freq_to_note(v.freq);
+            // aftertouch opens only this voice's filters
+            let pressure =
+                2f32.powf(v.pressure * pressure_to_cutoff * PRESSURE_CUTOFF_SEMIS / 12.0);
+            v.cutoffs = (
+                filter_params.tracked_cutoff(note) * sh_cutoff * pressure,
+                filter2_params.tracked_cutoff(note) * sh_cutoff * pressure,
+            );
+            set_filters(v, &filter_params, &filter2_params, 1.0, self.sample_rate);
+            if let Some(w) = &warmth_params {
+                let (left, right) = &mut v.warmth;
+                left.set(
+                    w.tracked_cutoff(note),
+                    w.resonance,
+                    w.mode,
+                    self.sample_rate,
+                );
+                right.follow(left);
+            }
+            v.noise.set(&noise_params, self.sample_rate);
+        }
+        let grain_level = grain_level * source_gains[SRC_GRAINS];
+        let ramp = (smoothing * self.sample_rate) as u32;
+        for (param, target) in
+            self.smoothed
+                .iter_mut()
+                .zip([gain, master, osc_mix, phase_offset, stereoize])
+        {
+            param.set(target, ramp);
+        }
+        pick_up(&state.grain_source, &mut self.grain_src);
+        pick_up(&state.wavetable, &mut self.wavetable);
+        let wavetable = self.wavetable.as_deref().filter(|_| wt_enabled);
+        let saw = &self.saw;
+
+        // if the UI is draining the tap right now, this buffer is missing from the take
+        let mut tap = if recording {
+            let tap = state.record_tap.try_lock().ok();
+            if tap.is_none() {
+                state.record_overrun.store(true, Ordering::SeqCst);
+            }
+            tap
+        } else {
+            None
+        };
+        // drained even while not monitoring, so the meter works and the queue doesn't back up
+        self.input_buf.clear();
+        if let Ok(mut queue) = state.input_queue.try_lock() {
+            let n = (data.len() / channels.max(1)).min(queue.len());
+            self.input_buf.extend(queue.drain(..n));
+        }
+        let (mut peak_a, mut peak_b, mut peak_g) = (0.0f32, 0.0f32, 0.0f32);
+        let (mut non_finite, mut peak_out, mut peak_in) = (false, 0.0f32, 0.0f32);
+        // sums of L·R, L² and R² for the correlation meter
+        let mut corr = (0.0f32, 0.0f32, 0.0f32);
+        let fade_step = 1.0 / (STARTUP_FADE_SECS * self.sample_rate);
+        // the envelope may move at most this much per sample (1 = unlimited)
+        let declick_step = if declick {
+            1.0 / (DECLICK_SECS * self.sample_rate)
+        } else {
+            1.0
+        };
+        // with FM on, B runs at a multiple of A (its own tuning and tracking are set aside)
+        let (phase_ratio, key_track) = match fm {
+            Some(fm) => (
+                [osc_ratio[SRC_OSC_A], osc_ratio[SRC_OSC_A] * fm.ratio],
+                [key_track[SRC_OSC_A]; 2],
+            ),
+            None => (osc_ratio, key_track),
+        };
+        // the string is a single mono delay line; phase width applies to the oscillators
+        let wide = phase_width > 0.0 && voice_engine != VoiceEngine::String;
+        for (i, frame) in data.chunks_mut(channels).enumerate() {
+            let [gain, master, osc_mix, phase_offset, stereoize] =
+                self.smoothed.each_mut().map(|p| p.next());
+            let voices = &mut self.voices;
+            let compressor = &mut self.compressor;
+            self.sequencer.process(&seq_params, |ev| {
+                let ev = match ev {
+                    // on this very sample, so the restart lines up with the step
+                    SeqEvent::Step { index } => {
+                        if seq_params.phase_reset {
+                            for v in voices.voices.iter_mut() {
+                                v.restart_phase();
+                            }
+                        }
+                        match duck_trigger {
+                            DuckTrigger::Step => compressor.trigger_duck(),
+                            // four 16ths to the beat
+                            DuckTrigger::Beat if index % 4 == 0 => compressor.sync_beat(),
+                            _ => {}
+                        }
+                        return;
+                    }
+                    SeqEvent::On { key, slide } => NoteEvent::On {
+                        key,
+                        freq: note_to_freq(note_map.played_note(key) as f32),
+                        velocity: SEQ_VELOCITY,
+                        slide,
+                    },
+                    SeqEvent::Off { key } => NoteEvent::Off { key, velocity: 0.5 },
+                };
+                voices.handle(ev, chord, mono);
+            });
+            let sh = self.sample_hold.process(&sh_params);
+            let sh_pitch = if sh_params.target == ShTarget::Pitch {
+                2f32.powf(sh * sh_params.depth / 12.0)
+            } else {
+                1.0
+            };
+            // sum every held or releasing voice
+            let (mut sl, mut sr, mut sum_a, mut sum_b) = (0.0, 0.0, 0.0, 0.0);
+            for v in self.voices.voices.iter_mut().filter(|v| v.is_active()) {
+                let m =
+                    v.mod_env.process(v.gate, &mod_env_params, self.sample_rate) * mod_env_amount;
+                let (mut osc_mix, mut mod_pitch) = (osc_mix, 1.0);
+                match mod_env_target {
+                    ModEnvTarget::Off => {}
+                    // coefficients follow the envelope per sample while it drives the cutoff
+                    ModEnvTarget::Cutoff => set_filters(
+                        v,
+                        &filter_params,
+                        &filter2_params,
+                        2f32.powf(m * MOD_ENV_CUTOFF_SEMIS / 12.0),
+                        self.sample_rate,
+                    ),
+                    ModEnvTarget::Pitch => mod_pitch = 2f32.powf(m * MOD_ENV_PITCH_SEMIS / 12.0),
+                    ModEnvTarget::OscMix => osc_mix = (osc_mix + m).clamp(0.0, 1.0),
+                }
+                let level = gain * trim * v.velocity;
+                let hz = if precision {
+                    v.glide_freq
+                } else {
+                    v.glide_freq * v.humanize_ratio * sh_pitch * mod_pitch
+                };
+                let drift = if precision { [1.0; 2] } else { v.drift_ratio };
+                // cycles per sample of each oscillator
+                let steps = [SRC_OSC_A, SRC_OSC_B]
+                    .map(|i| key_track[i].hz(hz) * drift[i] * phase_ratio[i] / self.sample_rate);
+                // the saw's harmonics stop short of Nyquist for the higher of the two
+                let saw_level = Wavetable::saw_level(steps[0].max(steps[1]));
+                // the carrier waveform: the wavetable while one is enabled, else the waveform
+                let osc = |p: f32| match (wavetable, waveform) {
+                    (Some(wt), _) => wt.sample(p, wt_position),
+                    (None, Waveform::Sine) => sine_osc(p),
+                    (None, Waveform::Saw) => saw.frame_sample(saw_level, p),
+                };
+                let sources = |phase: [f32; 2]| match fm {
+                    Some(fm) => fm_sources(osc, sine_osc, phase, fm.index, level, master),
+                    None => osc_sources(osc, phase, osc_mix, detune, phase_offset, level, master),
+                };
+                let (a, b) = match voice_engine {
+                    // the string takes oscillator A's slot (meter and mute/solo)
+                    VoiceEngine::String => (
+                        v.pluck.process(
+                            self.sample_rate
+                                / (key_track[SRC_OSC_A].hz(hz)
+                                    * drift[SRC_OSC_A]
+                                    * osc_ratio[SRC_OSC_A]),
+                            string_damping,
+                        ) * level
+                            * master,
+                        0.0,
+                    ),
+                    VoiceEngine::Oscillators => sources(v.phase),
+                };
+                // right channel: the same oscillators a fixed fraction of a cycle ahead
+                let (ar, br) = if wide {
+                    sources(v.phase.map(|p| (p + phase_width) % 1.0))
+                } else {
+                    (a, b)
+                };
+                let (a, b) = (a * source_gains[SRC_OSC_A], b * source_gains[SRC_OSC_B]);
+                sum_a += a;
+                sum_b += b;
+                // warmth softens the oscillators before the filters (or without them); the
+                // meters above read the oscillators themselves
+                let warm = |svf: &mut Svf, x: f32| match warmth_params {
+                    Some(_) => svf.process(x),
+                    None => x,
+                };
+                let sub = if sub.level > 0.0 {
+                    let phase = sub.phase(v.phase[SRC_OSC_A], v.sub_count);
+                    sub.sample(phase, steps[SRC_OSC_A], sine_osc, saw)
+                        * sub.level
+                        * level
+                        * master
+                        * source_gains[SRC_OSC_A]
+                } else {
+                    0.0
+                };
+                let tone = warm(&mut v.warmth.0, a + b + sub);
+                let voice_env = EnvParams {
+                    release: env_params.release * v.release_scale,
+                    ..env_params
+                };
+                let env = v.env.process(v.gate, &voice_env, self.sample_rate);
+                v.env_out = slew(v.env_out, env, declick_step);
+                let env = v.env_out;
+                let tone_r = if wide {
+                    let r = ar * source_gains[SRC_OSC_A] + br * source_gains[SRC_OSC_B] + sub;
+                    warm(&mut v.warmth.1, r)
+                } else {
+                    tone
+                };
+                let out = if filter_on {
+                    let driven = filter::drive(tone, filter_params.drive);
+                    filter_routing.process(&mut v.filter, &mut v.filter2, driven) * env
+                } else {
+                    tone * env
+                };
+                let out_r = if !filter_on {
+                    tone_r * env
+                } else if wide {
+                    let (f1, f2) = &mut v.filters_r;
+                    f1.follow(&v.filter);
+                    f2.follow(&v.filter2);
+                    filter_routing.process(f1, f2, filter::drive(tone_r, filter_params.drive)) * env
+                } else {
+                    v.filters_r = (v.filter, v.filter2);
+                    out
+                };
+                // the noise has its own band-pass, so it skips the voice filters but shares the
+                // envelope
+                let noise = if noise_params.level > 0.0 {
+                    v.noise.process() * noise_params.level * level * master * env
+                } else {
+                    0.0
+                };
+                sl += (out + noise) * v.pan_gains.0;
+                sr += (out_r + noise) * v.pan_gains.1;
+                let step = if v.slide { &slide } else { &glide };
+                v.glide_freq = step.advance(v.glide_freq, v.freq);
+                let a_was = v.phase[SRC_OSC_A];
+                for (phase, step) in v.phase.iter_mut().zip(steps) {
+                    *phase = (*phase + step) % 1.0;
+                }
+                if v.phase[SRC_OSC_A] < a_was {
+                    v.sub_count = v.sub_count.wrapping_add(1);
+                }
+            }
+            peak_a = peak_a.max(sum_a.abs());
+            peak_b = peak_b.max(sum_b.abs());
+            if let Some(src) = &self.grain_src {
+                let g = self.granular.process(src, &grain_params) * grain_level * master;
+                peak_g = peak_g.max(g.abs());
+                sl += g;
+                sr += g;
+            }
+            // hard limit after the trim, so a mic picking up the speakers can't run away
+            let input =
+                (self.input_buf.get(i).copied().unwrap_or(0.0) * input_gain).clamp(-1.0, 1.0);
+            peak_in = peak_in.max(input.abs());
+            state.input_capture.push(input);
+            if input_monitor {
+                sl += input;
+                sr += input;
+            }
+            if inject_nan && i == 0 {
+                sl = f32::NAN;
+            }
+            // the loop is recorded from, and plays back into, the bus feeding the effects
+            let (loop_l, loop_r) = self.looper.process(sl, sr, &looper_params);
+            sl += loop_l;
+            sr += loop_r;
+            let (mut l, mut r) = (sl, sr);
+            if fx_on[Effect::Distortion as usize] {
+                (l, r) = self.distortion.process(l, r, &dist_params);
+            }
+            if fx_on[Effect::Delay as usize] {
+                (l, r) = self.delay.process(l, r, &delay_params);
+            }
+            if fx_on[Effect::Reverb as usize] {
+                let (wet_l, wet_r) = self.reverb.process(l, r, &reverb_params);
+                (l, r) = (l + wet_l, r + wet_r);
+            }
+            let (l, r) = self.stereoize.process(l, r, stereoize);
+            let tremolo = self.tremolo.process(&tremolo_params);
+            let trem =
+                if precision { 1.0 } else { tremolo } * self.trance_gate.process(&trance_params);
+            let (l, r) = if fx_on[Effect::Eq as usize] {
+                self.eq.process(l * trem, r * trem)
+            } else {
+                (l * trem, r * trem)
+            };
+            let (l, r) = match &comp_params {
+                Some(p) => self.compressor.process(l, r, p),
+                None => (l, r),
+            };
+            let fade = self.startup_gain * self.exit_gain;
+            self.startup_gain = (self.startup_gain + fade_step).min(1.0);
+            self.exit_gain = (self.exit_gain - exit_step).max(0.0);
+            let (mut l, mut r) = (l * fade, r * fade);
+            // watchdog: one bad value must not take the whole output (and the feedback
+            // paths) with it; the state is reset after the buffer
+            if !(l.is_finite() && r.is_finite()) {
+                l = 0.0;
+                r = 0.0;
+                non_finite = true;
+            }
+            if lookahead {
+                (l, r) = self.limiter.process(l, r, max_output);
+            }
+            peak_out = peak_out.max(l.abs()).max(r.abs());
+            corr = (corr.0 + l * r, corr.1 + l * l, corr.2 + r * r);
+            // safety ceiling, the last stage: nothing louder reaches the device (the meter
+            // above still shows the true level)
+            let (l, r) = (
+                l.clamp(-max_output, max_output),
+                r.clamp(-max_output, max_output),
+            );
+            state.scope.push(0.5 * (l + r));
+            if let Some(tap) = tap.as_mut() {
+                if tap.len() + 2 <= tap.capacity() {
+                    tap.extend_from_slice(&[l, r]);
+                } else {
+                    state.record_overrun.store(true, Ordering::SeqCst);
+                }
+            }
+            write_frame(frame, l, r, routing, &convert);
+        }
+        if non_finite {
+            self.reset_effect(Effect::Delay);
+            self.reset_effect(Effect::Reverb);
+            self.reset_effect(Effect::Filter);
+            self.reset_effect(Effect::Eq);
+            self.distortion.clear();
+            self.stereoize.clear();
+            self.limiter.clear();
+            self.compressor.clear();
+            self.looper.reset();
+            state.output_fault.store(true, Ordering::SeqCst);
+        }
+        if self.voices.voices.iter().any(|v| v.gate) && peak_out < SILENCE_LEVEL {
+            self.silent_secs += buffer_secs;
+        } else {
+            self.silent_secs = 0.0;
+        }
+        state
+            .silent_while_gated
+            .store(self.silent_secs >= SILENCE_ALARM_SECS, Ordering::SeqCst);
+        if started.elapsed().as_secs_f32() > buffer_secs {
+            state.late_callbacks.fetch_add(1, Ordering::SeqCst);
+        }
+        state
+            .active_voices
+            .store(self.voices.active_count() as u32, Ordering::SeqCst);
+        state
+            .loop_state
+            .store(self.looper.state() as u8, Ordering::SeqCst);
+        store_f32(&state.loop_position, self.looper.position());
+        store_f32(&state.peak_osc_a, peak_a);
+        store_f32(&state.peak_osc_b, peak_b);
+        store_f32(&state.peak_grains, peak_g);
+        store_f32(&state.peak_input, peak_in);
+        store_f32(&state.peak_output, peak_out);
+        if self.exit_gain == 0.0 {
+            state.exit_faded.store(true, Ordering::SeqCst);
+        }
+        let reduction = match comp_params {
+            Some(_) => self.compressor.reduction(),
+            None => 0.0,
+        };
+        store_f32(&state.comp_reduction, reduction);
+        state
+            .output_clamping
+            .store(peak_out > max_output, Ordering::SeqCst);
+        store_f32(&state.correlation, correlation(corr.0, corr.1, corr.2));
+        state
+            .trance_step
+            .store(self.trance_gate.step() as u8, Ordering::SeqCst);
+        state
+            .seq_step
+            .store(self.sequencer.step() as u8, Ordering::SeqCst);
+    }
+}
+
+fn build_stream_f32(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    sample_rate: f32,
+    state: Arc<SharedState>,
+) -> Result<cpal::Stream, anyhow::Error> {
+    let channels = config.channels as usize;
+    let mut engine = Engine::new(sample_rate, &state);
+
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [f32], _| engine.render(&state, data, channels, |s| s),
+        |err| eprintln!("audio err: {}", err),
+        None,
+    )?;
+
+    Ok(stream)
+}
+
+fn build_stream_i16(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    sample_rate: f32,
+    state: Arc<SharedState>,
+) -> Result<cpal::Stream, anyhow::Error> {
+    let channels = config.channels as usize;
+    let mut engine = Engine::new(sample_rate, &state);
+
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [i16], _| {
+            // clamp & scale to i16
+            engine.render(&state, data, channels, |s| {
+                (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+            })
+        },
+        |err| eprintln!("audio err: {}", err),
+        None,
+    )?;
+
+    Ok(stream)
+}
+
+fn build_stream_u16(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    sample_rate: f32,
+    state: Arc<SharedState>,
+) -> Result<cpal::Stream, anyhow::Error> {
+    let channels = config.channels as usize;
+    let mut engine = Engine::new(sample_rate, &state);
+
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [u16], _| {
+            // convert from [-1,1] to [0, u16::MAX]
+            engine.render(&state, data, channels, |s| {
+                (((s.clamp(-1.0, 1.0) * 0.5) + 0.5) * u16::MAX as f32) as u16
+            })
+        },
+        |err| eprintln!("audio err: {}", err),
+        None,
+    )?;
+
+    Ok(stream)
+}
+
+// ---------- MIDI ----------
+
+/// A MIDI file playing through the synth on its own thread
+struct SongPlayback {
+    stop: Arc<AtomicBool>,
+    progress: Arc<AtomicU32>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl SongPlayback {
+    /// Play `song` until it ends or `stop` is set. Time comes from the audio sample clock, so
+    /// nothing plays while the stream is down; without `file_tempo` the song's beats follow
+    /// `tempo_bpm`, live.
+    fn start(state: Arc<SharedState>, song: Arc<Song>, file_tempo: bool) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let progress = Arc::new(AtomicU32::new(0));
+        let (stop2, progress2) = (stop.clone(), progress.clone());
+        let thread = thread::spawn(move || {
+            let clock = || {
+                state.sample_clock.load(Ordering::SeqCst) as f64
+                    / state.sample_rate.load(Ordering::SeqCst) as f64
+            };
+            let mut cursor = SongCursor::new();
+            let mut sounding = [false; 128];
+            let mut last = clock();
+            while !stop2.load(Ordering::SeqCst) {
+                let now = clock();
+                let bpm = (!file_tempo).then(|| load_f32(&state.tempo_bpm) as f64);
+                let playing = cursor.advance(&song, now - last, bpm, |n| {
+                    let key = n.key & 0x7f;
+                    sounding[key as usize] = n.velocity > 0;
+                    if n.velocity > 0 {
+                        state.play_note(key, n.velocity);
+                    } else {
+                        state.send_note(NoteEvent::Off { key, velocity: 0.5 });
+                    }
+                });
+                last = now;
+                store_f32(&progress2, cursor.progress(&song));
+                if !playing {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(2));
+            }
+            // stopped mid-song: release whatever is still held
+            for (key, _) in sounding.iter().enumerate().filter(|(_, &on)| on) {
+                state.send_note(NoteEvent::Off {
+                    key: key as u8,
+                    velocity: 0.5,
+                });
+            }
+        });
+        Self {
+            stop,
+            progress,
+            thread,
+        }
+    }
+}
+
+/// Runs on the midir thread for every incoming message
+fn handle_midi(state: &SharedState, clock: &mut ClockTracker, stamp_us: u64, msg: &[u8]) {
+    match MidiEvent::parse(msg) {
+        MidiEvent::NoteOn { note, velocity } => state.key_down(note, velocity),
+        MidiEvent::NoteOff { note, velocity } => state.key_up(note, velocity as f32 / 127.0),
+        MidiEvent::PolyPressure { note, pressure } => state.send_note(NoteEvent::Pressure {
+            key: note,
+            pressure: pressure as f32 / 127.0,
+        }),
+        // programs past the last slot are ignored
+        MidiEvent::ProgramChange { program } => {
+            if state.program_change.load(Ordering::SeqCst) {
+                if let Some(p) = Preset::slot(program) {
+                    state.apply_preset(&p);
+                    state.publish();
+                }
+            }
+        }
+        MidiEvent::Clock => {
+            if let Some(bpm) = clock.tick(stamp_us) {
+                store_f32(&state.external_bpm, bpm);
+                if state.external_sync.load(Ordering::SeqCst) {
+                    store_f32(&state.tempo_bpm, bpm);
+                    state.publish();
+                }
+            }
+        }
+        MidiEvent::Start => {
+            // start means "from the top": drop the old tick so the first interval is clean
+            clock.reset();
+            state.clock_running.store(true, Ordering::SeqCst);
+            state.transport_rewind.store(true, Ordering::SeqCst);
+            state.seq_running.store(true, Ordering::SeqCst);
+            state.publish();
+        }
+        MidiEvent::Continue => {
+            state.clock_running.store(true, Ordering::SeqCst);
+            state.seq_running.store(true, Ordering::SeqCst);
+            state.publish();
+        }
+        MidiEvent::Stop => {
+            clock.reset();
+            state.clock_running.store(false, Ordering::SeqCst);
+            state.seq_running.store(false, Ordering::SeqCst);
+            state.publish();
+        }
+        MidiEvent::Other => {}
+    }
+}
+
+/// Random seed from `--seed <n>` (or `--seed=<n>`) among `args`, if given and a number
+fn startup_seed(mut args: impl Iterator<Item = String>) -> Option<u32> {
+    while let Some(arg) = args.next() {
+        if arg == "--seed" {
+            return args.next()?.parse().ok();
+        }
+        if let Some(seed) = arg.strip_prefix("--seed=") {
+            return seed.parse().ok();
+        }
+    }
+    None
+}
+
+// ---------- main ----------
+
+fn main() {
+    let shared = Arc::new(SharedState::new());
+    if let Some(seed) = startup_seed(std::env::args().skip(1)) {
+        shared.random_seed.store(seed, Ordering::SeqCst);
+    }
+
+    // initial preset: a file named on the command line or in the environment, falling back
+    // to the built-in one if there is none or it can't be loaded
+    let startup = presets::startup_path(
+        std::env::args().skip(1),
+        std::env::var(presets::DEFAULT_PRESET_VAR).ok(),
+    );
+    let (initial, preset_status) = match startup.map(|path| (Preset::load(&path), path)) {
+        Some((Ok(p), _)) => (p, String::new()),
+        Some((Err(e), path)) => {
+            let warning = format!(
+                "couldn't load {}: {:#}; using the built-in preset",
+                path.display(),
+                e
+            );
+            eprintln!("{}", warning);
+            (Preset::ryan_josh(), warning)
+        }
+        None => (Preset::ryan_josh(), String::new()),
+    };
+    shared.apply_preset(&initial);
+
+    // spawn audio thread
+    {
+        let s = shared.clone();
+        thread::spawn(move || {
+            if let Err(e) = start_audio_thread(s) {
+                eprintln!("Audio thread error: {:?}", e);
+            }
+        });
+    }
+
+    // MIDI input; the connection must outlive the GUI loop
+    let midi = {
+        let s = shared.clone();
+        let mut clock = ClockTracker::new();
+        midi::connect_first(move |stamp, msg| handle_midi(&s, &mut clock, stamp, msg))
+    };
+    let (_midi_conn, midi_status) = match midi {
+        Ok((conn, name)) => (Some(conn), name),
+        Err(e) => {
+            eprintln!("MIDI unavailable: {:?}", e);
+            (None, "not connected".to_string())
+        }
+    };
+
+    // run eframe GUI
+    let options = eframe::NativeOptions::default();
+    let app = SynthApp {
+        state: shared,
+        db_sliders: false,
+        settings: UiSettings::load(std::path::Path::new(UI_SETTINGS_FILE)),
+        grain_path: String::new(),
+        grain_status: String::new(),
+        wt_path: String::new(),
+        wt_status: String::new(),
+        midi_status,
+        output_devices: output_device_names(),
+        scope_buf: Vec::with_capacity(scope::SCOPE_LEN),
+        pitch_buf: Vec::with_capacity(scope::SCOPE_LEN),
+        spectrum_frozen: None,
+        xy_snap_back: false,
+        rec_path: "take.wav".to_string(),
+        rec_depth: BitDepth::Int24,
+        rec_mono: false,
+        automation: Automation::new(),
+        auto_beats: 16,
+        auto_seen: [0.0; ParamId::ALL.len()],
+        kb_held: None,
+        kb_velocity_from_y: true,
+        computer_held: Vec::new(),
+        preset_dir: PRESET_DIR.to_string(),
+        preset_entries: presets::scan(std::path::Path::new(PRESET_DIR)).unwrap_or_default(),
+        preset_save_name: String::new(),
+        preset_status,
+        preset_tag_filter: None,
+        preset_tags_text: String::new(),
+        patch_code: String::new(),
+        song_path: String::new(),
+        song: None,
+        song_status: String::new(),
+        song_file_tempo: true,
+        song_playback: None,
+        recorder: None,
+        rec_status: String::new(),
+        rec_scratch: Vec::with_capacity(TAP_CAPACITY),
+        fault_logged: false,
+        silence_logged: false,
+    };
+    if let Err(e) = eframe::run_native(
         "Rust Synth Prototype",
         options,
         Box::new(|_cc| Box::new(app)),
@@ -371,3 +5444,1286 @@ fn main() {
         eprintln!("eframe error: {:?}", e);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    const EPS: f32 = 1e-6;
+
+    /// Counts allocations made on the current thread, so tests running in parallel don't
+    /// disturb each other's counts
+    struct CountingAlloc;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            // try_with: the slot is gone while the thread is being torn down
+            let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: CountingAlloc = CountingAlloc;
+
+    fn allocations() -> usize {
+        ALLOCATIONS.with(Cell::get)
+    }
+
+    /// Mixed output for one phase, as the engine sums it
+    fn mixed(phase: f32, osc_mix: f32, detune: f32, gain: f32, master: f32) -> f32 {
+        let (a, b) = osc_sources(sine, [phase; 2], osc_mix, detune, 0.0, gain, master);
+        a + b
+    }
+
+    fn phases() -> impl Iterator<Item = f32> {
+        (0..256).map(|i| i as f32 / 256.0)
+    }
+
+    #[test]
+    fn mix_zero_is_oscillator_a() {
+        for phase in phases() {
+            let expected = (phase * TAU).sin() * 0.7 * 0.8;
+            assert!((mixed(phase, 0.0, 25.0, 0.7, 0.8) - expected).abs() < EPS);
+        }
+    }
+
+    #[test]
+    fn mix_one_is_detuned_oscillator_b() {
+        let detune = 25.0;
+        for phase in phases() {
+            let expected = ((phase + detune * 0.001).fract() * TAU).sin() * 0.7 * 0.8;
+            assert!((mixed(phase, 1.0, detune, 0.7, 0.8) - expected).abs() < EPS);
+        }
+    }
+
+    #[test]
+    fn output_is_bounded_by_gain_times_master() {
+        for osc_mix in [0.0, 0.25, 0.5, 0.75, 1.0] {
+            for detune in [-100.0, -3.0, 0.0, 12.5, 100.0] {
+                for phase in phases() {
+                    let out = mixed(phase, osc_mix, detune, 0.6, 0.9);
+                    assert!(out.abs() <= 0.6 * 0.9 + EPS, "{} > bound", out);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn half_cycle_offset_inverts_oscillator_b() {
+        for phase in phases() {
+            let (_, b0) = osc_sources(sine, [phase; 2], 1.0, 0.0, 0.0, 1.0, 1.0);
+            let (_, b) = osc_sources(sine, [phase; 2], 1.0, 0.0, 0.5, 1.0, 1.0);
+            assert!((b + b0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn zero_gain_is_silent() {
+        for phase in phases() {
+            assert_eq!(mixed(phase, 0.5, 40.0, 0.0, 1.0), 0.0);
+        }
+    }
+
+    #[test]
+    fn sources_are_reported_separately() {
+        let (a, b) = osc_sources(sine, [0.25; 2], 0.5, 0.0, 0.0, 1.0, 1.0);
+        assert!((a - 0.5).abs() < EPS && (b - 0.5).abs() < EPS);
+    }
+
+    #[test]
+    fn the_sidechain_ducks_the_output_on_each_beat() {
+        let sr = 48_000.0;
+        let state = SharedState::new();
+        // ratio 1: the compressor only passes the duck on
+        state.comp_enabled.store(true, Ordering::SeqCst);
+        store_f32(&state.comp_ratio, 1.0);
+        state
+            .duck_trigger
+            .store(DuckTrigger::Beat as u8, Ordering::SeqCst);
+        let mut engine = Engine::new(sr, &state);
+        let beat = (sr / 2.0) as usize; // 120 BPM
+        let mut data = vec![0.0f32; 2 * 4 * beat];
+        engine.render(&state, &mut data, 2, |s| s);
+        let peak = |from: f32, to: f32, b: usize| {
+            let frames =
+                b * beat + (from * beat as f32) as usize..b * beat + (to * beat as f32) as usize;
+            frames.fold(0.0f32, |m, n| m.max(data[2 * n].abs()))
+        };
+        for b in 1..4 {
+            let (ducked, open) = (peak(0.005, 0.02, b), peak(0.7, 0.95, b));
+            assert!(ducked < 0.35 * open, "beat {}: {} vs {}", b, ducked, open);
+        }
+    }
+
+    #[test]
+    fn beat_ducking_locks_to_the_sequencer() {
+        let sr = 48_000.0;
+        let state = SharedState::new();
+        state.comp_enabled.store(true, Ordering::SeqCst);
+        store_f32(&state.comp_ratio, 1.0);
+        state
+            .duck_trigger
+            .store(DuckTrigger::Beat as u8, Ordering::SeqCst);
+        for step in state.seq_steps.lock().unwrap().iter_mut() {
+            step.on = false;
+        }
+        let mut engine = Engine::new(sr, &state);
+        // frames per beat at 120 BPM
+        let beat = (sr / 2.0) as usize;
+        // the compressor's own beat clock runs from here; the sequencer starts 0.3 beat
+        // later
+        let mut data = vec![0.0f32; 2 * (0.3 * beat as f32) as usize];
+        engine.render(&state, &mut data, 2, |s| s);
+        state.seq_running.store(true, Ordering::SeqCst);
+        state.publish();
+        data.resize(2 * 3 * beat, 0.0);
+        engine.render(&state, &mut data, 2, |s| s);
+        let peak = |from: f32, to: f32, b: usize| {
+            let frames =
+                b * beat + (from * beat as f32) as usize..b * beat + (to * beat as f32) as usize;
+            frames.fold(0.0f32, |m, n| m.max(data[2 * n].abs()))
+        };
+        for b in 1..3 {
+            let (ducked, open) = (peak(0.005, 0.02, b), peak(0.6, 0.65, b));
+            assert!(ducked < 0.35 * open, "beat {}: {} vs {}", b, ducked, open);
+        }
+    }
+
+    #[test]
+    fn poly_pressure_brightens_only_its_own_note() {
+        let state = SharedState::new();
+        let mut engine = Engine::new(48_000.0, &state);
+        let mut data = vec![0.0f32; 2 * 64];
+        let mut clock = ClockTracker::new();
+        for note in [60, 64] {
+            handle_midi(&state, &mut clock, 0, &[0x90, note, 100]);
+        }
+        engine.render(&state, &mut data, 2, |s| s);
+        let cutoff = |engine: &Engine, key: u8| {
+            let v = engine.voices.voices.iter().find(|v| v.gate && v.key == key);
+            v.unwrap().cutoffs.0
+        };
+        let before = (cutoff(&engine, 60), cutoff(&engine, 64));
+        handle_midi(&state, &mut clock, 0, &[0xA0, 64, 127]);
+        engine.render(&state, &mut data, 2, |s| s);
+        assert_eq!(cutoff(&engine, 60), before.0);
+        let rise = cutoff(&engine, 64) / before.1;
+        let expected = 2f32.powf(0.5 * PRESSURE_CUTOFF_SEMIS / 12.0);
+        assert!((rise - expected).abs() < 1e-3, "{} vs {}", rise, expected);
+    }
+
+    #[test]
+    fn the_input_pitch_is_detected_whether_monitored_or_not() {
+        let sr = 48_000.0;
+        let state = SharedState::new();
+        let mut engine = Engine::new(sr, &state);
+        let mut data = vec![0.0f32; 2 * 512];
+        let mut scratch = Vec::new();
+        assert_eq!(input_pitch(&state, &mut scratch), None);
+        // an A3 arriving at the input, one buffer at a time
+        for block in 0..8 {
+            state.input_queue.lock().unwrap().extend(
+                (block * 512..(block + 1) * 512)
+                    .map(|n| 0.3 * (std::f32::consts::TAU * 220.0 * n as f32 / sr).sin()),
+            );
+            engine.render(&state, &mut data, 2, |s| s);
+        }
+        let hz = input_pitch(&state, &mut scratch).unwrap();
+        assert!((hz - 220.0).abs() < 0.5, "{}", hz);
+        assert_eq!(freq_to_note_name(hz).0, "A3");
+    }
+
+    #[test]
+    fn the_sub_sits_one_or_two_octaves_under_the_note() {
+        let sr = 48_000.0;
+        // the 220 Hz demo tone mixed all the way to a muted B, so only the sub is heard (it
+        // follows A's mute and solo)
+        let sub_pitch = |octaves: u8, waveform: SubWaveform, mute_a: bool| {
+            let state = SharedState::new();
+            store_f32(&state.osc_mix, 1.0);
+            state.source_mute[SRC_OSC_A].store(mute_a, Ordering::SeqCst);
+            state.source_mute[SRC_OSC_B].store(true, Ordering::SeqCst);
+            store_f32(&state.sub_level, 1.0);
+            state.sub_octave.store(octaves, Ordering::SeqCst);
+            state.sub_waveform.store(waveform as u8, Ordering::SeqCst);
+            let mut engine = Engine::new(sr, &state);
+            let mut data = vec![0.0f32; 2 * 24_000];
+            engine.render(&state, &mut data, 2, |s| s);
+            let left: Vec<f32> = data.chunks(2).map(|f| f[0]).collect();
+            pitch::detect(&left[left.len() - pitch::window_len(sr)..], sr)
+        };
+        for waveform in SubWaveform::ALL {
+            assert_eq!(sub_pitch(1, waveform, true), None);
+            for (octaves, hz) in [(1, 110.0), (2, 55.0)] {
+                let found = sub_pitch(octaves, waveform, false).unwrap();
+                assert!(
+                    (found - hz).abs() < 0.5,
+                    "{:?} -{}: {}",
+                    waveform,
+                    octaves,
+                    found
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn closing_fades_the_output_out() {
+        let sr = 48_000.0;
+        let state = SharedState::new();
+        let mut engine = Engine::new(sr, &state);
+        let mut data = vec![0.0f32; 2 * 4_800];
+        engine.render(&state, &mut data, 2, |s| s);
+        let level = data.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        state.exiting.store(true, Ordering::SeqCst);
+        state.publish();
+        engine.render(&state, &mut data, 2, |s| s);
+        // a straight-line fade under the tone's level, silent after `EXIT_FADE_SECS`
+        let ramp = EXIT_FADE_SECS * sr;
+        for (n, frame) in data.chunks(2).enumerate() {
+            let bound = level * (1.0 - n as f32 / ramp).max(0.0);
+            assert!(
+                frame[0].abs() <= bound + 1e-6,
+                "{} at frame {}",
+                frame[0],
+                n
+            );
+        }
+        assert!(state.exit_faded.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn first_buffer_fades_in() {
+        let sr = 48_000.0;
+        // the demo tone is gated on at launch; an instant attack leaves only the fade
+        let state = SharedState::new();
+        store_f32(&state.env_attack, 0.0);
+        let mut engine = Engine::new(sr, &state);
+        let ramp = STARTUP_FADE_SECS * sr;
+        let mut data = vec![0.0f32; 2 * 2 * ramp as usize];
+        engine.render(&state, &mut data, 2, |s| s);
+        assert_eq!(data[0], 0.0);
+        for (n, frame) in data.chunks(2).enumerate() {
+            let bound = (n as f32 / ramp).min(1.0);
+            assert!(
+                frame[0].abs() <= bound + 1e-6,
+                "{} at frame {}",
+                frame[0],
+                n
+            );
+        }
+        assert_eq!(engine.startup_gain, 1.0);
+        let tail = data[data.len() / 2..]
+            .iter()
+            .fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!(tail > 0.1, "{}", tail);
+    }
+
+    /// Largest left/right difference over 100 ms of the demo tone at phase width `width`
+    fn channel_difference(width: f32) -> f32 {
+        let state = SharedState::new();
+        store_f32(&state.phase_width, width);
+        let mut engine = Engine::new(48_000.0, &state);
+        let mut data = vec![0.0f32; 2 * 4_800];
+        engine.render(&state, &mut data, 2, |s| s);
+        data.chunks(2).fold(0.0, |m, f| m.max((f[0] - f[1]).abs()))
+    }
+
+    #[test]
+    fn phase_width_decorrelates_only_when_set() {
+        assert_eq!(channel_difference(0.0), 0.0);
+        assert!(channel_difference(0.25) > 0.05);
+    }
+
+    #[test]
+    fn correlation_meter_tracks_the_phase_width() {
+        let reading = |width: f32| {
+            let state = SharedState::new();
+            store_f32(&state.phase_width, width);
+            let mut engine = Engine::new(48_000.0, &state);
+            let mut data = vec![0.0f32; 2 * 4_800];
+            engine.render(&state, &mut data, 2, |s| s);
+            load_f32(&state.correlation)
+        };
+        assert!(reading(0.0) > 0.99);
+        // half a cycle apart, the sine voice is inverted between the channels
+        assert!(reading(0.5) < -0.9, "{}", reading(0.5));
+    }
+
+    /// Share of the power (up to 5 kHz) that lies on harmonics of a 200 Hz FM carrier whose
+    /// modulator runs at `ratio` times its frequency
+    fn fm_harmonic_share(ratio: f32) -> f32 {
+        const SR: f32 = 48_000.0;
+        const LEN: usize = 4_800; // 10 Hz bins
+        let mut phase = [0.0f32; 2];
+        let x: Vec<f32> = (0..LEN)
+            .map(|_| {
+                let (a, _) = fm_sources(sine, sine, phase, 2.0, 1.0, 1.0);
+                phase[0] = (phase[0] + 200.0 / SR) % 1.0;
+                phase[1] = (phase[1] + 200.0 * ratio / SR) % 1.0;
+                a
+            })
+            .collect();
+        let power = |k: usize| {
+            let (mut re, mut im) = (0.0f32, 0.0f32);
+            for (n, &v) in x.iter().enumerate() {
+                let w = TAU * ((k * n) % LEN) as f32 / LEN as f32;
+                re += v * w.cos();
+                im -= v * w.sin();
+            }
+            re * re + im * im
+        };
+        let (mut harmonic, mut total) = (0.0, 0.0);
+        for k in 1..500 {
+            let p = power(k);
+            total += p;
+            if k % 20 == 0 {
+                harmonic += p;
+            }
+        }
+        harmonic / total
+    }
+
+    #[test]
+    fn fm_integer_ratios_are_harmonic() {
+        for ratio in [1.0, 2.0, 3.0] {
+            let share = fm_harmonic_share(ratio);
+            assert!(share > 0.999, "ratio {}: {}", ratio, share);
+        }
+        let share = fm_harmonic_share(std::f32::consts::SQRT_2);
+        assert!(share < 0.5, "{}", share);
+    }
+
+    #[test]
+    fn switched_off_effects_do_not_replay_stale_tails() {
+        let state = SharedState::new();
+        store_f32(&state.delay_mix, 0.5);
+        store_f32(&state.delay_feedback, 0.5);
+        store_f32(&state.reverb_send, 0.5);
+        let mut engine = Engine::new(48_000.0, &state);
+        let mut data = vec![0.0f32; 2 * 4_800];
+        // fill the delay and reverb with the demo tone, then switch both off and let it end
+        engine.render(&state, &mut data, 2, |s| s);
+        state.gate.store(false, Ordering::SeqCst);
+        for effect in [Effect::Delay, Effect::Reverb] {
+            state.fx_on[effect as usize].store(false, Ordering::SeqCst);
+        }
+        state.publish();
+        for _ in 0..10 {
+            engine.render(&state, &mut data, 2, |s| s);
+        }
+        assert!(data.iter().all(|&s| s == 0.0));
+        // back on, they start empty
+        for effect in [Effect::Delay, Effect::Reverb] {
+            state.fx_on[effect as usize].store(true, Ordering::SeqCst);
+        }
+        state.publish();
+        engine.render(&state, &mut data, 2, |s| s);
+        assert!(data.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn latched_keys_toggle_and_release_when_latch_goes_off() {
+        let state = SharedState::new();
+        let drain = || {
+            let events: Vec<(u8, bool)> = state
+                .note_events
+                .lock()
+                .unwrap()
+                .drain(..)
+                .map(|ev| match ev {
+                    NoteEvent::On { key, .. } => (key, true),
+                    NoteEvent::Off { key, .. } => (key, false),
+                    NoteEvent::Pressure { .. } => unreachable!("keys send no pressure"),
+                })
+                .collect();
+            events
+        };
+        state.set_latch(true);
+        state.key_down(60, 100);
+        state.key_up(60, 0.5);
+        state.key_down(64, 100);
+        state.key_up(64, 0.5);
+        assert_eq!(drain(), [(60, true), (64, true)]);
+        // pressing 60 again releases it; switching latch off releases the rest
+        state.key_down(60, 100);
+        assert_eq!(drain(), [(60, false)]);
+        state.set_latch(false);
+        assert_eq!(drain(), [(64, false)]);
+        state.key_down(67, 100);
+        state.key_up(67, 0.5);
+        assert_eq!(drain(), [(67, true), (67, false)]);
+    }
+
+    #[test]
+    fn a_short_note_rings_through_its_whole_release() {
+        let state = SharedState::new();
+        state.gate.store(false, Ordering::SeqCst);
+        store_f32(&state.env_attack, 0.0);
+        store_f32(&state.env_release, 0.5);
+        state.publish();
+        let mut engine = Engine::new(48_000.0, &state);
+        // past the startup fade
+        let mut data = vec![0.0f32; 2 * 4_800];
+        engine.render(&state, &mut data, 2, |s| s);
+        // a 5 ms note, then the release in 10 ms chunks
+        state.key_down(60, 100);
+        data.resize(2 * 240, 0.0);
+        engine.render(&state, &mut data, 2, |s| s);
+        state.key_up(60, 0.5);
+        data.resize(2 * 480, 0.0);
+        for chunk in 0..70 {
+            engine.render(&state, &mut data, 2, |s| s);
+            let peak = data.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+            if chunk < 45 {
+                assert!(peak > 1e-3, "silent {} ms into the release", chunk * 10);
+            } else if chunk > 55 {
+                assert!(peak < 1e-6, "{} still sounding {} ms in", peak, chunk * 10);
+            }
+        }
+    }
+
+    /// Largest sample-to-sample jump around a zero-attack, zero-release note
+    fn gate_click(declick: bool) -> f32 {
+        let state = SharedState::new();
+        state.gate.store(false, Ordering::SeqCst);
+        store_f32(&state.env_attack, 0.0);
+        store_f32(&state.env_release, 0.0);
+        state.declick.store(declick, Ordering::SeqCst);
+        let mut engine = Engine::new(48_000.0, &state);
+        let mut data = vec![0.0f32; 2 * 4_800];
+        // past the startup fade
+        engine.render(&state, &mut data, 2, |s| s);
+        let mut left = vec![0.0];
+        // released away from a zero crossing, where a hard stop clicks
+        for (gate, frames) in [(true, 4_855), (false, 4_800)] {
+            state.gate.store(gate, Ordering::SeqCst);
+            state.publish();
+            data.resize(2 * frames, 0.0);
+            engine.render(&state, &mut data, 2, |s| s);
+            left.extend(data.iter().step_by(2));
+        }
+        left.windows(2).fold(0.0, |m, w| m.max((w[1] - w[0]).abs()))
+    }
+
+    #[test]
+    fn declick_smooths_zero_attack_and_release() {
+        let raw = gate_click(false);
+        let smooth = gate_click(true);
+        assert!(smooth < raw / 3.0, "{} vs {}", smooth, raw);
+    }
+
+    /// Power of DFT bins 21, 22 and 23 (10 Hz apart) of the demo tone, oscillator A only, at
+    /// 220 Hz with drift, S&H pitch and tremolo all turned up
+    fn tone_bins(precision: bool) -> [f32; 3] {
+        const LEN: usize = 4_800;
+        let state = SharedState::new();
+        store_f32(&state.osc_mix, 0.0);
+        store_f32(&state.analog_drift, 25.0);
+        state
+            .sh_target
+            .store(ShTarget::Pitch as u8, Ordering::SeqCst);
+        store_f32(&state.sh_depth, 1.0);
+        store_f32(&state.tremolo_depth, 0.5);
+        state.precision.store(precision, Ordering::SeqCst);
+        let mut engine = Engine::new(48_000.0, &state);
+        let mut data = vec![0.0f32; 2 * LEN];
+        // past the attack and decay, then one analysis window
+        for _ in 0..6 {
+            engine.render(&state, &mut data, 2, |s| s);
+        }
+        [21, 22, 23].map(|k| {
+            let (mut re, mut im) = (0.0f32, 0.0f32);
+            for (n, frame) in data.chunks(2).enumerate() {
+                let w = TAU * ((k * n) % LEN) as f32 / LEN as f32;
+                re += frame[0] * w.cos();
+                im -= frame[0] * w.sin();
+            }
+            re * re + im * im
+        })
+    }
+
+    #[test]
+    fn precision_mode_sits_exactly_on_the_note() {
+        let [below, at, above] = tone_bins(true);
+        assert!(below.max(above) < at * 1e-4, "{} {} {}", below, at, above);
+        let [below, at, above] = tone_bins(false);
+        assert!(below.max(above) > at * 1e-4, "{} {} {}", below, at, above);
+    }
+
+    #[test]
+    fn untracked_oscillators_drone_whatever_the_note() {
+        let render = |hz: f32, track: f32| {
+            let state = SharedState::new();
+            store_f32(&state.freq_hz, hz);
+            for osc in [SRC_OSC_A, SRC_OSC_B] {
+                store_f32(&state.osc_key_track[osc], track);
+            }
+            let mut engine = Engine::new(48_000.0, &state);
+            let mut data = vec![0.0f32; 2 * 4_800];
+            engine.render(&state, &mut data, 2, |s| s);
+            data
+        };
+        assert_eq!(render(220.0, 0.0), render(330.0, 0.0));
+        assert_ne!(render(220.0, 1.0), render(330.0, 1.0));
+        // halfway sits halfway in pitch
+        let half = KeyTrack {
+            amount: 0.5,
+            fixed_hz: 110.0,
+        };
+        assert!((half.hz(440.0) - 220.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn the_same_seed_renders_the_same_wav() {
+        let render = |seed: u32| {
+            let state = SharedState::new();
+            store_f32(&state.analog_drift, 25.0);
+            store_f32(&state.humanize, 20.0);
+            store_f32(&state.noise_level, 0.3);
+            state
+                .sh_target
+                .store(ShTarget::Pitch as u8, Ordering::SeqCst);
+            state.random_seed.store(seed, Ordering::SeqCst);
+            let mut engine = Engine::new(48_000.0, &state);
+            let mut data = vec![0.0f32; 2 * 4_800];
+            let mut take = Recorder::new(48_000);
+            for _ in 0..5 {
+                engine.render(&state, &mut data, 2, |s| s);
+                take.push(&data);
+            }
+            let path = std::env::temp_dir().join(format!(
+                "synth-seed-{}-{}.wav",
+                seed,
+                std::process::id()
+            ));
+            take.finish(&path, BitDepth::Int24, false).unwrap();
+            let bytes = std::fs::read(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            bytes
+        };
+        assert_eq!(render(42), render(42));
+        assert_ne!(render(42), render(43));
+        let args = |a: &[&str]| {
+            a.iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+                .into_iter()
+        };
+        assert_eq!(startup_seed(args(&["--seed", "42"])), Some(42));
+        assert_eq!(
+            startup_seed(args(&["--preset", "a.json", "--seed=7"])),
+            Some(7)
+        );
+        assert_eq!(startup_seed(args(&["--seed", "x"])), None);
+    }
+
+    #[test]
+    fn fast_math_stays_within_the_sine_error() {
+        let render = |fast_math: bool| {
+            let state = SharedState::new();
+            state.precision.store(true, Ordering::SeqCst);
+            state.fast_math.store(fast_math, Ordering::SeqCst);
+            let mut engine = Engine::new(48_000.0, &state);
+            let mut data = vec![0.0f32; 2 * 4_800];
+            for _ in 0..3 {
+                engine.render(&state, &mut data, 2, |s| s);
+            }
+            data
+        };
+        let (exact, fast) = (render(false), render(true));
+        let worst = exact
+            .iter()
+            .zip(&fast)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0f32, f32::max);
+        // the demo tone is below full scale, so its error is below the sine's
+        assert!(worst > 0.0 && worst < 0.0011, "{}", worst);
+    }
+
+    #[test]
+    fn max_output_is_a_hard_ceiling() {
+        let state = SharedState::new();
+        store_f32(&state.gain, 2.0);
+        store_f32(&state.master_gain, 2.0);
+        store_f32(&state.max_output_db, -12.0);
+        let mut engine = Engine::new(48_000.0, &state);
+        let mut data = vec![0.0f32; 2 * 4_800];
+        for _ in 0..3 {
+            engine.render(&state, &mut data, 2, |s| s);
+        }
+        let ceiling = db_to_linear(-12.0);
+        assert!(data.iter().all(|s| s.abs() <= ceiling));
+        assert!(state.output_clamping.load(Ordering::SeqCst));
+        // the true level is still metered
+        assert!(load_f32(&state.peak_output) > ceiling);
+
+        store_f32(&state.max_output_db, 0.0);
+        store_f32(&state.gain, 0.1);
+        state.publish();
+        // past the smoothing ramp down
+        for _ in 0..2 {
+            engine.render(&state, &mut data, 2, |s| s);
+        }
+        assert!(!state.output_clamping.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn lookahead_keeps_the_ceiling_from_clipping() {
+        let state = SharedState::new();
+        store_f32(&state.gain, 2.0);
+        store_f32(&state.master_gain, 2.0);
+        store_f32(&state.max_output_db, -12.0);
+        state.limiter_lookahead.store(true, Ordering::SeqCst);
+        // the note's attack is the transient
+        let mut engine = Engine::new(48_000.0, &state);
+        let mut data = vec![0.0f32; 2 * 480];
+        for _ in 0..30 {
+            engine.render(&state, &mut data, 2, |s| s);
+            assert!(!state.output_clamping.load(Ordering::SeqCst));
+        }
+        let ceiling = db_to_linear(-12.0);
+        assert!(data.iter().any(|s| s.abs() > 0.9 * ceiling));
+    }
+
+    /// Left channel of the 25 ms buffer after the master is pulled to 0 under a held note,
+    /// with the effects off so nothing rings on
+    fn after_master_drop(smoothing: bool) -> Vec<f32> {
+        let state = SharedState::new();
+        state.smoothing_enabled.store(smoothing, Ordering::SeqCst);
+        for on in &state.fx_on {
+            on.store(false, Ordering::SeqCst);
+        }
+        let mut engine = Engine::new(48_000.0, &state);
+        let mut data = vec![0.0f32; 2 * 1_200];
+        for _ in 0..4 {
+            engine.render(&state, &mut data, 2, |s| s);
+        }
+        store_f32(&state.master_gain, 0.0);
+        state.publish();
+        engine.render(&state, &mut data, 2, |s| s);
+        data.chunks(2).map(|f| f[0]).collect()
+    }
+
+    #[test]
+    fn disabling_smoothing_makes_changes_instant() {
+        assert!(after_master_drop(false).iter().all(|&s| s == 0.0));
+        // the default 20 ms ramp fades out instead
+        let smoothed = after_master_drop(true);
+        assert!(smoothed[..100].iter().any(|&s| s != 0.0));
+        assert_eq!(smoothed[smoothed.len() - 1], 0.0);
+    }
+
+    // stress modes are ignored in release builds
+    #[cfg(debug_assertions)]
+    #[test]
+    fn stress_modes_trip_the_watchdogs() {
+        let state = SharedState::new();
+        state.stress_interval.store(2, Ordering::SeqCst);
+        let mut engine = Engine::new(48_000.0, &state);
+        let mut data = vec![0.0f32; 2 * 64];
+        engine.render(&state, &mut data, 2, |s| s);
+
+        state
+            .stress_mode
+            .store(StressMode::Nan as u8, Ordering::SeqCst);
+        state.publish();
+        engine.render(&state, &mut data, 2, |s| s);
+        assert!(!state.output_fault.load(Ordering::SeqCst));
+        engine.render(&state, &mut data, 2, |s| s);
+        assert!(state.output_fault.load(Ordering::SeqCst));
+        assert!(data.iter().all(|s| s.is_finite()));
+
+        // once the bad values stop, the reset effects let the sound back through
+        state
+            .stress_mode
+            .store(StressMode::Off as u8, Ordering::SeqCst);
+        state.publish();
+        state.output_fault.store(false, Ordering::SeqCst);
+        for _ in 0..4 {
+            engine.render(&state, &mut data, 2, |s| s);
+        }
+        assert!(data.iter().any(|&s| s != 0.0));
+        assert!(!state.output_fault.load(Ordering::SeqCst));
+
+        state
+            .stress_mode
+            .store(StressMode::Overrun as u8, Ordering::SeqCst);
+        state.publish();
+        let late = state.late_callbacks.load(Ordering::SeqCst);
+        for _ in 0..2 {
+            engine.render(&state, &mut data, 2, |s| s);
+        }
+        assert!(state.late_callbacks.load(Ordering::SeqCst) > late);
+    }
+
+    #[test]
+    fn slide_steps_glide_and_others_follow_the_global_setting() {
+        let sr = 48_000.0;
+        let state = SharedState::new();
+        // global glide off; step 2 slides up from step 1, step 3 doesn't
+        let off = Step {
+            on: false,
+            note: 0,
+            slide: false,
+            gate: DEFAULT_GATE,
+        };
+        let mut steps = [off; SEQ_STEPS];
+        for (i, (note, slide)) in [(48, false), (60, true), (48, false)]
+            .into_iter()
+            .enumerate()
+        {
+            steps[i] = Step {
+                on: true,
+                note,
+                slide,
+                gate: DEFAULT_GATE,
+            };
+        }
+        *state.seq_steps.lock().unwrap() = steps;
+        state.seq_running.store(true, Ordering::SeqCst);
+        state.publish();
+        let mut engine = Engine::new(sr, &state);
+        let step_len = (sr / 8.0) as usize; // 16ths at 120 BPM
+        let mut data = vec![0.0f32; 2 * 64];
+        let mut rendered = 0;
+        let mut render_to = |engine: &mut Engine, frames: usize| {
+            while rendered < frames {
+                engine.render(&state, &mut data, 2, |s| s);
+                rendered += 64;
+            }
+        };
+        let sounding = |engine: &Engine, key: u8| {
+            let v = engine.voices.voices.iter().find(|v| v.gate && v.key == key);
+            v.map(|v| (v.glide_freq, v.freq)).unwrap()
+        };
+
+        render_to(&mut engine, 128);
+        let (now, target) = sounding(&engine, 48);
+        assert_eq!(now, target);
+        render_to(&mut engine, step_len + 128);
+        let (now, target) = sounding(&engine, 60);
+        assert!(now < target * 0.99, "{} vs {}", now, target);
+        render_to(&mut engine, 2 * step_len + 128);
+        let (now, target) = sounding(&engine, 48);
+        assert_eq!(now, target);
+    }
+
+    #[test]
+    fn short_gates_separate_repeated_notes() {
+        // the same note on every step: is it still held just before the next step starts?
+        let held_before_next_step = |gate: f32| {
+            let state = SharedState::new();
+            for step in state.seq_steps.lock().unwrap().iter_mut() {
+                *step = Step {
+                    on: true,
+                    note: 48,
+                    slide: false,
+                    gate,
+                };
+            }
+            state.seq_running.store(true, Ordering::SeqCst);
+            let mut engine = Engine::new(48_000.0, &state);
+            let mut data = vec![0.0f32; 2 * 64];
+            // 16ths at 120 BPM are 6000 frames; stop a buffer short of the second step
+            for _ in 0..6000 / 64 {
+                engine.render(&state, &mut data, 2, |s| s);
+            }
+            engine.voices.voices.iter().any(|v| v.gate && v.key == 48)
+        };
+        assert!(!held_before_next_step(0.25));
+        assert!(held_before_next_step(1.0));
+    }
+
+    #[test]
+    fn midi_start_rewinds_the_sequencer_and_trance_gate() {
+        let state = SharedState::new();
+        state.seq_running.store(true, Ordering::SeqCst);
+        state.trance_enabled.store(true, Ordering::SeqCst);
+        let mut engine = Engine::new(48_000.0, &state);
+        let mut data = vec![0.0f32; 2 * 64];
+        // 16ths at 120 BPM are 6000 frames: a little over five steps in
+        for _ in 0..500 {
+            engine.render(&state, &mut data, 2, |s| s);
+        }
+        assert_eq!(engine.sequencer.step(), 5);
+        assert_eq!(engine.trance_gate.step(), 5);
+        handle_midi(&state, &mut ClockTracker::new(), 0, &[0xFA]);
+        engine.render(&state, &mut data, 2, |s| s);
+        assert_eq!(engine.sequencer.step(), 0);
+        assert_eq!(engine.trance_gate.step(), 0);
+    }
+
+    #[test]
+    fn midi_start_and_stop_run_the_sequencer() {
+        let state = SharedState::new();
+        let mut clock = ClockTracker::new();
+        for step in state.seq_steps.lock().unwrap().iter_mut() {
+            step.on = true;
+        }
+        let mut engine = Engine::new(48_000.0, &state);
+        let mut data = vec![0.0f32; 2 * 64];
+        handle_midi(&state, &mut clock, 0, &[0xFA]);
+        engine.render(&state, &mut data, 2, |s| s);
+        assert!(state.seq_running.load(Ordering::SeqCst));
+        assert_eq!(engine.sequencer.step(), 0);
+        let seq_key = state.seq_steps.lock().unwrap()[0].note;
+        assert!(engine
+            .voices
+            .voices
+            .iter()
+            .any(|v| v.gate && v.key == seq_key));
+        for _ in 0..200 {
+            engine.render(&state, &mut data, 2, |s| s);
+        }
+        assert_eq!(engine.sequencer.step(), 2);
+        handle_midi(&state, &mut clock, 0, &[0xFC]);
+        engine.render(&state, &mut data, 2, |s| s);
+        assert!(!state.seq_running.load(Ordering::SeqCst));
+        assert_eq!(engine.sequencer.step(), 0);
+        assert!(!engine
+            .voices
+            .voices
+            .iter()
+            .any(|v| v.gate && v.key == seq_key));
+    }
+
+    #[test]
+    fn phase_reset_lands_on_the_step_boundary() {
+        // the demo tone held under an empty running pattern, one frame per buffer
+        let engine_with = |reset: bool| {
+            let state = SharedState::new();
+            for step in state.seq_steps.lock().unwrap().iter_mut() {
+                step.on = false;
+            }
+            state.seq_running.store(true, Ordering::SeqCst);
+            state.phase_reset_on_step.store(reset, Ordering::SeqCst);
+            let engine = Engine::new(48_000.0, &state);
+            (state, engine)
+        };
+        let (plain_state, mut plain) = engine_with(false);
+        let (reset_state, mut reset) = engine_with(true);
+        let mut frame = [0.0f32; 2];
+        let phase = |e: &Engine| e.voices.voices.iter().find(|v| v.gate).unwrap().phase[0];
+        for n in 0..7_000 {
+            plain.render(&plain_state, &mut frame, 2, |s| s);
+            reset.render(&reset_state, &mut frame, 2, |s| s);
+            // the phases part on the very frame the second step starts (about 6000 in)
+            let stepped = reset.sequencer.step() == 1;
+            assert_eq!(phase(&plain) != phase(&reset), stepped, "frame {}", n);
+            if stepped {
+                assert!(n > 5_900, "{}", n);
+                assert!(phase(&reset) < 220.0 / 48_000.0 * 1.01);
+                return;
+            }
+        }
+        panic!("the second step never started");
+    }
+
+    #[test]
+    fn render_never_allocates() {
+        let sr = 48_000.0;
+        let state = SharedState::new();
+        // switch on as much of the engine as possible
+        store_f32(&state.delay_mix, 0.3);
+        store_f32(&state.reverb_send, 0.5);
+        store_f32(&state.dist_drive, 12.0);
+        store_f32(&state.phase_width, 0.2);
+        state.fm_enabled.store(true, Ordering::SeqCst);
+        state.trance_enabled.store(true, Ordering::SeqCst);
+        state.comp_enabled.store(true, Ordering::SeqCst);
+        state.seq_running.store(true, Ordering::SeqCst);
+        store_f32(&state.noise_level, 0.3);
+        state
+            .dist_oversample
+            .store(Oversample::X4 as u8, Ordering::SeqCst);
+        store_f32(&state.tremolo_depth, 0.5);
+        store_f32(&state.eq_low_gain, 3.0);
+        store_f32(&state.filter_cutoff, 2_000.0);
+        state
+            .filter_routing
+            .store(FilterRouting::Parallel as u8, Ordering::SeqCst);
+        state
+            .sh_target
+            .store(ShTarget::Cutoff as u8, Ordering::SeqCst);
+        state
+            .mod_env_target
+            .store(ModEnvTarget::Pitch as u8, Ordering::SeqCst);
+        state
+            .chord_mode
+            .store(ChordType::Major as u8, Ordering::SeqCst);
+        store_f32(&state.glide_time, 0.1);
+        store_f32(&state.voice_spread, 1.0);
+        state.input_monitor.store(true, Ordering::SeqCst);
+        state.loop_beats.store(1, Ordering::SeqCst);
+        state.loop_overdub.store(true, Ordering::SeqCst);
+        state.loop_record.store(true, Ordering::SeqCst);
+        state.publish();
+
+        let mut engine = Engine::new(sr, &state);
+        let mut data = vec![0.0f32; 2 * 512];
+        let mut render = |engine: &mut Engine| {
+            let before = allocations();
+            engine.render(&state, &mut data, 2, |s| s);
+            allocations() - before
+        };
+        let mut total = 0;
+        for note in [60, 64, 67] {
+            state.play_note(note, 100);
+        }
+        // long enough for the one-beat loop to record and play back
+        for _ in 0..60 {
+            total += render(&mut engine);
+        }
+        state.send_note(NoteEvent::Off {
+            key: 64,
+            velocity: 0.5,
+        });
+        state
+            .voice_engine
+            .store(VoiceEngine::String as u8, Ordering::SeqCst);
+        state.mono.store(true, Ordering::SeqCst);
+        state.publish();
+        for _ in 0..20 {
+            total += render(&mut engine);
+        }
+        state.tuner.store(true, Ordering::SeqCst);
+        state.publish();
+        total += render(&mut engine);
+        assert_eq!(total, 0, "allocations in the audio callback");
+    }
+
+    #[test]
+    fn patch_codes_round_trip_and_reject_garbage() {
+        let mut preset = Preset::laura_les();
+        preset.glide_time = 0.25;
+        let code = preset.to_code();
+        let back = Preset::from_code(&code).unwrap();
+        assert_eq!(back.name, preset.name);
+        assert_eq!((back.osc_mix, back.glide_time), (0.85, 0.25));
+        assert!(Preset::from_code("not a code!").is_err());
+        // valid base64, but not deflated JSON
+        assert!(Preset::from_code(&code[1..]).is_err());
+        // a few KB that would inflate to 8 MB are refused without inflating them all
+        let mut deflate = DeflateEncoder::new(Vec::new(), Compression::best());
+        deflate.write_all(&vec![b' '; 8 << 20]).unwrap();
+        let bomb = base64::encode(&deflate.finish().unwrap());
+        let err = Preset::from_code(&bomb).err().unwrap();
+        assert_eq!(err.to_string(), "patch code too large");
+    }
+
+    #[test]
+    fn filter_sweep_preset_opens_and_closes_on_each_note() {
+        let state = SharedState::new();
+        state.apply_preset(&Preset::filter_sweep());
+        for effect in [
+            Effect::Distortion,
+            Effect::Delay,
+            Effect::Reverb,
+            Effect::Eq,
+        ] {
+            state.fx_on[effect as usize].store(false, Ordering::SeqCst);
+        }
+        let mut engine = Engine::new(48_000.0, &state);
+        let mut data = vec![0.0f32; 2 * 480];
+        let mut left = Vec::new();
+        for _ in 0..100 {
+            engine.render(&state, &mut data, 2, |s| s);
+            left.extend(data.chunks(2).map(|f| f[0]));
+        }
+        // brightness: power of the sample-to-sample difference relative to the signal's
+        let brightness = |from_ms: usize| {
+            let w = &left[from_ms * 48..(from_ms + 50) * 48];
+            let diff: f32 = w.windows(2).map(|p| (p[1] - p[0]).powi(2)).sum();
+            diff / w.iter().map(|x| x * x).sum::<f32>()
+        };
+        let (open, closed) = (brightness(30), brightness(900));
+        assert!(open > 4.0 * closed, "{} vs {}", open, closed);
+    }
+
+    #[test]
+    fn typing_into_a_text_field_plays_nothing() {
+        use egui::Key;
+        let mut held = Vec::new();
+        // "C4" and "ad" typed into a field: nothing sounds
+        for key in [Key::C, Key::Num4, Key::A, Key::D] {
+            assert_eq!(computer_key(&mut held, key, true, true), None);
+            assert_eq!(computer_key(&mut held, key, false, true), None);
+        }
+        // outside a field, A and D are C4 and E4; a repeat doesn't retrigger
+        assert_eq!(
+            computer_key(&mut held, Key::A, true, false),
+            Some((60, true))
+        );
+        assert_eq!(computer_key(&mut held, Key::A, true, false), None);
+        assert_eq!(
+            computer_key(&mut held, Key::D, true, false),
+            Some((64, true))
+        );
+        // focus moving into a field mid-note still lets the notes go
+        assert_eq!(
+            computer_key(&mut held, Key::A, false, true),
+            Some((60, false))
+        );
+        assert_eq!(
+            computer_key(&mut held, Key::D, false, false),
+            Some((64, false))
+        );
+        assert!(held.is_empty());
+    }
+
+    #[test]
+    fn warmth_softens_the_harmonics_but_keeps_the_note() {
+        // 100 ms of the demo tone as a sawtooth, after the attack, without effects
+        let render = |warmth: f32| {
+            let state = SharedState::new();
+            state.waveform.store(Waveform::Saw as u8, Ordering::SeqCst);
+            store_f32(&state.warmth, warmth);
+            for on in &state.fx_on {
+                on.store(false, Ordering::SeqCst);
+            }
+            let mut engine = Engine::new(48_000.0, &state);
+            let mut data = vec![0.0f32; 2 * 4_800];
+            for _ in 0..3 {
+                engine.render(&state, &mut data, 2, |s| s);
+            }
+            let left: Vec<f32> = data.chunks(2).map(|f| f[0]).collect();
+            let power: f32 = left.iter().map(|x| x * x).sum();
+            let diff: f32 = left.windows(2).map(|p| (p[1] - p[0]).powi(2)).sum();
+            (power, diff / power)
+        };
+        let (open_power, open_bright) = render(0.0);
+        let (warm_power, warm_bright) = render(1.0);
+        assert!(
+            warm_bright < 0.5 * open_bright,
+            "{} vs {}",
+            warm_bright,
+            open_bright
+        );
+        // most of a saw's power is its fundamental, which stays
+        assert!(
+            warm_power > 0.6 * open_power,
+            "{} vs {}",
+            warm_power,
+            open_power
+        );
+    }
+
+    #[test]
+    fn the_loudness_trim_hears_the_waveform_and_filter() {
+        let sweep = Preset::filter_sweep();
+        let open = Preset {
+            filter: PresetFilter::default(),
+            mod_env: PresetModEnv::default(),
+            ..sweep.clone()
+        }
+        .with_loudness_trim();
+        let sine = Preset {
+            waveform: Waveform::Sine,
+            ..open.clone()
+        }
+        .with_loudness_trim();
+        // the same saw under a low-pass two octaves below the reference pitch, then with the
+        // envelope opening it
+        let closed = Preset {
+            filter: PresetFilter {
+                cutoff: 55.0,
+                ..PresetFilter::default()
+            },
+            ..open.clone()
+        }
+        .with_loudness_trim();
+        let swept = Preset {
+            mod_env: sweep.mod_env,
+            ..closed.clone()
+        }
+        .with_loudness_trim();
+        // a saw carries less power than a sine of the same peak, the closed low-pass takes
+        // most of it away, and the envelope gives some back
+        assert!(
+            open.trim > 1.1 * sine.trim,
+            "{} vs {}",
+            open.trim,
+            sine.trim
+        );
+        assert!(
+            closed.trim > 4.0 * open.trim,
+            "{} vs {}",
+            closed.trim,
+            open.trim
+        );
+        assert!(
+            swept.trim < closed.trim,
+            "{} vs {}",
+            swept.trim,
+            closed.trim
+        );
+    }
+
+    #[test]
+    fn descriptions_and_tags_round_trip_and_older_files_have_none() {
+        let mut preset = Preset::filter_sweep();
+        preset.description = "Squelchy \"acid\" line".to_string();
+        preset.tags = presets::parse_tags("Bass, acid");
+        let json = serde_json::to_string(&preset).unwrap();
+        let back: Preset = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.description, preset.description);
+        assert_eq!(back.tags, ["bass", "acid"]);
+        // and they follow the sound through the shared state into a save
+        let state = SharedState::new();
+        state.apply_preset(&back);
+        let saved = state.current_preset("copy");
+        assert_eq!(
+            (saved.description, saved.tags),
+            (back.description, back.tags)
+        );
+        let old: Preset =
+            serde_json::from_str(r#"{"name": "Old", "osc_mix": 0.5, "detune": 0, "gain": 0.5}"#)
+                .unwrap();
+        assert!(old.description.is_empty() && old.tags.is_empty());
+    }
+
+    #[test]
+    fn the_velocity_range_is_expanded_before_the_curve() {
+        let state = SharedState::new();
+        store_f32(&state.vel_min, 0.3);
+        store_f32(&state.vel_max, 0.9);
+        state
+            .velocity_curve
+            .store(VelocityCurve::Exponential as u8, Ordering::SeqCst);
+        for velocity in [20, 76, 127] {
+            state.key_down(60, velocity);
+        }
+        let levels: Vec<f32> = state
+            .note_events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|ev| match *ev {
+                NoteEvent::On { velocity, .. } => Some(velocity),
+                _ => None,
+            })
+            .collect();
+        // 76/127 is about the middle of 0.3..0.9, so half velocity, squared by the curve
+        assert_eq!(levels[0], 0.0);
+        assert!((levels[1] - 0.25).abs() < 0.01, "{}", levels[1]);
+        assert_eq!(levels[2], 1.0);
+    }
+
+    #[test]
+    fn dropped_files_must_be_json_presets() {
+        let dir = std::env::temp_dir().join(format!("synth-drop-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, text: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, text).unwrap();
+            path
+        };
+        let good = write(
+            "good.json",
+            &serde_json::to_string(&Preset::laura_les()).unwrap(),
+        );
+        let broken = write("broken.json", "{\"name\": ");
+        let wav = write("sample.wav", "RIFF");
+        let loaded = Preset::load_dropped(&good).map(|p| p.name);
+        let errors = [&broken, &wav].map(|p| Preset::load_dropped(p).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.unwrap(), Preset::laura_les().name);
+        assert_eq!(errors, [true, true]);
+    }
+
+    #[test]
+    fn loopback_devices_are_recognized() {
+        for name in [
+            "BlackHole 2ch",
+            "CABLE Input (VB-Audio Virtual Cable)",
+            "Loopback Audio",
+            "Monitor of Built-in Audio Analog Stereo",
+        ] {
+            assert!(is_virtual_device(name), "{}", name);
+        }
+        assert!(!is_virtual_device("MacBook Pro Speakers"));
+        assert!(!is_virtual_device("DELL U2720Q Monitor (HDMI)"));
+        assert!(!is_virtual_device("HDA NVidia, LG Monitor DisplayPort"));
+        assert!(!is_virtual_device("default"));
+    }
+
+    #[test]
+    fn key_velocity_is_soft_at_the_top() {
+        assert_eq!(key_velocity(0.0), 1);
+        assert_eq!(key_velocity(1.0), 127);
+        assert_eq!(key_velocity(0.5), 64);
+        assert_eq!(key_velocity(1.5), 127);
+        assert!(key_velocity(0.25) < key_velocity(0.75));
+    }
+
+    #[test]
+    fn disco_layers_over_the_theme_and_the_theme_persists() {
+        let accent = Color32::from_rgb(255, 0, 128);
+        let disco = Theme::Light.visuals(Some(accent));
+        assert!(!disco.dark_mode);
+        assert_eq!(disco.widgets.inactive.bg_fill, accent);
+        // switching disco off lands back on the plain theme
+        assert_eq!(Theme::Light.visuals(None), egui::Visuals::light());
+        assert_eq!(Theme::Dark.visuals(None), egui::Visuals::dark());
+
+        let path = std::env::temp_dir().join(format!("synth-ui-{}.json", std::process::id()));
+        assert_eq!(UiSettings::load(&path).theme, Theme::Dark);
+        UiSettings {
+            theme: Theme::Light,
+            ..UiSettings::default()
+        }
+        .save(&path)
+        .unwrap();
+        let loaded = UiSettings::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.theme, Theme::Light);
+    }
+
+    #[test]
+    fn open_sections_persist_and_older_settings_get_the_defaults() {
+        let labels: std::collections::HashSet<_> = Section::ALL.map(Section::label).into();
+        assert_eq!(labels.len(), Section::ALL.len());
+
+        let path = std::env::temp_dir().join(format!("synth-sections-{}.json", std::process::id()));
+        UiSettings {
+            theme: Theme::Dark,
+            open: vec![Section::Master, Section::Patches],
+        }
+        .save(&path)
+        .unwrap();
+        let loaded = UiSettings::load(&path);
+        assert_eq!(loaded.open, [Section::Master, Section::Patches]);
+        // a file from before sections keeps its theme and opens the usual ones
+        std::fs::write(&path, r#"{ "theme": "Light" }"#).unwrap();
+        let loaded = UiSettings::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.theme, Theme::Light);
+        assert_eq!(loaded.open, UiSettings::default().open);
+    }
+
+    #[test]
+    fn note_names_with_cents() {
+        let (name, cents) = freq_to_note_name(440.0);
+        assert_eq!(name, "A4");
+        assert!(cents.abs() < 0.01);
+        let (name, cents) = freq_to_note_name(445.0);
+        assert_eq!(name, "A4");
+        assert!((cents - 19.56).abs() < 0.05, "{}", cents);
+        // 59.6 is nearer C4 than B3: reported as C4, 40 cents flat
+        let (name, cents) = freq_to_note_name(note_to_freq(59.6));
+        assert_eq!(name, "C4");
+        assert!((cents + 40.0).abs() < 0.05, "{}", cents);
+    }
+}
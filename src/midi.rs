@@ -0,0 +1,240 @@
+// src/midi.rs
+// MIDI input: opens the first available port and decodes the messages the synth cares about
+// (notes and polyphonic key pressure on any channel, clock and transport).
+// The midir callback runs on its own thread, so handlers must only touch atomics.
+
+use anyhow::Result;
+use midir::{MidiInput, MidiInputConnection};
+
+/// MIDI clock resolution: timing-clock messages per quarter note
+const CLOCK_PPQN: f64 = 24.0;
+
+/// Decoded MIDI message
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MidiEvent {
+    NoteOn { note: u8, velocity: u8 },
+    NoteOff { note: u8, velocity: u8 },
+    // polyphonic aftertouch: how hard one held key is being pressed
+    PolyPressure { note: u8, pressure: u8 },
+    ProgramChange { program: u8 },
+    Clock,
+    Start,
+    Continue,
+    Stop,
+    Other,
+}
+
+impl MidiEvent {
+    pub fn parse(msg: &[u8]) -> Self {
+        match *msg {
+            // note-on with velocity 0 is a note-off by convention
+            // ...and carries no release velocity, so report the neutral 64
+            [status, note, 0] if status & 0xF0 == 0x90 => MidiEvent::NoteOff { note, velocity: 64 },
+            [status, note, velocity] if status & 0xF0 == 0x90 => {
+                MidiEvent::NoteOn { note, velocity }
+            }
+            [status, note, velocity] if status & 0xF0 == 0x80 => {
+                MidiEvent::NoteOff { note, velocity }
+            }
+            [status, note, pressure] if status & 0xF0 == 0xA0 => {
+                MidiEvent::PolyPressure { note, pressure }
+            }
+            [status, program] if status & 0xF0 == 0xC0 => MidiEvent::ProgramChange { program },
+            _ => Self::parse_realtime(msg),
+        }
+    }
+
+    fn parse_realtime(msg: &[u8]) -> Self {
+        match msg.first() {
+            Some(0xF8) => MidiEvent::Clock,
+            Some(0xFA) => MidiEvent::Start,
+            Some(0xFB) => MidiEvent::Continue,
+            Some(0xFC) => MidiEvent::Stop,
+            _ => MidiEvent::Other,
+        }
+    }
+}
+
+/// Response of note level to MIDI note-on velocity
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VelocityCurve {
+    Linear,
+    /// Soft playing stays quieter; the range opens up towards the top
+    Exponential,
+    /// Soft playing comes through louder; the top end is compressed
+    Logarithmic,
+    /// Every note at full level (organ-style)
+    Fixed,
+}
+
+impl VelocityCurve {
+    pub const ALL: [VelocityCurve; 4] = [
+        VelocityCurve::Linear,
+        VelocityCurve::Exponential,
+        VelocityCurve::Logarithmic,
+        VelocityCurve::Fixed,
+    ];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL
+            .get(v as usize)
+            .copied()
+            .unwrap_or(VelocityCurve::Linear)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            VelocityCurve::Linear => "linear",
+            VelocityCurve::Exponential => "exponential",
+            VelocityCurve::Logarithmic => "logarithmic",
+            VelocityCurve::Fixed => "fixed",
+        }
+    }
+
+    /// Level (0..1) for a velocity already scaled to 0..1 (see `expand_velocity`)
+    pub fn apply(self, v: f32) -> f32 {
+        match self {
+            VelocityCurve::Linear => v,
+            VelocityCurve::Exponential => v * v,
+            VelocityCurve::Logarithmic => v.sqrt(),
+            VelocityCurve::Fixed => 1.0,
+        }
+    }
+}
+
+/// Stretch `min..=max` of a 0..1 velocity over the whole range, so a controller that only
+/// sends part of it still plays from silent to full. Anything at or under `min` is 0, at or
+/// over `max` 1.
+pub fn expand_velocity(v: f32, min: f32, max: f32) -> f32 {
+    if max <= min {
+        return if v >= max { 1.0 } else { 0.0 };
+    }
+    ((v - min) / (max - min)).clamp(0.0, 1.0)
+}
+
+/// Derives BPM from the spacing of incoming timing-clock messages
+pub struct ClockTracker {
+    last_tick_us: Option<u64>,
+    // smoothed interval between ticks
+    avg_interval_us: f64,
+}
+
+impl ClockTracker {
+    pub fn new() -> Self {
+        Self {
+            last_tick_us: None,
+            avg_interval_us: 0.0,
+        }
+    }
+
+    /// Forget the previous tick (on start/stop, or after a gap)
+    pub fn reset(&mut self) {
+        self.last_tick_us = None;
+        self.avg_interval_us = 0.0;
+    }
+
+    /// Register a clock tick at `stamp_us`; returns the current tempo estimate once known
+    pub fn tick(&mut self, stamp_us: u64) -> Option<f32> {
+        let prev = self.last_tick_us.replace(stamp_us)?;
+        let interval = stamp_us.saturating_sub(prev) as f64;
+        // a gap longer than a 10 BPM tick means the clock paused; start over
+        if interval <= 0.0 || interval > 60e6 / (10.0 * CLOCK_PPQN) {
+            self.avg_interval_us = 0.0;
+            return None;
+        }
+        self.avg_interval_us = if self.avg_interval_us == 0.0 {
+            interval
+        } else {
+            // one-pole smoothing; clock jitter of a few hundred us is normal
+            self.avg_interval_us + 0.1 * (interval - self.avg_interval_us)
+        };
+        Some((60e6 / (self.avg_interval_us * CLOCK_PPQN)) as f32)
+    }
+}
+
+/// Connect to the first MIDI input port. `handler` gets the midir timestamp (us) and raw bytes.
+/// Returns the connection (input stops when it's dropped) and the port name.
+pub fn connect_first(
+    handler: impl FnMut(u64, &[u8]) + Send + 'static,
+) -> Result<(MidiInputConnection<()>, String)> {
+    let mut input = MidiInput::new("rustsynthproj")?;
+    // we want timing messages; only SysEx and active sensing stay filtered
+    input.ignore(midir::Ignore::SysexAndActiveSense);
+    let port = input
+        .ports()
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("No MIDI input port"))?;
+    let name = input.port_name(&port)?;
+    let mut handler = handler;
+    let conn = input
+        .connect(
+            &port,
+            "rustsynthproj-in",
+            move |stamp, msg, _| handler(stamp, msg),
+            (),
+        )
+        .map_err(|e| anyhow::anyhow!("MIDI connect failed: {}", e))?;
+    Ok((conn, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_curve_ignores_velocity() {
+        for velocity in 1..=127 {
+            assert_eq!(VelocityCurve::Fixed.apply(velocity as f32 / 127.0), 1.0);
+        }
+    }
+
+    #[test]
+    fn program_change_on_any_channel() {
+        assert!(matches!(
+            MidiEvent::parse(&[0xC3, 5]),
+            MidiEvent::ProgramChange { program: 5 }
+        ));
+        assert!(matches!(MidiEvent::parse(&[0xD3, 5]), MidiEvent::Other));
+    }
+
+    #[test]
+    fn poly_pressure_names_its_key() {
+        assert_eq!(
+            MidiEvent::parse(&[0xA2, 60, 100]),
+            MidiEvent::PolyPressure {
+                note: 60,
+                pressure: 100
+            }
+        );
+        assert_eq!(MidiEvent::parse(&[0xA2, 60]), MidiEvent::Other);
+    }
+
+    #[test]
+    fn a_narrow_velocity_range_expands_to_the_full_one() {
+        let expand = |v: f32| expand_velocity(v, 0.3, 0.9);
+        assert_eq!(expand(0.3), 0.0);
+        assert!((expand(0.6) - 0.5).abs() < 1e-6, "{}", expand(0.6));
+        assert!((expand(0.9) - 1.0).abs() < 1e-6, "{}", expand(0.9));
+        assert_eq!((expand(0.1), expand(1.0)), (0.0, 1.0));
+        // the default range leaves velocity alone
+        for v in [0.0, 0.25, 1.0] {
+            assert_eq!(expand_velocity(v, 0.0, 1.0), v);
+        }
+    }
+
+    #[test]
+    fn curves_span_the_full_range_monotonically() {
+        for curve in [
+            VelocityCurve::Linear,
+            VelocityCurve::Exponential,
+            VelocityCurve::Logarithmic,
+        ] {
+            assert_eq!(curve.apply(1.0), 1.0);
+            for velocity in 1..127 {
+                let (v, next) = (velocity as f32 / 127.0, (velocity + 1) as f32 / 127.0);
+                assert!(curve.apply(v) < curve.apply(next));
+            }
+        }
+    }
+}
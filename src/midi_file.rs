@@ -0,0 +1,239 @@
+// src/midi_file.rs
+// Standard MIDI file playback, for auditioning patches without a keyboard. All tracks are
+// merged into one time-ordered list of note events; each event carries both its musical time
+// (beats) and its time under the file's own tempo map (seconds), so playback can follow either
+// the file or the synth's tempo setting.
+
+use anyhow::Result;
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+
+/// Tempo a file plays at until it says otherwise (120 BPM)
+const DEFAULT_US_PER_BEAT: f64 = 500_000.0;
+
+/// One note-on (velocity > 0) or note-off (velocity 0) from the file
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SongNote {
+    pub beat: f64,
+    pub secs: f64,
+    pub key: u8,
+    pub velocity: u8,
+}
+
+pub struct Song {
+    pub notes: Vec<SongNote>,
+    pub tracks: usize,
+}
+
+impl Song {
+    pub fn load(path: &str) -> Result<Self> {
+        Self::parse(&std::fs::read(path)?)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let smf = Smf::parse(bytes)?;
+        let ppq = match smf.header.timing {
+            Timing::Metrical(t) => t.as_int().max(1) as f64,
+            Timing::Timecode(..) => anyhow::bail!("SMPTE-timed files aren't supported"),
+        };
+
+        enum Raw {
+            Tempo(u32),
+            Note(u8, u8),
+        }
+        // (tick, rank, event): at the same tick, tempo changes apply first and note-offs come
+        // before note-ons, so a repeated note isn't cut off by its own release
+        let mut raw = Vec::new();
+        for track in &smf.tracks {
+            let mut tick = 0u64;
+            for ev in track {
+                tick += ev.delta.as_int() as u64;
+                let (rank, event) = match ev.kind {
+                    TrackEventKind::Meta(MetaMessage::Tempo(t)) => (0, Raw::Tempo(t.as_int())),
+                    TrackEventKind::Midi {
+                        message: MidiMessage::NoteOff { key, .. },
+                        ..
+                    } => (1, Raw::Note(key.as_int(), 0)),
+                    TrackEventKind::Midi {
+                        message: MidiMessage::NoteOn { key, vel },
+                        ..
+                    } => {
+                        let vel = vel.as_int();
+                        (if vel == 0 { 1 } else { 2 }, Raw::Note(key.as_int(), vel))
+                    }
+                    _ => continue,
+                };
+                raw.push((tick, rank, event));
+            }
+        }
+        raw.sort_by_key(|&(tick, rank, _)| (tick, rank));
+
+        let mut notes = Vec::new();
+        let (mut secs, mut last_tick, mut us_per_beat) = (0.0, 0, DEFAULT_US_PER_BEAT);
+        for (tick, _, event) in raw {
+            secs += (tick - last_tick) as f64 / ppq * us_per_beat / 1e6;
+            last_tick = tick;
+            match event {
+                Raw::Tempo(t) => us_per_beat = t as f64,
+                Raw::Note(key, velocity) => notes.push(SongNote {
+                    beat: tick as f64 / ppq,
+                    secs,
+                    key,
+                    velocity,
+                }),
+            }
+        }
+        Ok(Self {
+            notes,
+            tracks: smf.tracks.len(),
+        })
+    }
+
+    /// Length up to the last note event, in beats and in seconds at the file's tempo
+    pub fn length(&self) -> (f64, f64) {
+        self.notes.last().map_or((0.0, 0.0), |n| (n.beat, n.secs))
+    }
+}
+
+/// Playback position within a song
+pub struct SongCursor {
+    next: usize,
+    pos: f64,
+}
+
+impl SongCursor {
+    pub fn new() -> Self {
+        Self { next: 0, pos: 0.0 }
+    }
+
+    /// Move on by `dt` seconds, at `bpm` or (None) the file's tempo, handing every note that
+    /// came due to `emit`. Returns false once the song is over.
+    pub fn advance(
+        &mut self,
+        song: &Song,
+        dt: f64,
+        bpm: Option<f64>,
+        mut emit: impl FnMut(&SongNote),
+    ) -> bool {
+        self.pos += bpm.map_or(dt, |bpm| dt * bpm / 60.0);
+        while let Some(n) = song.notes.get(self.next) {
+            let at = if bpm.is_some() { n.beat } else { n.secs };
+            if at > self.pos {
+                break;
+            }
+            emit(n);
+            self.next += 1;
+        }
+        self.next < song.notes.len()
+    }
+
+    /// How far through the song's notes playback is, 0..1
+    pub fn progress(&self, song: &Song) -> f32 {
+        self.next as f32 / song.notes.len().max(1) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midly::{Format, Header, TrackEvent};
+
+    fn ev(delta: u32, kind: TrackEventKind<'static>) -> TrackEvent<'static> {
+        TrackEvent {
+            delta: delta.into(),
+            kind,
+        }
+    }
+
+    fn note(delta: u32, key: u8, vel: u8) -> TrackEvent<'static> {
+        ev(
+            delta,
+            TrackEventKind::Midi {
+                channel: 0.into(),
+                message: MidiMessage::NoteOn {
+                    key: key.into(),
+                    vel: vel.into(),
+                },
+            },
+        )
+    }
+
+    /// Two note tracks plus a tempo track that doubles the speed after two beats
+    fn song() -> Song {
+        let mut smf = Smf::new(Header::new(Format::Parallel, Timing::Metrical(480.into())));
+        let end = || ev(0, TrackEventKind::Meta(MetaMessage::EndOfTrack));
+        smf.tracks.push(vec![
+            ev(
+                960,
+                TrackEventKind::Meta(MetaMessage::Tempo(250_000.into())),
+            ),
+            end(),
+        ]);
+        smf.tracks.push(vec![
+            note(0, 60, 100),
+            note(960, 60, 0),
+            note(0, 60, 90),
+            note(960, 60, 0),
+            end(),
+        ]);
+        smf.tracks
+            .push(vec![note(480, 64, 80), note(960, 64, 0), end()]);
+        let mut bytes = Vec::new();
+        smf.write_std(&mut bytes).unwrap();
+        Song::parse(&bytes).unwrap()
+    }
+
+    #[test]
+    fn tracks_merge_in_time_order_with_the_tempo_map() {
+        let song = song();
+        assert_eq!(song.tracks, 3);
+        let got: Vec<(f64, f64, u8, u8)> = song
+            .notes
+            .iter()
+            .map(|n| (n.beat, n.secs, n.key, n.velocity))
+            .collect();
+        assert_eq!(
+            got,
+            [
+                (0.0, 0.0, 60, 100),
+                (1.0, 0.5, 64, 80),
+                // the release comes before the retrigger at the same tick
+                (2.0, 1.0, 60, 0),
+                (2.0, 1.0, 60, 90),
+                // twice as fast from beat 2
+                (3.0, 1.25, 64, 0),
+                (4.0, 1.5, 60, 0),
+            ]
+        );
+        assert_eq!(song.length(), (4.0, 1.5));
+    }
+
+    /// Time (seconds) at which each note fires while polling every 10 ms
+    fn fire_times(song: &Song, bpm: Option<f64>) -> Vec<f64> {
+        let mut cursor = SongCursor::new();
+        let mut out = Vec::new();
+        let mut now = 0.0;
+        let mut playing = true;
+        while playing {
+            playing = cursor.advance(song, if now == 0.0 { 0.0 } else { 0.01 }, bpm, |_| {
+                out.push(now)
+            });
+            now += 0.01;
+        }
+        out
+    }
+
+    #[test]
+    fn playback_follows_the_file_or_the_set_tempo() {
+        let song = song();
+        let file = fire_times(&song, None);
+        let set = fire_times(&song, Some(60.0));
+        assert_eq!((file.len(), set.len()), (6, 6));
+        for (got, want) in file.iter().zip([0.0, 0.5, 1.0, 1.0, 1.25, 1.5]) {
+            assert!((got - want).abs() < 0.015, "{} vs {}", got, want);
+        }
+        // at 60 BPM a beat is a second, ignoring the file's tempo change
+        for (got, want) in set.iter().zip([0.0, 1.0, 2.0, 2.0, 3.0, 4.0]) {
+            assert!((got - want).abs() < 0.015, "{} vs {}", got, want);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+// src/noise.rs
+// Band-passed noise source for metallic percussion (hi-hats, cymbals, snare wires): white
+// noise through its own state-variable band-pass, one per voice so it follows the voice's
+// envelope. State lives in the audio callback.
+
+use crate::filter::{FilterMode, Svf};
+use crate::rng::Rng;
+
+/// Noise settings read once per audio buffer
+#[derive(Clone, Copy, Debug)]
+pub struct NoiseParams {
+    pub level: f32,     // 0 = off
+    pub freq: f32,      // band-pass center, Hz
+    pub resonance: f32, // 0..1; higher narrows the band
+}
+
+pub struct Noise {
+    rng: Rng,
+    filter: Svf,
+    // keeps the output power roughly constant as the band narrows
+    norm: f32,
+}
+
+impl Noise {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            filter: Svf::new(),
+            norm: 1.0,
+        }
+    }
+
+    /// Restart the random sequence from `seed`, as if newly built with it
+    pub fn reseed(&mut self, seed: u32) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// Update the band-pass from `p`; call once per buffer
+    pub fn set(&mut self, p: &NoiseParams, sample_rate: f32) {
+        self.filter
+            .set(p.freq, p.resonance, FilterMode::BandPass, sample_rate);
+        // white noise through the band-pass has power in proportion to 1 / damping
+        self.norm = self.filter.damping().sqrt();
+    }
+
+    /// Start from rest (a fresh note)
+    pub fn reset(&mut self) {
+        self.filter = Svf::new();
+    }
+
+    pub fn process(&mut self) -> f32 {
+        self.filter.process(self.rng.bipolar()) * self.norm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const SR: f32 = 48_000.0;
+    const LEN: usize = 4_800; // 10 Hz bins
+
+    /// Average power of DFT bins `from..to` of `x`
+    fn band_power(x: &[f32], from: usize, to: usize) -> f32 {
+        let mut sum = 0.0;
+        for k in from..to {
+            let (mut re, mut im) = (0.0f32, 0.0f32);
+            for (n, &v) in x.iter().enumerate() {
+                let w = 2.0 * PI * ((k * n) % x.len()) as f32 / x.len() as f32;
+                re += v * w.cos();
+                im -= v * w.sin();
+            }
+            sum += re * re + im * im;
+        }
+        sum / (to - from) as f32
+    }
+
+    /// Power at the 4 kHz center relative to an octave above, in dB
+    fn center_to_octave_db(resonance: f32) -> f32 {
+        let mut noise = Noise::new(1);
+        noise.set(
+            &NoiseParams {
+                level: 1.0,
+                freq: 4_000.0,
+                resonance,
+            },
+            SR,
+        );
+        // skip the filter's settling
+        let out: Vec<f32> = (0..2 * LEN).map(|_| noise.process()).skip(LEN).collect();
+        10.0 * (band_power(&out, 390, 410) / band_power(&out, 790, 810)).log10()
+    }
+
+    #[test]
+    fn resonance_narrows_the_band() {
+        let wide = center_to_octave_db(0.1);
+        let narrow = center_to_octave_db(0.9);
+        assert!(wide > 0.0, "{:.1} dB", wide);
+        assert!(narrow > wide + 10.0, "{:.1} dB vs {:.1} dB", narrow, wide);
+    }
+}
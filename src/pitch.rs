@@ -0,0 +1,91 @@
+// src/pitch.rs
+// Pitch detection (YIN) for tuning the oscillator to an outside source. Runs on the UI thread
+// over the latest stretch of audio input; it allocates, so it never runs in the callback.
+
+/// Lowest and highest fundamentals looked for
+const MIN_HZ: f32 = 50.0;
+const MAX_HZ: f32 = 2_000.0;
+/// A lag counts as a period once the normalized difference dips under this; noise never does
+const THRESHOLD: f32 = 0.15;
+/// Quieter input than this (RMS) is treated as silence
+const MIN_LEVEL: f32 = 0.005;
+
+/// Samples to analyze at `sample_rate`: two periods of the lowest pitch
+pub fn window_len(sample_rate: f32) -> usize {
+    (2.0 * sample_rate / MIN_HZ) as usize
+}
+
+/// Fundamental of `buf` in Hz, or `None` if it's silent or unpitched
+pub fn detect(buf: &[f32], sample_rate: f32) -> Option<f32> {
+    let max_lag = ((sample_rate / MIN_HZ) as usize).min(buf.len() / 2);
+    let min_lag = ((sample_rate / MAX_HZ) as usize).max(2);
+    if max_lag <= min_lag + 1 {
+        return None;
+    }
+    let power = buf.iter().map(|x| x * x).sum::<f32>() / buf.len() as f32;
+    if power.sqrt() < MIN_LEVEL {
+        return None;
+    }
+    // cumulative mean normalized difference: 0 where the signal repeats exactly
+    let width = buf.len() - max_lag;
+    let mut diff = vec![1.0; max_lag + 1];
+    let mut running = 0.0;
+    for (tau, d) in diff.iter_mut().enumerate().skip(1) {
+        let sum: f32 = (0..width).map(|j| (buf[j] - buf[j + tau]).powi(2)).sum();
+        running += sum;
+        *d = if running > 0.0 {
+            sum * tau as f32 / running
+        } else {
+            1.0
+        };
+    }
+    // the first dip under the threshold, followed down to its bottom
+    let mut tau = (min_lag..max_lag).find(|&t| diff[t] < THRESHOLD)?;
+    while tau + 1 < max_lag && diff[tau + 1] < diff[tau] {
+        tau += 1;
+    }
+    // parabola through the bottom and its neighbours, for a fractional period
+    let (a, b, c) = (diff[tau - 1], diff[tau], diff[tau + 1]);
+    let curve = a - 2.0 * b + c;
+    let shift = if curve.abs() > 1e-9 {
+        0.5 * (a - c) / curve
+    } else {
+        0.0
+    };
+    Some(sample_rate / (tau as f32 + shift))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    const SR: f32 = 48_000.0;
+
+    #[test]
+    fn finds_the_fundamental_of_sines_and_saws() {
+        let len = window_len(SR);
+        for hz in [55.0, 110.0, 440.0, 1_234.0] {
+            let sine: Vec<f32> = (0..len)
+                .map(|n| 0.5 * (std::f32::consts::TAU * hz * n as f32 / SR).sin())
+                .collect();
+            let found = detect(&sine, SR).unwrap();
+            assert!((found / hz - 1.0).abs() < 0.002, "{} vs {}", found, hz);
+            // a saw's strong harmonics mustn't pull it up an octave
+            let saw: Vec<f32> = (0..len)
+                .map(|n| 0.5 * ((hz * n as f32 / SR).fract() * 2.0 - 1.0))
+                .collect();
+            let found = detect(&saw, SR).unwrap();
+            assert!((found / hz - 1.0).abs() < 0.005, "{} vs {}", found, hz);
+        }
+    }
+
+    #[test]
+    fn noise_and_silence_have_no_pitch() {
+        let len = window_len(SR);
+        let mut rng = Rng::new(7);
+        let noise: Vec<f32> = (0..len).map(|_| 0.5 * rng.bipolar()).collect();
+        assert_eq!(detect(&noise, SR), None);
+        assert_eq!(detect(&vec![0.0; len], SR), None);
+    }
+}
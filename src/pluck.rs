@@ -0,0 +1,74 @@
+// src/pluck.rs
+// Karplus-Strong plucked string: a delay line one note period long, excited with a noise
+// burst and fed back through a damping low-pass. Each voice owns one; the line is allocated
+// once when the voice pool is built, so plucking never allocates.
+
+use crate::dsp::flush_denormal;
+use crate::rng::Rng;
+
+/// Delay line length in samples; bounds the lowest note (about 12 Hz at 48 kHz)
+pub const MAX_PERIOD: usize = 4096;
+
+/// Energy kept per trip around the loop, so even undamped strings die away
+const LOOP_GAIN: f32 = 0.996;
+
+pub struct Pluck {
+    buf: Vec<f32>,
+    pos: usize,
+    prev: f32,
+    rng: Rng,
+    // excite on the next sample (set at note-on, when the period isn't known yet)
+    pending: bool,
+}
+
+impl Pluck {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            buf: vec![0.0; MAX_PERIOD],
+            pos: 0,
+            prev: 0.0,
+            rng: Rng::new(seed),
+            pending: false,
+        }
+    }
+
+    /// Restart the random sequence from `seed`, as if newly built with it
+    pub fn reseed(&mut self, seed: u32) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// Strike the string: the next `process` call fills one period with noise
+    pub fn pluck(&mut self) {
+        self.pending = true;
+    }
+
+    /// Next output sample for a string `period` samples long (sample rate / frequency).
+    /// `damping` 0..1 blends in the two-point average that makes high partials die faster.
+    pub fn process(&mut self, period: f32, damping: f32) -> f32 {
+        let damping = damping.clamp(0.0, 1.0);
+        // the averaging filter delays by half a sample at full damping; keep the pitch true
+        let period = (period - 0.5 * damping).clamp(2.0, (MAX_PERIOD - 2) as f32);
+        if self.pending {
+            self.pending = false;
+            for i in 1..=period.ceil() as usize {
+                self.buf[(self.pos + MAX_PERIOD - i) % MAX_PERIOD] = self.rng.bipolar();
+            }
+            self.prev = 0.0;
+        }
+
+        // fractional read one period back
+        let back = self.pos as f32 + MAX_PERIOD as f32 - period;
+        let i = back as usize;
+        let frac = back - i as f32;
+        let s0 = self.buf[i % MAX_PERIOD];
+        let s1 = self.buf[(i + 1) % MAX_PERIOD];
+        let cur = s0 + (s1 - s0) * frac;
+
+        let avg = 0.5 * (cur + self.prev);
+        self.prev = cur;
+        let fed_back = cur + damping * (avg - cur);
+        self.buf[self.pos] = flush_denormal(fed_back * LOOP_GAIN);
+        self.pos = (self.pos + 1) % MAX_PERIOD;
+        cur
+    }
+}
@@ -0,0 +1,223 @@
+// src/presets.rs
+// Preset files on disk: one JSON document per preset in a presets directory. The browser
+// lists them by the `name` field inside, not by file name, and can narrow them down by the
+// `tags` stored alongside.
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// One `.json` file found in the presets directory
+pub struct PresetEntry {
+    pub path: PathBuf,
+    // the preset's own name, or the file name if it couldn't be read
+    pub name: String,
+    pub tags: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl PresetEntry {
+    /// Whether the browser shows this entry under `filter` (`None` shows everything; files
+    /// that can't be read have no tags)
+    pub fn matches(&self, filter: Option<&str>) -> bool {
+        filter.is_none_or(|tag| self.tags.iter().any(|t| t == tag))
+    }
+}
+
+/// Tags typed as a comma-separated list: trimmed, lowercased, empty and repeated ones dropped
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split(',').map(|t| t.trim().to_lowercase()) {
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// Every tag used in `entries`, sorted, for the browser's filter
+pub fn all_tags(entries: &[PresetEntry]) -> Vec<String> {
+    let mut tags: Vec<String> = entries.iter().flat_map(|e| e.tags.clone()).collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// File name (without directory) for a preset called `name`: anything that isn't safe in a
+/// file name becomes '_'
+pub fn file_name(name: &str) -> String {
+    let stem: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || " -_()&".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.json", if stem.is_empty() { "preset" } else { &stem })
+}
+
+/// Environment variable naming a preset file to start with
+pub const DEFAULT_PRESET_VAR: &str = "SYNTH_DEFAULT_PRESET";
+
+/// Preset file to load at launch: `--preset <path>` (or `--preset=<path>`) among `args`,
+/// else the `DEFAULT_PRESET_VAR` value in `env`, else none (the built-in default)
+pub fn startup_path(
+    mut args: impl Iterator<Item = String>,
+    env: Option<String>,
+) -> Option<PathBuf> {
+    while let Some(arg) = args.next() {
+        if arg == "--preset" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--preset=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    env.filter(|v| !v.is_empty()).map(PathBuf::from)
+}
+
+/// List the `.json` files in `dir`, sorted by name. A missing directory is just empty; files
+/// that fail to parse are still listed, with the reason.
+pub fn scan(dir: &Path) -> std::io::Result<Vec<PresetEntry>> {
+    #[derive(Deserialize)]
+    struct Named {
+        name: String,
+        #[serde(default)]
+        tags: Vec<String>,
+    }
+
+    let read = match std::fs::read_dir(dir) {
+        Ok(read) => read,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut entries = Vec::new();
+    for item in read {
+        let path = item?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let named = std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| Ok(serde_json::from_str::<Named>(&text)?));
+        let file = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        entries.push(match named {
+            Ok(n) => PresetEntry {
+                path,
+                name: n.name,
+                tags: n.tags,
+                error: None,
+            },
+            Err(e) => PresetEntry {
+                path,
+                name: file,
+                tags: Vec::new(),
+                error: Some(e.to_string()),
+            },
+        });
+    }
+    entries.sort_by_key(|e| e.name.to_lowercase());
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_lists_json_presets_by_name() {
+        let dir = std::env::temp_dir().join(format!("synth-presets-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("b.json"), r#"{"name": "Warm pad"}"#).unwrap();
+        std::fs::write(dir.join("a.json"), r#"{"name": "Bright lead"}"#).unwrap();
+        std::fs::write(dir.join("broken.json"), "{").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a preset").unwrap();
+
+        let entries = scan(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["Bright lead", "broken", "Warm pad"]);
+        assert!(entries[0].error.is_none());
+        assert!(entries[1].error.is_some());
+    }
+
+    #[test]
+    fn entries_filter_by_tag() {
+        let dir = std::env::temp_dir().join(format!("synth-tags-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("a.json"),
+            r#"{"name": "Sub bass", "tags": ["bass", "aggressive"]}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("b.json"),
+            r#"{"name": "Glass pad", "tags": ["pad"]}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("c.json"), r#"{"name": "Untagged"}"#).unwrap();
+
+        let entries = scan(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(all_tags(&entries), ["aggressive", "bass", "pad"]);
+        let shown = |filter: Option<&str>| -> Vec<&str> {
+            entries
+                .iter()
+                .filter(|e| e.matches(filter))
+                .map(|e| e.name.as_str())
+                .collect()
+        };
+        assert_eq!(shown(None), ["Glass pad", "Sub bass", "Untagged"]);
+        assert_eq!(shown(Some("bass")), ["Sub bass"]);
+        assert!(shown(Some("lead")).is_empty());
+        assert_eq!(parse_tags(" Bass, pad,, bass ,"), ["bass", "pad"]);
+    }
+
+    #[test]
+    fn missing_directory_is_empty() {
+        let dir = std::env::temp_dir().join("synth-presets-does-not-exist");
+        assert!(scan(&dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn the_command_line_wins_over_the_environment() {
+        let args = |a: &[&str]| {
+            a.iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+                .into_iter()
+        };
+        let env = Some("env.json".to_string());
+        assert_eq!(
+            startup_path(args(&["--preset", "cli.json"]), env.clone()),
+            Some(PathBuf::from("cli.json"))
+        );
+        assert_eq!(
+            startup_path(args(&["--preset=cli.json"]), env.clone()),
+            Some(PathBuf::from("cli.json"))
+        );
+        assert_eq!(
+            startup_path(args(&[]), env),
+            Some(PathBuf::from("env.json"))
+        );
+        assert_eq!(startup_path(args(&[]), Some(String::new())), None);
+        assert_eq!(startup_path(args(&[]), None), None);
+    }
+
+    #[test]
+    fn file_names_are_sanitized() {
+        assert_eq!(
+            file_name("Ryan & Josh (romantic)"),
+            "Ryan & Josh (romantic).json"
+        );
+        assert_eq!(file_name("a/b:c"), "a_b_c.json");
+        assert_eq!(file_name("  "), "preset.json");
+    }
+}
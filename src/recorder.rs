@@ -0,0 +1,163 @@
+// src/recorder.rs
+// WAV recording of the master output. The audio callback copies stereo frames into a
+// preallocated tap without ever growing it; the UI drains the tap into the take every frame
+// and writes the file when recording stops.
+
+use std::path::Path;
+
+use anyhow::Result;
+
+/// Interleaved samples the tap holds between UI drains (~1 s of stereo at 192 kHz)
+pub const TAP_CAPACITY: usize = 2 * 192_000;
+
+/// Sample format of the written file
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BitDepth {
+    Int16,
+    Int24,
+    Float32,
+}
+
+impl BitDepth {
+    pub const ALL: [BitDepth; 3] = [BitDepth::Int16, BitDepth::Int24, BitDepth::Float32];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BitDepth::Int16 => "16-bit",
+            BitDepth::Int24 => "24-bit",
+            BitDepth::Float32 => "32-bit float",
+        }
+    }
+
+    fn spec(self, channels: u16, sample_rate: u32) -> hound::WavSpec {
+        let (bits_per_sample, sample_format) = match self {
+            BitDepth::Int16 => (16, hound::SampleFormat::Int),
+            BitDepth::Int24 => (24, hound::SampleFormat::Int),
+            BitDepth::Float32 => (32, hound::SampleFormat::Float),
+        };
+        hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample,
+            sample_format,
+        }
+    }
+}
+
+/// One take: interleaved stereo f32 collected on the UI thread
+pub struct Recorder {
+    samples: Vec<f32>,
+    sample_rate: u32,
+}
+
+impl Recorder {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            samples: Vec::new(),
+            sample_rate,
+        }
+    }
+
+    /// Append interleaved stereo samples
+    pub fn push(&mut self, interleaved: &[f32]) {
+        self.samples.extend_from_slice(interleaved);
+    }
+
+    pub fn seconds(&self) -> f32 {
+        self.samples.len() as f32 / 2.0 / self.sample_rate.max(1) as f32
+    }
+
+    /// Write the take as WAV. Integer formats clip at full scale; float keeps overs intact.
+    pub fn finish(self, path: impl AsRef<Path>, depth: BitDepth, mono: bool) -> Result<()> {
+        let channels = if mono { 1 } else { 2 };
+        let mut writer = hound::WavWriter::create(path, depth.spec(channels, self.sample_rate))?;
+        let out: Vec<f32> = if mono {
+            self.samples
+                .chunks_exact(2)
+                .map(|f| 0.5 * (f[0] + f[1]))
+                .collect()
+        } else {
+            self.samples
+        };
+        match depth {
+            BitDepth::Int16 => {
+                for s in out {
+                    writer.write_sample((s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+                }
+            }
+            BitDepth::Int24 => {
+                const MAX_24: f32 = ((1 << 23) - 1) as f32;
+                for s in out {
+                    writer.write_sample((s.clamp(-1.0, 1.0) * MAX_24) as i32)?;
+                }
+            }
+            BitDepth::Float32 => {
+                for s in out {
+                    writer.write_sample(s)?;
+                }
+            }
+        }
+        writer.finalize()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn take(samples: &[f32]) -> Recorder {
+        let mut rec = Recorder::new(48_000);
+        rec.push(samples);
+        rec
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("rustsynth-{}-{}.wav", name, std::process::id()))
+    }
+
+    #[test]
+    fn float32_keeps_full_range() {
+        let path = temp_path("f32");
+        let input = [1e-6, -1e-6, 0.5, -0.5, 2.0, -4.0];
+        take(&input)
+            .finish(&path, BitDepth::Float32, false)
+            .unwrap();
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().bits_per_sample, 32);
+        assert_eq!(reader.spec().sample_format, hound::SampleFormat::Float);
+        let read: Vec<f32> = reader.samples::<f32>().map(|s| s.unwrap()).collect();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(read, input);
+    }
+
+    #[test]
+    fn int24_round_trips_at_full_scale() {
+        let path = temp_path("i24");
+        take(&[1.0, -1.0, 0.5, 0.0])
+            .finish(&path, BitDepth::Int24, false)
+            .unwrap();
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().bits_per_sample, 24);
+        assert_eq!(reader.len(), 4);
+        let read: Vec<i32> = reader.samples::<i32>().map(|s| s.unwrap()).collect();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(read, [8_388_607, -8_388_607, 4_194_303, 0]);
+    }
+
+    #[test]
+    fn mono_downmix_halves_the_sum() {
+        let path = temp_path("mono");
+        take(&[0.5, 0.25, -1.0, 1.0])
+            .finish(&path, BitDepth::Float32, true)
+            .unwrap();
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().channels, 1);
+        let read: Vec<f32> = reader.samples::<f32>().map(|s| s.unwrap()).collect();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(read, [0.375, 0.0]);
+    }
+}
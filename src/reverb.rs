@@ -0,0 +1,199 @@
+// src/reverb.rs
+// Stereo Schroeder/Moorer reverb (Freeverb topology: eight damped feedback combs in parallel,
+// four allpasses in series, right channel's lines slightly longer for width), with a freeze
+// mode that holds the current tail indefinitely. Used as a send effect: it returns only the
+// wet signal, which the caller adds to the untouched dry path.
+// Buffers for the full topology are allocated once when the stream is built; the quality
+// setting only chooses how many of the lines run, so `process` never allocates.
+
+use crate::dsp::flush_denormal;
+
+/// Freeverb's line lengths, in samples at 44.1 kHz
+const COMB_TUNING: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNING: [usize; 4] = [556, 441, 341, 225];
+const STEREO_SPREAD: usize = 23;
+/// Input attenuation so the eight summed combs stay near unity
+const INPUT_GAIN: f32 = 0.015;
+
+/// How much of the topology runs: fewer lines cost less CPU but give a sparser tail
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReverbQuality {
+    Low,
+    Medium,
+    High,
+}
+
+impl ReverbQuality {
+    pub const ALL: [ReverbQuality; 3] = [
+        ReverbQuality::Low,
+        ReverbQuality::Medium,
+        ReverbQuality::High,
+    ];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL
+            .get(v as usize)
+            .copied()
+            .unwrap_or(ReverbQuality::High)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ReverbQuality::Low => "low (4 combs)",
+            ReverbQuality::Medium => "medium (6 combs)",
+            ReverbQuality::High => "high (8 combs)",
+        }
+    }
+
+    /// Combs and allpasses in use
+    fn stages(self) -> (usize, usize) {
+        match self {
+            ReverbQuality::Low => (4, 2),
+            ReverbQuality::Medium => (6, 3),
+            ReverbQuality::High => (COMB_TUNING.len(), ALLPASS_TUNING.len()),
+        }
+    }
+}
+
+/// Reverb settings read once per audio buffer
+#[derive(Clone, Copy, Debug)]
+pub struct ReverbParams {
+    pub size: f32,    // 0..1, comb feedback
+    pub damping: f32, // 0..1, high-frequency loss per pass
+    pub send: f32,    // 0..1, share of the dry signal fed into the reverb
+    // hold the tail: unity feedback, no damping, no new input
+    pub freeze: bool,
+    pub quality: ReverbQuality,
+}
+
+struct Comb {
+    buf: Vec<f32>,
+    pos: usize,
+    lowpass: f32,
+}
+
+impl Comb {
+    fn new(len: usize) -> Self {
+        Self {
+            buf: vec![0.0; len.max(1)],
+            pos: 0,
+            lowpass: 0.0,
+        }
+    }
+
+    fn process(&mut self, x: f32, feedback: f32, damp: f32) -> f32 {
+        let out = self.buf[self.pos];
+        self.lowpass = flush_denormal(out * (1.0 - damp) + self.lowpass * damp);
+        self.buf[self.pos] = flush_denormal(x + self.lowpass * feedback);
+        self.pos = (self.pos + 1) % self.buf.len();
+        out
+    }
+}
+
+struct Allpass {
+    buf: Vec<f32>,
+    pos: usize,
+}
+
+impl Allpass {
+    fn new(len: usize) -> Self {
+        Self {
+            buf: vec![0.0; len.max(1)],
+            pos: 0,
+        }
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let delayed = self.buf[self.pos];
+        self.buf[self.pos] = flush_denormal(x + delayed * 0.5);
+        self.pos = (self.pos + 1) % self.buf.len();
+        delayed - x
+    }
+}
+
+struct Channel {
+    combs: Vec<Comb>,
+    allpasses: Vec<Allpass>,
+}
+
+impl Channel {
+    fn new(sample_rate: f32, extra: usize) -> Self {
+        let scale = |n: usize| ((n + extra) as f32 * sample_rate / 44_100.0) as usize;
+        Self {
+            combs: COMB_TUNING.iter().map(|&n| Comb::new(scale(n))).collect(),
+            allpasses: ALLPASS_TUNING
+                .iter()
+                .map(|&n| Allpass::new(scale(n)))
+                .collect(),
+        }
+    }
+
+    fn clear(&mut self) {
+        for c in &mut self.combs {
+            c.buf.fill(0.0);
+            c.lowpass = 0.0;
+        }
+        for a in &mut self.allpasses {
+            a.buf.fill(0.0);
+        }
+    }
+
+    fn process(&mut self, x: f32, feedback: f32, damp: f32, stages: (usize, usize)) -> f32 {
+        let mut out = 0.0;
+        for c in &mut self.combs[..stages.0] {
+            out += c.process(x, feedback, damp);
+        }
+        for a in &mut self.allpasses[..stages.1] {
+            out = a.process(out);
+        }
+        out
+    }
+}
+
+pub struct Reverb {
+    left: Channel,
+    right: Channel,
+    quality: ReverbQuality,
+}
+
+impl Reverb {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            left: Channel::new(sample_rate, 0),
+            right: Channel::new(sample_rate, STEREO_SPREAD),
+            quality: ReverbQuality::High,
+        }
+    }
+
+    /// Silence the tail (e.g. after a non-finite value got into the feedback path)
+    pub fn clear(&mut self) {
+        self.left.clear();
+        self.right.clear();
+    }
+
+    /// Wet return for one stereo frame. Both channels are fed the mono sum scaled by the
+    /// send; frozen, the combs recirculate what they hold without loss and ignore the input.
+    pub fn process(&mut self, l: f32, r: f32, p: &ReverbParams) -> (f32, f32) {
+        if p.quality != self.quality {
+            // lines switched back on would replay a stale tail; start the new topology clean
+            self.quality = p.quality;
+            self.clear();
+        }
+        let stages = p.quality.stages();
+        // fewer combs sum to less; keep the level about the same
+        let comb_gain = COMB_TUNING.len() as f32 / stages.0 as f32;
+        let (input, feedback, damp) = if p.freeze {
+            (0.0, 1.0, 0.0)
+        } else {
+            (
+                (l + r) * 0.5 * p.send * INPUT_GAIN * comb_gain,
+                0.7 + 0.28 * p.size.clamp(0.0, 1.0),
+                0.4 * p.damping.clamp(0.0, 1.0),
+            )
+        };
+        (
+            self.left.process(input, feedback, damp, stages),
+            self.right.process(input, feedback, damp, stages),
+        )
+    }
+}
@@ -0,0 +1,30 @@
+// src/rng.rs
+// Tiny xorshift PRNG for audio-thread randomness (no allocation, no locking, no syscalls).
+
+pub struct Rng(u32);
+
+impl Rng {
+    pub fn new(seed: u32) -> Self {
+        // xorshift gets stuck at zero
+        Self(seed.max(1))
+    }
+
+    /// Uniform in 0..1
+    pub fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Uniform in -1..1
+    pub fn bipolar(&mut self) -> f32 {
+        self.next_f32() * 2.0 - 1.0
+    }
+}
+
+/// Seed for one random source (`stream`, its built-in seed) under the global `seed`.
+/// Seed 0 leaves every source on its built-in seed, so the default sound never changes.
+pub fn mix(seed: u32, stream: u32) -> u32 {
+    stream ^ seed.wrapping_mul(0x9E37_79B9).rotate_left(7)
+}
@@ -0,0 +1,72 @@
+// src/rt_priority.rs
+// Opt-in real-time scheduling for the audio callback thread. On Unix this asks for
+// SCHED_FIFO, which usually needs an rtprio limit (e.g. membership of an "audio" group);
+// when the OS refuses, the thread just keeps its normal priority.
+
+/// Outcome of the last request, for the UI
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RtStatus {
+    Normal,
+    Elevated,
+    Denied,
+    Unsupported,
+}
+
+impl RtStatus {
+    pub const ALL: [RtStatus; 4] = [
+        RtStatus::Normal,
+        RtStatus::Elevated,
+        RtStatus::Denied,
+        RtStatus::Unsupported,
+    ];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL
+            .get(v as usize)
+            .copied()
+            .unwrap_or(RtStatus::Normal)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RtStatus::Normal => "normal priority",
+            RtStatus::Elevated => "real-time priority",
+            RtStatus::Denied => "denied by the OS (normal priority)",
+            RtStatus::Unsupported => "not supported on this OS",
+        }
+    }
+}
+
+/// Switch the calling thread to real-time (`true`) or normal (`false`) scheduling
+#[cfg(unix)]
+pub fn set_current_thread(realtime: bool) -> RtStatus {
+    // SAFETY: plain syscalls on the current thread with a fully initialized sched_param
+    unsafe {
+        let (policy, priority) = if realtime {
+            let min = libc::sched_get_priority_min(libc::SCHED_FIFO);
+            let max = libc::sched_get_priority_max(libc::SCHED_FIFO);
+            (libc::SCHED_FIFO, min + (max - min) / 2)
+        } else {
+            (libc::SCHED_OTHER, 0)
+        };
+        let param = libc::sched_param {
+            sched_priority: priority,
+        };
+        match libc::pthread_setschedparam(libc::pthread_self(), policy, &param) {
+            0 if realtime => RtStatus::Elevated,
+            0 => RtStatus::Normal,
+            _ if realtime => RtStatus::Denied,
+            // dropping back can't really fail; report what we asked for
+            _ => RtStatus::Normal,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn set_current_thread(realtime: bool) -> RtStatus {
+    if realtime {
+        RtStatus::Unsupported
+    } else {
+        RtStatus::Normal
+    }
+}
@@ -0,0 +1,34 @@
+// src/sample.rs
+// WAV loading for sample-based sources. Runs on the UI thread; the audio side only ever
+// sees the finished `Arc<Vec<f32>>`.
+
+use std::path::Path;
+
+use anyhow::Result;
+
+/// Read a WAV file and downmix it to mono f32 in [-1, 1]
+pub fn load_wav_mono(path: impl AsRef<Path>) -> Result<Vec<f32>> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let channels = spec.channels.max(1) as usize;
+
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|v| v as f32 * scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+
+    let mono = interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect::<Vec<_>>();
+    if mono.is_empty() {
+        return Err(anyhow::anyhow!("WAV file has no samples"));
+    }
+    Ok(mono)
+}
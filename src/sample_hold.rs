@@ -0,0 +1,88 @@
+// src/sample_hold.rs
+// Sample-and-hold random modulation: latches a new random value once per tempo-synced step,
+// optionally gliding between held values. State lives in the audio callback.
+
+use crate::rng::{mix, Rng};
+
+/// Built-in seed of the random values
+const SEED: u32 = 0x5EED_5A4D;
+
+/// What the sample-and-hold value modulates
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShTarget {
+    Off,
+    Pitch,
+    Cutoff,
+}
+
+impl ShTarget {
+    pub const ALL: [ShTarget; 3] = [ShTarget::Off, ShTarget::Pitch, ShTarget::Cutoff];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL.get(v as usize).copied().unwrap_or(ShTarget::Off)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ShTarget::Off => "off",
+            ShTarget::Pitch => "pitch",
+            ShTarget::Cutoff => "cutoff",
+        }
+    }
+}
+
+/// Sample-and-hold settings read once per audio buffer
+#[derive(Clone, Copy, Debug)]
+pub struct ShParams {
+    pub rate: f32,      // new values per second (converted from a tempo division)
+    pub smoothing: f32, // 0 = hard steps, 1 = glide across the whole step
+    pub depth: f32,     // +/- semitones at full swing
+    pub target: ShTarget,
+}
+
+pub struct SampleHold {
+    phase: f32,
+    held: f32,
+    value: f32,
+    rng: Rng,
+    sample_rate: f32,
+}
+
+impl SampleHold {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            phase: 0.0,
+            held: 0.0,
+            value: 0.0,
+            rng: Rng::new(SEED),
+            sample_rate,
+        }
+    }
+
+    /// Restart the random sequence under the global `seed` (see `rng::mix`)
+    pub fn reseed(&mut self, seed: u32) {
+        self.rng = Rng::new(mix(seed, SEED));
+    }
+
+    /// Current (smoothed) value, -1..1
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    /// Advance one sample and return the current value (-1..1)
+    pub fn process(&mut self, p: &ShParams) -> f32 {
+        self.phase += p.rate / self.sample_rate;
+        if self.phase >= 1.0 {
+            self.phase = self.phase.fract();
+            self.held = self.rng.bipolar();
+        }
+        // one-pole glide with a time constant of `smoothing` steps
+        let glide_samples = p.smoothing * self.sample_rate / p.rate.max(0.01);
+        if glide_samples < 1.0 {
+            self.value = self.held;
+        } else {
+            self.value += (self.held - self.value) / glide_samples;
+        }
+        self.value
+    }
+}
@@ -0,0 +1,92 @@
+// src/scale.rs
+// Scale quantizer: snaps incoming notes to the nearest degree of the selected scale so
+// anything played lands in key.
+
+/// Note names for the root selector, C = 0
+pub const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Scale {
+    Chromatic,
+    Major,
+    Minor,
+    Pentatonic,
+}
+
+impl Scale {
+    pub const ALL: [Scale; 4] = [
+        Scale::Chromatic,
+        Scale::Major,
+        Scale::Minor,
+        Scale::Pentatonic,
+    ];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL
+            .get(v as usize)
+            .copied()
+            .unwrap_or(Scale::Chromatic)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Scale::Chromatic => "chromatic",
+            Scale::Major => "major",
+            Scale::Minor => "minor",
+            Scale::Pentatonic => "pentatonic",
+        }
+    }
+
+    /// Semitones above the root that belong to the scale
+    fn degrees(self) -> &'static [i32] {
+        match self {
+            Scale::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            Scale::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Scale::Minor => &[0, 2, 3, 5, 7, 8, 10],
+            Scale::Pentatonic => &[0, 2, 4, 7, 9],
+        }
+    }
+
+    /// Nearest scale note to `note` (MIDI number) in the key of `root` (0 = C). Ties snap
+    /// down; chromatic returns the note unchanged.
+    pub fn quantize(self, note: i32, root: u8) -> i32 {
+        let rel = note - root as i32;
+        let octave = rel.div_euclid(12);
+        let within = rel.rem_euclid(12);
+        // include the next octave's root so notes just below it can snap up
+        let nearest = self
+            .degrees()
+            .iter()
+            .copied()
+            .chain(std::iter::once(12))
+            .min_by_key(|&d| (d - within).abs())
+            .unwrap_or(0);
+        root as i32 + octave * 12 + nearest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chromatic_is_a_no_op() {
+        for note in 0..128 {
+            assert_eq!(Scale::Chromatic.quantize(note, 5), note);
+        }
+    }
+
+    #[test]
+    fn snaps_to_nearest_degree_in_key() {
+        // C major: C# ties between C and D and snaps down, F# snaps down to F
+        assert_eq!(Scale::Major.quantize(61, 0), 60);
+        assert_eq!(Scale::Major.quantize(66, 0), 65);
+        // A major pentatonic: B is a degree, C sits between B and C# and snaps down
+        assert_eq!(Scale::Pentatonic.quantize(71, 9), 71);
+        assert_eq!(Scale::Pentatonic.quantize(72, 9), 71);
+        // C major pentatonic: B is closer to the next C than to A
+        assert_eq!(Scale::Pentatonic.quantize(71, 0), 72);
+    }
+}
@@ -0,0 +1,77 @@
+// src/scope.rs
+// Oscilloscope capture: the audio callback pushes samples into a lock-free ring, and the UI
+// copies the ring out and aligns the display window on a rising-edge trigger.
+
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+/// Samples kept in the ring (must exceed the display window so there's room to search); the
+/// longest window at 192 kHz still leaves a quarter of it
+pub const SCOPE_LEN: usize = 32_768;
+/// Time-base range: how much time the display window spans
+pub const MIN_WINDOW_MS: f32 = 5.0;
+pub const MAX_WINDOW_MS: f32 = 100.0;
+
+pub struct ScopeBuffer {
+    samples: Box<[AtomicU32]>,
+    pos: AtomicUsize,
+}
+
+impl ScopeBuffer {
+    pub fn new() -> Self {
+        Self {
+            samples: (0..SCOPE_LEN).map(|_| AtomicU32::new(0)).collect(),
+            pos: AtomicUsize::new(0),
+        }
+    }
+
+    /// Called from the audio callback. Relaxed is fine: a torn frame only shows as a glitch
+    /// in the drawing, never in the audio.
+    pub fn push(&self, v: f32) {
+        let i = self.pos.load(Ordering::Relaxed);
+        self.samples[i].store(v.to_bits(), Ordering::Relaxed);
+        self.pos.store((i + 1) % SCOPE_LEN, Ordering::Relaxed);
+    }
+
+    /// Copy the ring into `out`, oldest sample first
+    pub fn snapshot(&self, out: &mut Vec<f32>) {
+        let start = self.pos.load(Ordering::Relaxed);
+        out.clear();
+        out.extend((0..SCOPE_LEN).map(|k| {
+            f32::from_bits(self.samples[(start + k) % SCOPE_LEN].load(Ordering::Relaxed))
+        }));
+    }
+}
+
+/// Samples in a display window spanning `ms` at `sample_rate`
+pub fn window_len(ms: f32, sample_rate: f32) -> usize {
+    let ms = ms.clamp(MIN_WINDOW_MS, MAX_WINDOW_MS);
+    ((ms / 1000.0 * sample_rate) as usize).clamp(2, SCOPE_LEN * 3 / 4)
+}
+
+/// Start index of the display window: the most recent rising crossing of `level` that still
+/// leaves `window` samples after it. `None` means no crossing was found (free-run).
+pub fn find_trigger(buf: &[f32], level: f32, window: usize) -> Option<usize> {
+    let last = buf.len().checked_sub(window)?;
+    (1..=last)
+        .rev()
+        .find(|&i| buf[i - 1] < level && buf[i] >= level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_time_base_fits_the_ring_and_still_triggers() {
+        assert_eq!(window_len(5.0, 48_000.0), 240);
+        assert_eq!(window_len(1_000.0, 48_000.0), 4_800);
+        assert!(window_len(MAX_WINDOW_MS, 192_000.0) < SCOPE_LEN);
+        // a 40 Hz bass cycle fits in the room left over, so the long window can trigger on it
+        let buf: Vec<f32> = (0..SCOPE_LEN)
+            .map(|n| (std::f32::consts::TAU * 40.0 * n as f32 / 48_000.0).sin())
+            .collect();
+        let window = window_len(MAX_WINDOW_MS, 48_000.0);
+        let start = find_trigger(&buf, 0.0, window).unwrap();
+        assert!(start + window <= SCOPE_LEN && buf[start] >= 0.0 && buf[start - 1] < 0.0);
+    }
+}
@@ -0,0 +1,243 @@
+// src/sequencer.rs
+// 16-step note sequencer in 16ths of the tempo, running in the audio callback so its timing
+// is sample-accurate. A step can slide: its note glides in from the previous one at the
+// sequencer's slide time (303-style), whatever the global glide is, and the previous note is
+// held into it so the two join legato. Each step has its own gate length: short gates give
+// staccato notes, a full gate holds right up to the next step.
+
+/// Steps in a pattern (one bar of 16ths)
+pub const SEQ_STEPS: usize = 16;
+
+/// Fraction of a step a new step's note is held for
+pub const DEFAULT_GATE: f32 = 0.5;
+/// Shortest gate a step can have, so every note still sounds
+pub const MIN_GATE: f32 = 0.05;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Step {
+    pub on: bool,
+    pub note: u8,
+    pub slide: bool,
+    // fraction of the step the note is held for, MIN_GATE..=1 (unless the next step slides
+    // into it)
+    pub gate: f32,
+}
+
+/// Sequencer settings read once per audio buffer
+#[derive(Clone, Copy, Debug)]
+pub struct SeqParams {
+    pub running: bool,
+    pub steps: [Step; SEQ_STEPS],
+    pub rate: f32,       // steps per second (already converted from the tempo)
+    pub slide_time: f32, // glide time (s/octave) of slide steps
+    // restart the oscillators' phases as each step starts (done by the engine on `Step`)
+    pub phase_reset: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SeqEvent {
+    // a step starts (on or not), before its notes; `index` is its place in the bar
+    Step { index: usize },
+    On { key: u8, slide: bool },
+    Off { key: u8 },
+}
+
+pub struct Sequencer {
+    step: usize,
+    // position within the current step, 0..1
+    pos: f32,
+    // the current step hasn't been started yet
+    pending: bool,
+    sounding: Option<u8>,
+    sample_rate: f32,
+}
+
+impl Sequencer {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            step: 0,
+            pos: 0.0,
+            pending: true,
+            sounding: None,
+            sample_rate,
+        }
+    }
+
+    /// Step playing, for the UI
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    /// Go back to the top of the pattern; the first step starts on the next sample
+    pub fn rewind(&mut self) {
+        self.step = 0;
+        self.pos = 0.0;
+        self.pending = true;
+    }
+
+    /// Advance one sample, handing note events to `emit`. Stopping releases the note and
+    /// rewinds to the first step.
+    pub fn process(&mut self, p: &SeqParams, mut emit: impl FnMut(SeqEvent)) {
+        if !p.running {
+            if let Some(key) = self.sounding.take() {
+                emit(SeqEvent::Off { key });
+            }
+            self.step = 0;
+            self.pos = 0.0;
+            self.pending = true;
+            return;
+        }
+        if self.pending {
+            self.pending = false;
+            self.start_step(p, &mut emit);
+        }
+        let before = self.pos;
+        self.pos += p.rate / self.sample_rate;
+        let gate = p.steps[self.step].gate.clamp(MIN_GATE, 1.0);
+        let next = p.steps[(self.step + 1) % SEQ_STEPS];
+        if before < gate && self.pos >= gate && !(next.on && next.slide) {
+            if let Some(key) = self.sounding.take() {
+                emit(SeqEvent::Off { key });
+            }
+        }
+        if self.pos >= 1.0 {
+            self.pos -= 1.0;
+            self.step = (self.step + 1) % SEQ_STEPS;
+            self.start_step(p, &mut emit);
+        }
+    }
+
+    fn start_step(&mut self, p: &SeqParams, emit: &mut impl FnMut(SeqEvent)) {
+        emit(SeqEvent::Step { index: self.step });
+        let step = p.steps[self.step];
+        if !step.on {
+            return;
+        }
+        // the new note starts before the held one is released, so a slide is legato
+        emit(SeqEvent::On {
+            key: step.note,
+            slide: step.slide,
+        });
+        if let Some(old) = self.sounding.replace(step.note) {
+            if old != step.note {
+                emit(SeqEvent::Off { key: old });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SR: f32 = 48_000.0;
+
+    /// (sample, event) for everything emitted over `samples`
+    fn run(p: &SeqParams, samples: usize) -> Vec<(usize, SeqEvent)> {
+        let mut seq = Sequencer::new(SR);
+        let mut out = Vec::new();
+        for n in 0..samples {
+            seq.process(p, |ev| out.push((n, ev)));
+        }
+        out
+    }
+
+    fn params() -> SeqParams {
+        let mut steps = [Step {
+            on: false,
+            note: 0,
+            slide: false,
+            gate: DEFAULT_GATE,
+        }; SEQ_STEPS];
+        steps[0] = Step {
+            on: true,
+            note: 48,
+            slide: false,
+            gate: DEFAULT_GATE,
+        };
+        steps[1] = Step {
+            on: true,
+            note: 60,
+            slide: true,
+            gate: DEFAULT_GATE,
+        };
+        steps[4] = Step {
+            on: true,
+            note: 55,
+            slide: false,
+            gate: DEFAULT_GATE,
+        };
+        SeqParams {
+            running: true,
+            steps,
+            rate: 8.0, // 16ths at 120 BPM
+            slide_time: 0.06,
+            phase_reset: false,
+        }
+    }
+
+    #[test]
+    fn steps_play_on_time_and_loop() {
+        let step = (SR / 8.0) as usize;
+        let events = run(&params(), 2 * SEQ_STEPS * step);
+        let ons: Vec<(usize, u8)> = events
+            .iter()
+            .filter_map(|&(n, ev)| match ev {
+                SeqEvent::On { key, .. } => Some(((n + step / 2) / step, key)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            ons,
+            [(0, 48), (1, 60), (4, 55), (16, 48), (17, 60), (20, 55)]
+        );
+    }
+
+    #[test]
+    fn a_slide_holds_the_previous_note_into_it() {
+        let step = (SR / 8.0) as usize;
+        let events = run(&params(), SEQ_STEPS * step);
+        let first_off = events
+            .iter()
+            .position(|&(_, ev)| ev == SeqEvent::Off { key: 48 })
+            .unwrap();
+        // 48 is held until 60 has started (sliding), instead of released half-way
+        assert_eq!(
+            events[first_off - 1].1,
+            SeqEvent::On {
+                key: 60,
+                slide: true
+            }
+        );
+        assert!(events[first_off].0 >= step);
+        // 60 itself is released at half a step, as 55 doesn't slide
+        let (at, _) = events
+            .iter()
+            .find(|&&(_, ev)| ev == SeqEvent::Off { key: 60 })
+            .unwrap();
+        assert!(at.abs_diff(step + step / 2) <= 2, "{}", at);
+    }
+
+    #[test]
+    fn each_step_releases_at_its_own_gate_length() {
+        let step = (SR / 8.0) as usize;
+        let mut p = params();
+        p.steps[1].slide = false;
+        p.steps[0].gate = 0.25;
+        p.steps[1].gate = 1.0;
+        p.steps[4].gate = 0.0; // below the minimum
+        let events = run(&p, SEQ_STEPS * step);
+        let off_at = |key: u8| {
+            events
+                .iter()
+                .find(|&&(_, ev)| ev == SeqEvent::Off { key })
+                .unwrap()
+                .0
+        };
+        // a quarter gate leaves three quarters of the step silent before the next note
+        assert!(off_at(48).abs_diff(step / 4) <= 2, "{}", off_at(48));
+        assert!(off_at(60).abs_diff(2 * step) <= 2, "{}", off_at(60));
+        let short = (MIN_GATE * step as f32) as usize;
+        assert!(off_at(55).abs_diff(4 * step + short) <= 2, "{}", off_at(55));
+    }
+}
@@ -0,0 +1,69 @@
+// src/smoothing.rs
+// Parameter smoothing: a value that ramps linearly to each new target instead of jumping, so
+// knob moves don't click. Advanced once per sample in the audio callback.
+
+#[derive(Clone, Copy, Debug)]
+pub struct SmoothedParam {
+    value: f32,
+    target: f32,
+    step: f32,
+    // samples left in the current ramp
+    left: u32,
+}
+
+impl SmoothedParam {
+    pub fn new(value: f32) -> Self {
+        Self {
+            value,
+            target: value,
+            step: 0.0,
+            left: 0,
+        }
+    }
+
+    /// Head for `target` over `samples` samples from wherever the value is now; 0 jumps
+    /// straight there. Setting the current target again leaves the ramp alone.
+    pub fn set(&mut self, target: f32, samples: u32) {
+        if samples == 0 {
+            self.value = target;
+            self.left = 0;
+        } else if target != self.target {
+            self.step = (target - self.value) / samples as f32;
+            self.left = samples;
+        }
+        self.target = target;
+    }
+
+    /// Value for the next sample
+    pub fn next(&mut self) -> f32 {
+        if self.left > 0 {
+            self.left -= 1;
+            self.value = if self.left == 0 {
+                self.target
+            } else {
+                self.value + self.step
+            };
+        }
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ramps_to_the_target_or_jumps_with_no_time() {
+        let mut p = SmoothedParam::new(0.0);
+        p.set(1.0, 4);
+        let ramp: Vec<f32> = (0..6).map(|_| p.next()).collect();
+        assert_eq!(ramp, [0.25, 0.5, 0.75, 1.0, 1.0, 1.0]);
+        // a ramp already underway isn't restarted by the same target
+        p.set(0.0, 4);
+        p.next();
+        p.set(0.0, 4);
+        assert_eq!(p.next(), 0.5);
+        p.set(2.0, 0);
+        assert_eq!(p.next(), 2.0);
+    }
+}
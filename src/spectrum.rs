@@ -0,0 +1,104 @@
+// src/spectrum.rs
+// Spectrum analyzer for the UI: a Hann-windowed FFT of the newest scope samples, folded into
+// log-spaced bands. Runs on the UI thread only, so it is free to allocate.
+
+use std::f32::consts::TAU;
+
+/// Samples analyzed per frame (a power of two, no longer than the scope ring)
+pub const FFT_LEN: usize = 2048;
+/// Bars drawn, log-spaced between `LOW_HZ` and `HIGH_HZ`
+pub const BANDS: usize = 48;
+/// Bottom of the display range; quieter bands are drawn empty
+pub const FLOOR_DB: f32 = -90.0;
+
+const LOW_HZ: f32 = 20.0;
+const HIGH_HZ: f32 = 20_000.0;
+
+/// In-place iterative radix-2 FFT; `re.len()` must be a power of two
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let step = -TAU / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (wi, wr) = (step * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let tr = re[b] * wr - im[b] * wi;
+                let ti = re[b] * wi + im[b] * wr;
+                re[b] = re[a] - tr;
+                im[b] = im[a] - ti;
+                re[a] += tr;
+                im[a] += ti;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Level of each band in dB (0 dB = a full-scale sine), from the last `FFT_LEN` of `samples`
+pub fn bands_db(samples: &[f32], sample_rate: f32) -> [f32; BANDS] {
+    let tail = &samples[samples.len().saturating_sub(FFT_LEN)..];
+    let mut re = vec![0.0; FFT_LEN];
+    let mut im = vec![0.0; FFT_LEN];
+    for (n, (r, &x)) in re.iter_mut().zip(tail).enumerate() {
+        let hann = 0.5 - 0.5 * (TAU * n as f32 / FFT_LEN as f32).cos();
+        *r = x * hann;
+    }
+    fft(&mut re, &mut im);
+    // a full-scale sine peaks at FFT_LEN / 4 after the Hann window (coherent gain 1/2)
+    let scale = 4.0 / FFT_LEN as f32;
+    let bin_hz = sample_rate / FFT_LEN as f32;
+    let ratio = (HIGH_HZ / LOW_HZ).powf(1.0 / BANDS as f32);
+    std::array::from_fn(|band| {
+        let lo = LOW_HZ * ratio.powi(band as i32);
+        let from = ((lo / bin_hz) as usize).max(1);
+        let to = ((lo * ratio / bin_hz) as usize).clamp(from + 1, FFT_LEN / 2);
+        let peak = (from..to)
+            .map(|k| re[k].hypot(im[k]) * scale)
+            .fold(0.0f32, f32::max);
+        (20.0 * peak.max(1e-9).log10()).max(FLOOR_DB)
+    })
+}
+
+/// Center frequency (geometric) of `band`, for labels and tests
+pub fn band_hz(band: usize) -> f32 {
+    let ratio = (HIGH_HZ / LOW_HZ).powf(1.0 / BANDS as f32);
+    LOW_HZ * ratio.powf(band as f32 + 0.5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_sine_lights_its_own_band_at_its_level() {
+        let sr = 48_000.0;
+        let hz = 1_000.0;
+        let x: Vec<f32> = (0..FFT_LEN)
+            .map(|n| 0.5 * (TAU * hz * n as f32 / sr).sin())
+            .collect();
+        let bands = bands_db(&x, sr);
+        let loudest = (0..BANDS)
+            .max_by(|&a, &b| bands[a].total_cmp(&bands[b]))
+            .unwrap();
+        let ratio = (HIGH_HZ / LOW_HZ).powf(1.0 / BANDS as f32);
+        assert!((band_hz(loudest) / hz).log(ratio).abs() <= 1.0);
+        // -6 dB for half scale, within the window's scalloping
+        assert!((bands[loudest] + 6.0).abs() < 1.5, "{}", bands[loudest]);
+        assert!(bands[0] < -60.0);
+    }
+}
@@ -0,0 +1,124 @@
+// src/stereoize.rs
+// Stereoizer: widens a mono source by adding a copy pitched a few cents up on the left and a
+// few cents down on the right. Each shift is a short delay line read by two taps sweeping
+// its length (Doppler), half a sweep apart and crossfaded so the jumps at the ends never
+// sound. Buffers are allocated once; `process` never allocates.
+
+use std::f32::consts::PI;
+
+/// Pitch offset of each side's copy, in cents (up on the left, down on the right)
+pub const SHIFT_CENTS: f32 = 6.0;
+/// Length of the delay sweep
+const WINDOW_SECS: f32 = 0.03;
+
+struct Shifter {
+    buf: Vec<f32>,
+    pos: usize,
+    // sweep position of the first tap, 0..1 of the window
+    phase: f32,
+    step: f32,
+    window: f32, // samples
+}
+
+impl Shifter {
+    fn new(cents: f32, sample_rate: f32) -> Self {
+        let window = WINDOW_SECS * sample_rate;
+        let ratio = 2f32.powf(cents / 1200.0);
+        Self {
+            buf: vec![0.0; window as usize + 3],
+            pos: 0,
+            phase: 0.0,
+            // a shrinking delay plays faster (higher), a growing one slower
+            step: (1.0 - ratio) / window,
+            window,
+        }
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let len = self.buf.len();
+        self.buf[self.pos] = x;
+        let mut out = 0.0;
+        for tap in [self.phase, (self.phase + 0.5) % 1.0] {
+            // linear interpolation between the two samples around the delay
+            let delay = 1.0 + tap * self.window;
+            let (whole, frac) = (delay as usize, delay.fract());
+            let a = self.buf[(self.pos + len - whole) % len];
+            let b = self.buf[(self.pos + len - whole - 1) % len];
+            // sin² fades: the two taps' gains always sum to 1
+            out += (PI * tap).sin().powi(2) * (a + (b - a) * frac);
+        }
+        self.phase = (self.phase + self.step).rem_euclid(1.0);
+        self.pos = (self.pos + 1) % len;
+        out
+    }
+}
+
+pub struct Stereoize {
+    left: Shifter,
+    right: Shifter,
+}
+
+impl Stereoize {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            left: Shifter::new(SHIFT_CENTS, sample_rate),
+            right: Shifter::new(-SHIFT_CENTS, sample_rate),
+        }
+    }
+
+    /// Forget the buffered signal (after a fault)
+    pub fn clear(&mut self) {
+        for side in [&mut self.left, &mut self.right] {
+            side.buf.fill(0.0);
+        }
+    }
+
+    /// Add the shifted copies at `amount` (0..1) of the dry level; 0 passes the input
+    /// through untouched
+    pub fn process(&mut self, l: f32, r: f32, amount: f32) -> (f32, f32) {
+        if amount <= 0.0 {
+            return (l, r);
+        }
+        (
+            l + amount * self.left.process(l),
+            r + amount * self.right.process(r),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::correlation;
+
+    const SR: f32 = 48_000.0;
+
+    /// Stereoized 440 Hz mono sine over one second: (L/R correlation, mono-sum RMS relative
+    /// to the dry mono sum)
+    fn widen(amount: f32) -> (f32, f32) {
+        let mut st = Stereoize::new(SR);
+        let (mut sums, mut mono, mut dry) = ((0.0, 0.0, 0.0), 0.0, 0.0);
+        for n in 0..SR as usize {
+            let x = (2.0 * PI * 440.0 * n as f32 / SR).sin();
+            let (l, r) = st.process(x, x, amount);
+            sums = (sums.0 + l * r, sums.1 + l * l, sums.2 + r * r);
+            mono += (l + r) * (l + r);
+            dry += 4.0 * x * x;
+        }
+        (correlation(sums.0, sums.1, sums.2), (mono / dry).sqrt())
+    }
+
+    #[test]
+    fn zero_amount_stays_mono() {
+        let (corr, level) = widen(0.0);
+        assert_eq!(corr, 1.0);
+        assert!((level - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn widens_without_cancelling_in_mono() {
+        let (corr, level) = widen(0.5);
+        assert!(corr < 0.95, "{}", corr);
+        assert!(level > 0.8, "{}", level);
+    }
+}
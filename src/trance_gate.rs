@@ -0,0 +1,111 @@
+// src/trance_gate.rs
+// Trance gate: a 16-step on/off pattern chopping the master output in time with the tempo.
+// Each edge is a short linear ramp rather than a hard switch, so the chops don't click.
+// State lives in the audio callback.
+
+/// Steps in a pattern (one bar of 16ths)
+pub const STEPS: usize = 16;
+
+/// Length of the ramp at each on/off edge
+const EDGE_SECS: f32 = 0.003;
+
+/// Trance gate settings read once per audio buffer
+#[derive(Clone, Copy, Debug)]
+pub struct TranceGateParams {
+    pub enabled: bool,
+    pub pattern: u16, // bit n = step n is open
+    pub rate: f32,    // steps per second (already converted from the tempo)
+}
+
+pub struct TranceGate {
+    // position in steps, 0..STEPS
+    pos: f32,
+    gain: f32,
+    sample_rate: f32,
+}
+
+impl TranceGate {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            pos: 0.0,
+            gain: 1.0,
+            sample_rate,
+        }
+    }
+
+    /// Step the pattern is on, for the UI
+    pub fn step(&self) -> usize {
+        self.pos as usize % STEPS
+    }
+
+    /// Go back to the first step (on MIDI Start)
+    pub fn rewind(&mut self) {
+        self.pos = 0.0;
+    }
+
+    /// Gain for the next sample. While disabled the gate opens and rewinds, so switching it on
+    /// starts the pattern from its first step.
+    pub fn process(&mut self, p: &TranceGateParams) -> f32 {
+        let open = !p.enabled || p.pattern & (1 << self.step()) != 0;
+        let target = if open { 1.0 } else { 0.0 };
+        let ramp = 1.0 / (EDGE_SECS * self.sample_rate);
+        self.gain = if self.gain < target {
+            (self.gain + ramp).min(target)
+        } else {
+            (self.gain - ramp).max(target)
+        };
+        self.pos = if p.enabled {
+            (self.pos + p.rate / self.sample_rate) % STEPS as f32
+        } else {
+            0.0
+        };
+        self.gain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SR: f32 = 48_000.0;
+
+    #[test]
+    fn pattern_loops_at_the_tempo() {
+        let mut gate = TranceGate::new(SR);
+        // 16ths at 120 BPM: 8 steps a second, a bar every 2 s
+        let p = TranceGateParams {
+            enabled: true,
+            pattern: 0b0110_0000_1111_0101,
+            rate: 8.0,
+        };
+        let step_len = (SR / 8.0) as usize;
+        let gains: Vec<f32> = (0..3 * STEPS * step_len)
+            .map(|_| gate.process(&p))
+            .collect();
+        for (step, chunk) in gains.chunks(step_len).enumerate() {
+            let want = if p.pattern & (1 << (step % STEPS)) != 0 {
+                1.0
+            } else {
+                0.0
+            };
+            // past the edge ramp, each step sits at its pattern value
+            assert_eq!(chunk[step_len / 2], want, "step {}", step);
+        }
+    }
+
+    #[test]
+    fn edges_are_ramped() {
+        let mut gate = TranceGate::new(SR);
+        let p = TranceGateParams {
+            enabled: true,
+            pattern: 0b10,
+            rate: 8.0,
+        };
+        let gains: Vec<f32> = (0..(SR / 2.0) as usize).map(|_| gate.process(&p)).collect();
+        let max_step = 1.0 / (EDGE_SECS * SR) + 1e-6;
+        for pair in gains.windows(2) {
+            assert!((pair[1] - pair[0]).abs() <= max_step);
+        }
+        assert!(gains.contains(&0.0) && gains.contains(&1.0));
+    }
+}
@@ -0,0 +1,119 @@
+// src/tremolo.rs
+// Master-bus tremolo: an LFO scaling the output amplitude. State lives in the audio callback.
+
+use std::f32::consts::TAU;
+
+use crate::dsp::fast_sine;
+
+/// LFO waveform
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LfoShape {
+    Sine,
+    Triangle,
+    Square,
+}
+
+impl LfoShape {
+    pub const ALL: [LfoShape; 3] = [LfoShape::Sine, LfoShape::Triangle, LfoShape::Square];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL.get(v as usize).copied().unwrap_or(LfoShape::Sine)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LfoShape::Sine => "sine",
+            LfoShape::Triangle => "triangle",
+            LfoShape::Square => "square",
+        }
+    }
+
+    /// Unipolar value (0..1) at `phase` (0..1), starting at the top of the cycle; `fast_math`
+    /// takes the sine from the polynomial approximation
+    pub fn unipolar(self, phase: f32, fast_math: bool) -> f32 {
+        match self {
+            LfoShape::Sine if fast_math => 0.5 + 0.5 * fast_sine((phase + 0.25) % 1.0),
+            LfoShape::Sine => 0.5 + 0.5 * (phase * TAU).cos(),
+            LfoShape::Triangle => (1.0 - 2.0 * phase).abs(),
+            LfoShape::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// Tremolo settings read once per audio buffer
+#[derive(Clone, Copy, Debug)]
+pub struct TremoloParams {
+    pub rate: f32,  // Hz (already converted from a tempo division when synced)
+    pub depth: f32, // 0 = no effect, 1 = LFO troughs are silent
+    pub shape: LfoShape,
+    pub fast_math: bool,
+}
+
+pub struct Tremolo {
+    phase: f32,
+    sample_rate: f32,
+}
+
+impl Tremolo {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            phase: 0.0,
+            sample_rate,
+        }
+    }
+
+    /// Gain for the next sample: 1 at LFO peaks, `1 - depth` at troughs
+    pub fn process(&mut self, p: &TremoloParams) -> f32 {
+        let g = 1.0 - p.depth * (1.0 - p.shape.unipolar(self.phase, p.fast_math));
+        self.phase = (self.phase + p.rate / self.sample_rate).fract();
+        g
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SR: f32 = 1_000.0;
+
+    #[test]
+    fn no_depth_is_exactly_unity() {
+        for shape in LfoShape::ALL {
+            let mut tremolo = Tremolo::new(SR);
+            let p = TremoloParams {
+                rate: 7.0,
+                depth: 0.0,
+                shape,
+                fast_math: false,
+            };
+            assert!(
+                (0..1_000).all(|_| tremolo.process(&p) == 1.0),
+                "{:?}",
+                shape
+            );
+        }
+    }
+
+    #[test]
+    fn a_full_depth_square_gates_half_of_each_cycle() {
+        // 256 samples a cycle, so the phase steps are exact: the first 128 open
+        let mut tremolo = Tremolo::new(1_024.0);
+        let p = TremoloParams {
+            rate: 4.0,
+            depth: 1.0,
+            shape: LfoShape::Square,
+            fast_math: false,
+        };
+        let gains: Vec<f32> = (0..1_024).map(|_| tremolo.process(&p)).collect();
+        for cycle in gains.chunks(256) {
+            assert!(cycle[..128].iter().all(|&g| g == 1.0));
+            assert!(cycle[128..].iter().all(|&g| g == 0.0));
+        }
+    }
+}
@@ -0,0 +1,830 @@
+// src/voice.rs
+// Polyphonic voice pool. A voice stays alive after note-off until its envelope has finished
+// the release stage, so release tails ring out instead of being cut.
+// The pool is allocated once when the stream is built; note handling never allocates.
+
+use crate::drift::Drift;
+use crate::envelope::Envelope;
+use crate::filter::Svf;
+use crate::glide::GlideMode;
+use crate::noise::Noise;
+use crate::pluck::Pluck;
+use crate::rng::{mix, Rng};
+
+/// Simultaneous voices; when all are busy the oldest one is stolen
+pub const MAX_VOICES: usize = 8;
+
+/// Key used by the UI gate's demo tone (outside the MIDI note range)
+pub const GATE_KEY: u8 = 128;
+
+/// Built-in seed of the humanize offsets
+const HUMANIZE_SEED: u32 = 0x6A09_E667;
+
+/// Built-in seed of voice `i`'s drift, noise and string excitation
+fn voice_seed(i: usize) -> u32 {
+    0x2545_F491 ^ (i as u32 + 1).wrapping_mul(0x9E37_79B9)
+}
+
+/// Chord played from a single key (chord mode); `Off` plays just the note
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChordType {
+    Off,
+    Major,
+    Minor,
+    Sus4,
+    Power,
+}
+
+impl ChordType {
+    pub const ALL: [ChordType; 5] = [
+        ChordType::Off,
+        ChordType::Major,
+        ChordType::Minor,
+        ChordType::Sus4,
+        ChordType::Power,
+    ];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL.get(v as usize).copied().unwrap_or(ChordType::Off)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ChordType::Off => "off",
+            ChordType::Major => "major",
+            ChordType::Minor => "minor",
+            ChordType::Sus4 => "sus4",
+            ChordType::Power => "power",
+        }
+    }
+
+    /// Semitones above the played note, one voice each
+    pub fn intervals(self) -> &'static [f32] {
+        match self {
+            ChordType::Off => &[0.0],
+            ChordType::Major => &[0.0, 4.0, 7.0],
+            ChordType::Minor => &[0.0, 3.0, 7.0],
+            ChordType::Sus4 => &[0.0, 5.0, 7.0],
+            ChordType::Power => &[0.0, 7.0, 12.0],
+        }
+    }
+}
+
+/// What generates a voice's raw signal
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VoiceEngine {
+    Oscillators,
+    /// Karplus-Strong plucked string
+    String,
+}
+
+impl VoiceEngine {
+    pub const ALL: [VoiceEngine; 2] = [VoiceEngine::Oscillators, VoiceEngine::String];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL
+            .get(v as usize)
+            .copied()
+            .unwrap_or(VoiceEngine::Oscillators)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            VoiceEngine::Oscillators => "oscillators",
+            VoiceEngine::String => "plucked string",
+        }
+    }
+}
+
+/// Which held note a monophonic voice plays
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NotePriority {
+    /// Most recently pressed
+    Last,
+    /// Lowest held (the classic bass-synth behavior)
+    Low,
+    /// Highest held
+    High,
+}
+
+impl NotePriority {
+    pub const ALL: [NotePriority; 3] = [NotePriority::Last, NotePriority::Low, NotePriority::High];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL
+            .get(v as usize)
+            .copied()
+            .unwrap_or(NotePriority::Last)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            NotePriority::Last => "last",
+            NotePriority::Low => "low",
+            NotePriority::High => "high",
+        }
+    }
+}
+
+/// Which voice a new note takes over when every voice is busy
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VoiceSteal {
+    /// The one started longest ago
+    Oldest,
+    /// The one whose envelope is lowest right now (usually the least noticeable cut)
+    Quietest,
+    /// The lowest-pitched
+    Lowest,
+    /// The highest-pitched
+    Highest,
+}
+
+impl VoiceSteal {
+    pub const ALL: [VoiceSteal; 4] = [
+        VoiceSteal::Oldest,
+        VoiceSteal::Quietest,
+        VoiceSteal::Lowest,
+        VoiceSteal::Highest,
+    ];
+
+    pub fn from_u8(v: u8) -> Self {
+        Self::ALL
+            .get(v as usize)
+            .copied()
+            .unwrap_or(VoiceSteal::Oldest)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            VoiceSteal::Oldest => "oldest",
+            VoiceSteal::Quietest => "quietest",
+            VoiceSteal::Lowest => "lowest",
+            VoiceSteal::Highest => "highest",
+        }
+    }
+
+    /// Index of the voice to steal among `voices`
+    fn victim(self, voices: &[Voice]) -> usize {
+        let by = |key: fn(&Voice) -> f32| {
+            (0..voices.len()).min_by(|&a, &b| key(&voices[a]).total_cmp(&key(&voices[b])))
+        };
+        match self {
+            VoiceSteal::Oldest => (0..voices.len()).min_by_key(|&i| voices[i].started),
+            VoiceSteal::Quietest => by(|v| v.env_out),
+            VoiceSteal::Lowest => by(|v| v.freq),
+            VoiceSteal::Highest => by(|v| -v.freq),
+        }
+        .unwrap_or(0)
+    }
+}
+
+/// Keys that can be held at once: every MIDI note plus the UI gate
+const MAX_HELD: usize = 129;
+
+/// A key held down in mono mode
+#[derive(Clone, Copy)]
+struct Held {
+    key: u8,
+    freq: f32,
+    velocity: f32,
+    slide: bool,
+}
+
+/// Note on/off messages queued for the audio thread. `slide` notes glide at the sequencer's
+/// slide time instead of the global glide.
+#[derive(Clone, Copy, Debug)]
+pub enum NoteEvent {
+    On {
+        key: u8,
+        freq: f32,
+        velocity: f32,
+        slide: bool,
+    },
+    Off {
+        key: u8,
+        velocity: f32,
+    },
+    // polyphonic aftertouch on a held key, 0..1
+    Pressure {
+        key: u8,
+        pressure: f32,
+    },
+}
+
+pub struct Voice {
+    pub key: u8,
+    // position within the key's chord, and its frequency ratio to the played note
+    pub chord_slot: u8,
+    pub chord_ratio: f32,
+    pub freq: f32,
+    // frequency actually sounding, gliding toward `freq` (portamento), and whether it glides
+    // at the sequencer's slide time rather than the global glide
+    pub glide_freq: f32,
+    pub slide: bool,
+    pub velocity: f32, // 0..1
+    // polyphonic aftertouch on this voice's key, 0..1; back to 0 on each new note
+    pub pressure: f32,
+    // note-off velocity (0..1, 0.5 = neutral), and the release-time multiplier derived from it
+    pub release_velocity: f32,
+    pub release_scale: f32,
+    // oscillator phases (A, B), 0..1; they run at different rates when B is transposed
+    pub phase: [f32; 2],
+    // oscillator A's completed cycles, counted for the sub-oscillator's divider
+    pub sub_count: u8,
+    // key is held (false once note-off arrives; the envelope then releases)
+    pub gate: bool,
+    pub env: Envelope,
+    // amplitude envelope as applied, after the anti-click slew (see `is_active`)
+    pub env_out: f32,
+    pub mod_env: Envelope,
+    pub pluck: Pluck,
+    pub filter: Svf,
+    pub filter2: Svf,
+    // right-channel copies of the two filters, running on their own only while phase width
+    // is on (otherwise they track the left ones)
+    pub filters_r: (Svf, Svf),
+    // the warmth low-pass, left and right (the right one runs only while phase width is on)
+    pub warmth: (Svf, Svf),
+    pub noise: Noise,
+    // this buffer's cutoffs for filter 1 and 2 before modulation-envelope offset
+    pub cutoffs: (f32, f32),
+    // analog-style pitch wander of oscillators A and B, seeded per voice and oscillator so
+    // voices and the two oscillators drift independently
+    pub drift: [Drift; 2],
+    pub drift_ratio: [f32; 2],
+    // humanize: a fixed random pitch offset (as a ratio) drawn each time the voice is triggered
+    pub humanize_ratio: f32,
+    // stereo position at full spread (-1..1), assigned at allocation, and the resulting gains
+    pub pan: f32,
+    pub pan_gains: (f32, f32),
+    // allocation order, for stealing the oldest voice
+    started: u64,
+}
+
+impl Voice {
+    fn new(seed: u32) -> Self {
+        Self {
+            key: 0,
+            chord_slot: 0,
+            chord_ratio: 1.0,
+            freq: 0.0,
+            glide_freq: 0.0,
+            slide: false,
+            velocity: 0.0,
+            pressure: 0.0,
+            release_velocity: 0.5,
+            release_scale: 1.0,
+            phase: [0.0; 2],
+            sub_count: 0,
+            gate: false,
+            env: Envelope::new(),
+            env_out: 0.0,
+            mod_env: Envelope::new(),
+            pluck: Pluck::new(seed.rotate_left(16)),
+            filter: Svf::new(),
+            filter2: Svf::new(),
+            filters_r: (Svf::new(), Svf::new()),
+            warmth: (Svf::new(), Svf::new()),
+            noise: Noise::new(seed.rotate_left(8)),
+            cutoffs: (0.0, 0.0),
+            drift: [Drift::new(seed), Drift::new(seed.rotate_left(24))],
+            drift_ratio: [1.0; 2],
+            humanize_ratio: 1.0,
+            pan: 0.0,
+            pan_gains: (1.0, 1.0),
+            started: 0,
+        }
+    }
+
+    /// Start the oscillators, and the sub's divider with them, from the top of their cycles
+    pub fn restart_phase(&mut self) {
+        self.phase = [0.0; 2];
+        self.sub_count = 0;
+    }
+
+    /// Held, or still sounding its release tail (including the anti-click fade after it)
+    pub fn is_active(&self) -> bool {
+        self.gate || !self.env.is_idle() || self.env_out > 0.0
+    }
+}
+
+/// Pan of the `i`th allocation at full spread: alternating sides and widening outwards
+/// (-1/4, +1/4, -1/2, +1/2, ...), so voices allocated together (a chord) land apart
+fn allocation_pan(i: usize) -> f32 {
+    let side = if i.is_multiple_of(2) { -1.0 } else { 1.0 };
+    side * (i / 2 + 1) as f32 / (MAX_VOICES / 2) as f32
+}
+
+pub struct VoicePool {
+    pub voices: Vec<Voice>,
+    counter: u64,
+    // last note played, where a fresh voice's glide starts (0 = none yet)
+    last_freq: f32,
+    // mono mode: keys held down, in press order (capacity reserved up front)
+    held: Vec<Held>,
+    // voices new notes may use (the rest finish their tails and stay silent)
+    limit: usize,
+    glide_mode: GlideMode,
+    steal: VoiceSteal,
+    // largest humanize offset, in cents (0 = every voice in tune), and its random source
+    humanize: f32,
+    rng: Rng,
+    // every note-on restarts the oscillators at phase 0; otherwise a retriggered voice runs on
+    reset_phase: bool,
+}
+
+impl VoicePool {
+    pub fn new() -> Self {
+        Self {
+            voices: (0..MAX_VOICES).map(|i| Voice::new(voice_seed(i))).collect(),
+            counter: 0,
+            last_freq: 0.0,
+            held: Vec::with_capacity(MAX_HELD),
+            limit: MAX_VOICES,
+            glide_mode: GlideMode::LastNote,
+            steal: VoiceSteal::Oldest,
+            humanize: 0.0,
+            rng: Rng::new(HUMANIZE_SEED),
+            reset_phase: false,
+        }
+    }
+
+    /// Restart every voice's random sources (drift, noise, string excitation) and the humanize
+    /// offsets under the global `seed` (see `rng::mix`)
+    pub fn reseed(&mut self, seed: u32) {
+        for (i, v) in self.voices.iter_mut().enumerate() {
+            let s = mix(seed, voice_seed(i));
+            v.drift[0].reseed(s);
+            v.drift[1].reseed(s.rotate_left(24));
+            v.noise.reseed(s.rotate_left(8));
+            v.pluck.reseed(s.rotate_left(16));
+        }
+        self.rng = Rng::new(mix(seed, HUMANIZE_SEED));
+    }
+
+    /// Where fresh voices start their glide from
+    pub fn set_glide_mode(&mut self, mode: GlideMode) {
+        self.glide_mode = mode;
+    }
+
+    /// Which voice a note takes when all are busy
+    pub fn set_steal(&mut self, steal: VoiceSteal) {
+        self.steal = steal;
+    }
+
+    /// Detune each note by a random amount of up to `cents` either way, drawn as it starts
+    pub fn set_humanize(&mut self, cents: f32) {
+        self.humanize = cents;
+    }
+
+    /// Restart the oscillator phases on every note-on, so each attack is the same. Legato
+    /// moves in mono mode don't retrigger, so they keep running either way.
+    pub fn set_reset_phase(&mut self, on: bool) {
+        self.reset_phase = on;
+    }
+
+    /// Cap polyphony at `limit` voices; notes held on voices past the cap are released
+    pub fn set_limit(&mut self, limit: usize) {
+        let limit = limit.clamp(1, MAX_VOICES);
+        if limit < self.limit {
+            for v in &mut self.voices[limit..] {
+                v.gate = false;
+            }
+        }
+        self.limit = limit;
+    }
+
+    /// Play `ev` polyphonically, or monophonically with the given note priority
+    pub fn handle(&mut self, ev: NoteEvent, chord: ChordType, mono: Option<NotePriority>) {
+        if let NoteEvent::Pressure { key, pressure } = ev {
+            // every voice of the key's chord, in any mode; released notes keep theirs
+            for v in self.voices.iter_mut().filter(|v| v.gate && v.key == key) {
+                v.pressure = pressure;
+            }
+            return;
+        }
+        let Some(priority) = mono else {
+            // held keys only matter in mono mode; start afresh if it's switched back on
+            self.held.clear();
+            match ev {
+                NoteEvent::On {
+                    key,
+                    freq,
+                    velocity,
+                    slide,
+                } => self.chord_on(key, freq, velocity, slide, chord),
+                NoteEvent::Off { key, velocity } => self.note_off(key, velocity),
+                NoteEvent::Pressure { .. } => {}
+            }
+            return;
+        };
+        match ev {
+            NoteEvent::On {
+                key,
+                freq,
+                velocity,
+                slide,
+            } => {
+                self.held.retain(|h| h.key != key);
+                if self.held.len() < MAX_HELD {
+                    self.held.push(Held {
+                        key,
+                        freq,
+                        velocity,
+                        slide,
+                    });
+                }
+                self.mono_select(priority, chord, true);
+            }
+            NoteEvent::Off { key, velocity } => {
+                self.held.retain(|h| h.key != key);
+                if self.held.is_empty() {
+                    for v in self.voices.iter_mut().filter(|v| v.gate) {
+                        v.gate = false;
+                        v.release_velocity = velocity;
+                    }
+                } else {
+                    self.mono_select(priority, chord, false);
+                }
+            }
+            NoteEvent::Pressure { .. } => {}
+        }
+    }
+
+    /// Sound the held note `priority` picks. A new key press retriggers the envelopes; going
+    /// back to a still-held key on release just retunes (legato), gliding if glide is on.
+    fn mono_select(&mut self, priority: NotePriority, chord: ChordType, retrigger: bool) {
+        let pick = match priority {
+            NotePriority::Last => self.held.last(),
+            NotePriority::Low => self.held.iter().min_by(|a, b| a.freq.total_cmp(&b.freq)),
+            NotePriority::High => self.held.iter().max_by(|a, b| a.freq.total_cmp(&b.freq)),
+        };
+        let Some(&Held {
+            key,
+            freq,
+            velocity,
+            slide,
+        }) = pick
+        else {
+            return;
+        };
+        let sounding = self.voices.iter().find(|v| v.gate).map(|v| v.key);
+        if sounding == Some(key) {
+            return;
+        }
+        if sounding.is_none() {
+            self.chord_on(key, freq, velocity, slide, chord);
+            return;
+        }
+        // keep the same voice(s), so there is never more than one note (or chord) at a time
+        for v in self.voices.iter_mut().filter(|v| v.gate) {
+            v.key = key;
+            // the pressure was the old key's
+            v.pressure = 0.0;
+            v.freq = freq * v.chord_ratio;
+            v.slide = slide;
+            if retrigger {
+                if self.reset_phase {
+                    v.restart_phase();
+                }
+                v.velocity = velocity;
+                v.env.trigger();
+                v.mod_env.trigger();
+                v.pluck.pluck();
+            }
+        }
+        self.last_freq = freq;
+    }
+
+    /// Start one voice per chord interval, all under `key` so note-off releases them together
+    pub fn chord_on(&mut self, key: u8, freq: f32, velocity: f32, slide: bool, chord: ChordType) {
+        for (slot, &semis) in chord.intervals().iter().enumerate() {
+            let ratio = 2f32.powf(semis / 12.0);
+            self.note_on(key, slot as u8, ratio, freq, velocity, slide);
+        }
+        self.last_freq = freq;
+    }
+
+    /// Start a note: retrigger the voice already holding `key` (and chord slot), else take a
+    /// silent voice, else steal one as the steal strategy picks
+    fn note_on(&mut self, key: u8, slot: u8, ratio: f32, freq: f32, velocity: f32, slide: bool) {
+        let usable = &self.voices[..self.limit];
+        let idx = usable
+            .iter()
+            .position(|v| v.gate && v.key == key && v.chord_slot == slot)
+            .or_else(|| usable.iter().position(|v| !v.is_active()))
+            .unwrap_or_else(|| self.steal.victim(usable));
+
+        let target = freq * ratio;
+        let glide_from = match self.glide_mode {
+            GlideMode::LastNote if self.last_freq > 0.0 => self.last_freq * ratio,
+            GlideMode::LastNote => target,
+            // the closest pitch (in octaves) among the other notes still sounding
+            GlideMode::Nearest => self
+                .voices
+                .iter()
+                .enumerate()
+                .filter(|&(i, v)| i != idx && v.is_active() && !(v.gate && v.key == key))
+                .map(|(_, v)| v.glide_freq)
+                .min_by(|a, b| (a / target).ln().abs().total_cmp(&(b / target).ln().abs()))
+                .unwrap_or(target),
+        };
+
+        let humanize_ratio = if self.humanize > 0.0 {
+            2f32.powf(self.rng.bipolar() * self.humanize / 1200.0)
+        } else {
+            1.0
+        };
+        self.counter += 1;
+        let v = &mut self.voices[idx];
+        if !v.is_active() {
+            // fresh voice: start the waveform and filter from rest
+            v.restart_phase();
+            v.filter = Svf::new();
+            v.filter2 = Svf::new();
+            v.filters_r = (Svf::new(), Svf::new());
+            v.warmth = (Svf::new(), Svf::new());
+            v.noise.reset();
+            v.pan = allocation_pan(self.counter as usize % MAX_VOICES);
+            // glide in per the glide mode; a busy voice glides from where it is
+            v.glide_freq = glide_from;
+        }
+        v.key = key;
+        v.chord_slot = slot;
+        v.chord_ratio = ratio;
+        v.freq = target;
+        v.velocity = velocity;
+        v.pressure = 0.0;
+        v.humanize_ratio = humanize_ratio;
+        if self.reset_phase {
+            v.restart_phase();
+        }
+        v.slide = slide;
+        v.gate = true;
+        v.started = self.counter;
+        v.env.trigger();
+        v.mod_env.trigger();
+        v.pluck.pluck();
+    }
+
+    /// Release every held voice playing `key`; they keep sounding through their release
+    pub fn note_off(&mut self, key: u8, velocity: f32) {
+        for v in self.voices.iter_mut().filter(|v| v.gate && v.key == key) {
+            v.gate = false;
+            v.release_velocity = velocity;
+        }
+    }
+
+    pub fn active_count(&self) -> usize {
+        self.voices.iter().filter(|v| v.is_active()).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn on(pool: &mut VoicePool, key: u8, priority: NotePriority) {
+        let freq = 440.0 * 2f32.powf((key as f32 - 69.0) / 12.0);
+        let ev = NoteEvent::On {
+            key,
+            freq,
+            velocity: 1.0,
+            slide: false,
+        };
+        pool.handle(ev, ChordType::Off, Some(priority));
+    }
+
+    fn off(pool: &mut VoicePool, key: u8, priority: NotePriority) {
+        let ev = NoteEvent::Off { key, velocity: 0.5 };
+        pool.handle(ev, ChordType::Off, Some(priority));
+    }
+
+    /// Key of the one gated voice (asserting there is exactly one), or None
+    fn sounding(pool: &VoicePool) -> Option<u8> {
+        let gated: Vec<u8> = pool
+            .voices
+            .iter()
+            .filter(|v| v.gate)
+            .map(|v| v.key)
+            .collect();
+        assert!(gated.len() <= 1, "{:?}", gated);
+        gated.first().copied()
+    }
+
+    /// Start `key` polyphonically and return the frequency its glide starts from
+    fn poly_glide_start(pool: &mut VoicePool, key: u8) -> f32 {
+        let freq = 440.0 * 2f32.powf((key as f32 - 69.0) / 12.0);
+        let ev = NoteEvent::On {
+            key,
+            freq,
+            velocity: 1.0,
+            slide: false,
+        };
+        pool.handle(ev, ChordType::Off, None);
+        pool.voices
+            .iter()
+            .find(|v| v.gate && v.key == key)
+            .unwrap()
+            .glide_freq
+    }
+
+    #[test]
+    fn each_oscillator_drifts_on_its_own() {
+        let mut pool = VoicePool::new();
+        pool.reseed(7);
+        let v = &mut pool.voices[0];
+        let (a, b): (Vec<f32>, Vec<f32>) = (0..20)
+            .map(|_| (v.drift[0].advance(0.1), v.drift[1].advance(0.1)))
+            .unzip();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn nearest_glide_starts_from_the_closest_sounding_voice() {
+        let mut pool = VoicePool::new();
+        pool.set_glide_mode(GlideMode::Nearest);
+        // nothing sounding: straight to the target
+        assert_eq!(poly_glide_start(&mut pool, 57), 220.0);
+        assert_eq!(poly_glide_start(&mut pool, 81), 220.0);
+        // let the glides land, then: E5 (659 Hz) is nearer 880 than 220, in octaves
+        for v in &mut pool.voices {
+            v.glide_freq = v.freq;
+        }
+        assert_eq!(poly_glide_start(&mut pool, 76), 880.0);
+
+        // once everything has gone quiet, the next note starts at its own pitch again
+        let mut pool = VoicePool::new();
+        pool.set_glide_mode(GlideMode::Nearest);
+        poly_glide_start(&mut pool, 57);
+        pool.note_off(57, 0.5);
+        for v in &mut pool.voices {
+            v.env = Envelope::new();
+        }
+        assert_eq!(poly_glide_start(&mut pool, 69), 440.0);
+    }
+
+    #[test]
+    fn last_note_glide_remembers_the_previous_note() {
+        let mut pool = VoicePool::new();
+        assert_eq!(poly_glide_start(&mut pool, 57), 220.0);
+        pool.note_off(57, 0.5);
+        for v in &mut pool.voices {
+            v.env = Envelope::new();
+        }
+        assert_eq!(poly_glide_start(&mut pool, 69), 220.0);
+    }
+
+    #[test]
+    fn last_priority_follows_presses_and_falls_back() {
+        let mut pool = VoicePool::new();
+        let p = NotePriority::Last;
+        on(&mut pool, 60, p);
+        on(&mut pool, 64, p);
+        on(&mut pool, 62, p);
+        assert_eq!(sounding(&pool), Some(62));
+        off(&mut pool, 62, p);
+        assert_eq!(sounding(&pool), Some(64));
+        off(&mut pool, 60, p);
+        assert_eq!(sounding(&pool), Some(64));
+        off(&mut pool, 64, p);
+        assert_eq!(sounding(&pool), None);
+    }
+
+    #[test]
+    fn low_and_high_priority_pick_by_pitch() {
+        let mut pool = VoicePool::new();
+        let p = NotePriority::Low;
+        on(&mut pool, 48, p);
+        on(&mut pool, 55, p);
+        assert_eq!(sounding(&pool), Some(48));
+        on(&mut pool, 43, p);
+        assert_eq!(sounding(&pool), Some(43));
+        off(&mut pool, 43, p);
+        assert_eq!(sounding(&pool), Some(48));
+
+        let mut pool = VoicePool::new();
+        let p = NotePriority::High;
+        on(&mut pool, 60, p);
+        on(&mut pool, 67, p);
+        on(&mut pool, 64, p);
+        assert_eq!(sounding(&pool), Some(67));
+        off(&mut pool, 67, p);
+        assert_eq!(sounding(&pool), Some(64));
+    }
+
+    #[test]
+    fn humanize_detunes_each_trigger_within_its_range() {
+        let ratios = |cents: f32| {
+            let mut pool = VoicePool::new();
+            pool.set_humanize(cents);
+            for key in [60, 64, 67, 72] {
+                let ev = NoteEvent::On {
+                    key,
+                    freq: 440.0,
+                    velocity: 1.0,
+                    slide: false,
+                };
+                pool.handle(ev, ChordType::Off, None);
+            }
+            pool.voices
+                .iter()
+                .filter(|v| v.gate)
+                .map(|v| v.humanize_ratio)
+                .collect::<Vec<f32>>()
+        };
+        assert!(ratios(0.0).iter().all(|&r| r == 1.0));
+        let detuned = ratios(10.0);
+        assert!(detuned.iter().all(|r| (1200.0 * r.log2()).abs() <= 10.0));
+        assert!(detuned.windows(2).all(|w| w[0] != w[1]));
+    }
+
+    #[test]
+    fn phase_reset_restarts_a_retriggered_voice() {
+        let phases = |reset: bool, mono: Option<NotePriority>| {
+            let mut pool = VoicePool::new();
+            pool.set_reset_phase(reset);
+            let mut starts = Vec::new();
+            // in mono mode the second and third notes retrigger the one voice; polyphonically
+            // the third lands on the voice still holding its key
+            for key in [36, 38, 36] {
+                let ev = NoteEvent::On {
+                    key,
+                    freq: 65.0,
+                    velocity: 1.0,
+                    slide: false,
+                };
+                pool.handle(ev, ChordType::Off, mono);
+                let v = pool.voices.iter_mut().find(|v| v.gate && v.key == key);
+                let v = v.unwrap();
+                starts.push((v.phase, v.sub_count));
+                v.phase = [0.3, 0.7];
+                v.sub_count = 3;
+            }
+            starts
+        };
+        for mono in [None, Some(NotePriority::Last)] {
+            assert!(phases(true, mono).iter().all(|&p| p == ([0.0; 2], 0)));
+            assert!(phases(false, mono).contains(&([0.3, 0.7], 3)));
+        }
+    }
+
+    #[test]
+    fn each_steal_strategy_picks_its_victim() {
+        let survivors = |steal: VoiceSteal| {
+            let mut pool = VoicePool::new();
+            pool.set_limit(3);
+            pool.set_steal(steal);
+            // the middle note is the oldest-but-one and the quietest
+            for (key, level) in [(64, 0.8), (60, 0.2), (67, 0.5), (62, 1.0)] {
+                let ev = NoteEvent::On {
+                    key,
+                    freq: 440.0 * 2f32.powf((key as f32 - 69.0) / 12.0),
+                    velocity: 1.0,
+                    slide: false,
+                };
+                pool.handle(ev, ChordType::Off, None);
+                let v = pool.voices.iter_mut().find(|v| v.gate && v.key == key);
+                v.unwrap().env_out = level;
+            }
+            let mut keys: Vec<u8> = pool
+                .voices
+                .iter()
+                .filter(|v| v.gate)
+                .map(|v| v.key)
+                .collect();
+            keys.sort();
+            keys
+        };
+        assert_eq!(survivors(VoiceSteal::Oldest), [60, 62, 67]);
+        assert_eq!(survivors(VoiceSteal::Quietest), [62, 64, 67]);
+        assert_eq!(survivors(VoiceSteal::Lowest), [62, 64, 67]);
+        assert_eq!(survivors(VoiceSteal::Highest), [60, 62, 64]);
+    }
+
+    #[test]
+    fn limit_caps_polyphony() {
+        let mut pool = VoicePool::new();
+        pool.set_limit(3);
+        for key in 60..66 {
+            let freq = 440.0 * 2f32.powf((key as f32 - 69.0) / 12.0);
+            let ev = NoteEvent::On {
+                key,
+                freq,
+                velocity: 1.0,
+                slide: false,
+            };
+            pool.handle(ev, ChordType::Off, None);
+        }
+        // the oldest notes were stolen; only the last three sound
+        let mut keys: Vec<u8> = pool
+            .voices
+            .iter()
+            .filter(|v| v.gate)
+            .map(|v| v.key)
+            .collect();
+        keys.sort();
+        assert_eq!(keys, [63, 64, 65]);
+        assert_eq!(pool.active_count(), 3);
+    }
+}
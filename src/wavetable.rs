@@ -0,0 +1,131 @@
+// src/wavetable.rs
+// Multi-frame wavetable oscillator source (Serum/Ableton style: single-cycle frames laid
+// end to end). Built on the UI thread, shared read-only with the audio callback. The built-in
+// saw uses its frames as a mip-map instead: one per octave, each with half the harmonics of
+// the one before, picked by pitch so no harmonic passes Nyquist.
+
+use std::f64::consts::{PI, TAU};
+
+use anyhow::Result;
+
+/// Samples per frame used by common wavetable editors
+pub const FRAME_LEN: usize = 2048;
+
+/// Harmonics in the fullest built-in saw frame (reaching about 20 kHz at 40 Hz)
+const SAW_HARMONICS: usize = 512;
+/// Frames of the built-in saw: 512 harmonics down to 1
+pub const SAW_LEVELS: usize = 10;
+
+pub struct Wavetable {
+    samples: Vec<f32>,
+    frames: usize,
+}
+
+impl Wavetable {
+    /// Split `samples` into `FRAME_LEN` frames; the length must be a whole number of frames
+    pub fn from_samples(samples: Vec<f32>) -> Result<Self> {
+        if samples.is_empty() || !samples.len().is_multiple_of(FRAME_LEN) {
+            return Err(anyhow::anyhow!(
+                "wavetable length {} is not a multiple of {} samples/frame",
+                samples.len(),
+                FRAME_LEN
+            ));
+        }
+        let frames = samples.len() / FRAME_LEN;
+        Ok(Self { samples, frames })
+    }
+
+    /// Built-in sawtooth rising from -1 to 1 over the cycle, summed from its harmonics: frame
+    /// `level` has `SAW_HARMONICS >> level` of them. Play it with `saw_level` and
+    /// `frame_sample`.
+    pub fn saw() -> Self {
+        let mut samples = Vec::with_capacity(SAW_LEVELS * FRAME_LEN);
+        for level in 0..SAW_LEVELS {
+            let harmonics = SAW_HARMONICS >> level;
+            samples.extend((0..FRAME_LEN).map(|n| {
+                // sin(kx) by the Chebyshev recurrence, far cheaper than a sin per harmonic
+                let x = TAU * n as f64 / FRAME_LEN as f64;
+                let (two_cos, mut prev, mut cur) = (2.0 * x.cos(), 0.0, x.sin());
+                let mut sum = 0.0;
+                for k in 1..=harmonics {
+                    sum += cur / k as f64;
+                    (prev, cur) = (cur, two_cos * cur - prev);
+                }
+                (-2.0 / PI * sum) as f32
+            }));
+        }
+        Self {
+            samples,
+            frames: SAW_LEVELS,
+        }
+    }
+
+    /// Frame of `saw()` to play at `step` cycles per sample: the fullest one whose top
+    /// harmonic stays under Nyquist
+    pub fn saw_level(step: f32) -> usize {
+        let over = (2.0 * SAW_HARMONICS as f32 * step).log2().ceil();
+        (over.max(0.0) as usize).min(SAW_LEVELS - 1)
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Linear interpolation within one frame at `phase` (0..1)
+    pub fn frame_sample(&self, frame: usize, phase: f32) -> f32 {
+        let table = &self.samples[frame * FRAME_LEN..(frame + 1) * FRAME_LEN];
+        let x = phase.rem_euclid(1.0) * FRAME_LEN as f32;
+        let i = (x as usize).min(FRAME_LEN - 1);
+        let frac = x - i as f32;
+        table[i] + (table[(i + 1) % FRAME_LEN] - table[i]) * frac
+    }
+
+    /// Sample at `phase` (0..1 within a cycle) and `position` (0..1 across frames),
+    /// interpolating between the two nearest frames
+    pub fn sample(&self, phase: f32, position: f32) -> f32 {
+        let f = position.clamp(0.0, 1.0) * (self.frames - 1) as f32;
+        let lo = f as usize;
+        let hi = (lo + 1).min(self.frames - 1);
+        let a = self.frame_sample(lo, phase);
+        let b = self.frame_sample(hi, phase);
+        a + (b - a) * (f - lo as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Amplitude of harmonic `k` in `frame` of `wt`
+    fn harmonic(wt: &Wavetable, frame: usize, k: usize) -> f32 {
+        let (re, im) = (0..FRAME_LEN).fold((0.0, 0.0), |(re, im), n| {
+            let x = wt.frame_sample(frame, n as f32 / FRAME_LEN as f32);
+            let a = TAU as f32 * (k * n) as f32 / FRAME_LEN as f32;
+            (re + x * a.cos(), im + x * a.sin())
+        });
+        2.0 * (re * re + im * im).sqrt() / FRAME_LEN as f32
+    }
+
+    #[test]
+    fn each_saw_level_stops_at_its_harmonic() {
+        let saw = Wavetable::saw();
+        for level in [0, 3, 6, 9] {
+            let top = SAW_HARMONICS >> level;
+            let expect = 2.0 / PI as f32 / top as f32;
+            assert!((harmonic(&saw, level, top) - expect).abs() < 0.1 * expect);
+            assert!(harmonic(&saw, level, top + 1) < 1e-4, "level {}", level);
+        }
+    }
+
+    #[test]
+    fn the_level_picked_keeps_every_harmonic_under_nyquist() {
+        for note in 0..=127 {
+            let step = 440.0 * 2f32.powf((note as f32 - 69.0) / 12.0) / 48_000.0;
+            let level = Wavetable::saw_level(step);
+            let top = |level: usize| (SAW_HARMONICS >> level) as f32 * step;
+            assert!(top(level) <= 0.5, "note {}", note);
+            // and it's the fullest one that does, short of the whole table
+            assert!(level == 0 || top(level - 1) > 0.5, "note {}", note);
+        }
+    }
+}